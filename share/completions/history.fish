@@ -1,5 +1,5 @@
 # Note that when a completion file is sourced a new block scope is created so `set -l` works.
-set -l __fish_history_all_commands search delete save merge clear clear-session append
+set -l __fish_history_all_commands search delete save merge clear clear-session append check repair export import
 
 complete -c history -s h -l help -d "Display help and exit"
 
@@ -10,6 +10,8 @@ complete -c history -n '__fish_seen_subcommand_from search delete; or not __fish
     -s c -l contains -d "Match items containing the string"
 complete -c history -n '__fish_seen_subcommand_from search delete; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
     -s e -l exact -d "Match items identical to the string"
+complete -c history -n '__fish_seen_subcommand_from search delete; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l regex -d "Match items with a regular expression"
 complete -c history -n '__fish_seen_subcommand_from search delete; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
     -s t -l show-time -d "Output with timestamps"
 complete -c history -n '__fish_seen_subcommand_from search delete; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
@@ -22,6 +24,21 @@ complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_s
     -s z -l null -d "Terminate entries with NUL character"
 complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
     -s R -l reverse -d "Output the oldest results first" -x
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l status -d "Only match commands that exited with the given status" -x
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l cwd -d "Only match commands run in the given directory" -xa '(__fish_complete_directories)'
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l session -d "Only match commands from the given session" -x
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l show-details -d "Output with exit status, duration and directory"
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l since -d "Only match commands run at or after the given time" -xa "1h 1d 1w"
+complete -c history -n '__fish_seen_subcommand_from search; or not __fish_seen_subcommand_from $__fish_history_all_commands' \
+    -l until -d "Only match commands run before the given time" -xa "1h 1d 1w"
+
+complete -c history -n '__fish_seen_subcommand_from export import' \
+    -l format -d "Format of the history" -xa "fish json bash zsh"
 
 # We don't include a completion for the "save" subcommand because it should not be used
 # interactively.
@@ -37,3 +54,11 @@ complete -f -c history -n "not __fish_seen_subcommand_from $__fish_history_all_c
     -a clear-session -d "Clears all history from the current session"
 complete -f -c history -n "not __fish_seen_subcommand_from $__fish_history_all_commands" \
     -a append -d "Appends commands to the history without needing to execute them"
+complete -f -c history -n "not __fish_seen_subcommand_from $__fish_history_all_commands" \
+    -a check -d "Reports damaged entries in the history file"
+complete -f -c history -n "not __fish_seen_subcommand_from $__fish_history_all_commands" \
+    -a repair -d "Rewrites the history file without damaged entries"
+complete -f -c history -n "not __fish_seen_subcommand_from $__fish_history_all_commands" \
+    -a export -d "Writes the history to stdout in fish or JSON format"
+complete -c history -n "not __fish_seen_subcommand_from $__fish_history_all_commands" \
+    -a import -d "Adds commands from a history file to the history"
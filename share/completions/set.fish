@@ -54,14 +54,23 @@ function __fish_complete_special_vars
         FISH_DEBUG_OUTPUT "debug output path" \
         umask "current file creation mask" \
         fish_handle_reflow "if fish should repaint prompt when the term resizes" \
+        fish_parse_error_hint "describe syntax errors below the commandline" \
+        fish_terminal_clipboard "copy/paste the killring via the terminal" \
         fish_trace "print cmds as they execute, like set -x" \
         fish_emoji_width "cols wide fish assumes emoji render as" \
         fish_key_bindings "name of function that sets binds" \
+        fish_async_prompt "show the previous prompt until it is updated" \
+        fish_autopair "pair brackets and quotes while typing" \
         fish_autosuggestion_enabled "turns autosuggestions on or off" \
+        fish_autosuggestion_sources "where autosuggestions come from, best first" \
         fish_ambiguous_width "affects computed width of east asian chars" \
         fish_escape_delay_ms "How long fish waits to distinguish escape and alt" \
         fish_greeting "The message to display at start (also a function)" \
         fish_history "The session id to store history under" \
+        fish_history_max_age "Max age of history entries to keep" \
+        fish_history_max_items "Max number of history entries to keep" \
+        fish_history_secret_patterns "Regexes for commands to keep out of the history file" \
+        fish_history_share "Share history with concurrent sessions" \
         fish_trace "Enables execution tracing (if set to non-empty value)" \
         fish_user_paths "A list of dirs to prepend to PATH"
 end
@@ -211,12 +211,6 @@ end" >$__fish_config_dir/config.fish
         end
     end
 
-    # Notify terminals when $PWD changes via OSC 7 (issue #906).
-    function __fish_update_cwd_osc --on-variable PWD --description 'Notify terminals when $PWD changes'
-        printf \e\]7\;file://%s%s\a $hostname (string escape --style=url -- $PWD)
-    end
-    __fish_update_cwd_osc # Run once because we might have already inherited a PWD from an old tab
-
     # Bump this whenever some code below needs to run once when upgrading to a new version.
     # The universal variable __fish_initialized is initialized in share/config.fish.
     set __fish_initialized 3800
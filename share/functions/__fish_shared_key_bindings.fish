@@ -63,6 +63,10 @@ function __fish_shared_key_bindings -d "Bindings shared between emacs and vi mod
     # https://github.com/fish-shell/fish-shell/issues/89
     bind --preset $argv alt-. history-token-search-backward
 
+    # Only do something while the history pager is open.
+    bind --preset $argv alt-r history-pager-toggle-regex
+    bind --preset $argv alt-z history-pager-toggle-fuzzy
+
     bind --preset $argv alt-l __fish_list_current_token
     bind --preset $argv alt-o __fish_preview_current_file
     bind --preset $argv alt-w __fish_whatis_current_token
@@ -79,6 +83,9 @@ function __fish_shared_key_bindings -d "Bindings shared between emacs and vi mod
 
     bind --preset $argv alt-s 'for cmd in sudo doas please; if command -q $cmd; fish_commandline_prepend $cmd; break; end; end'
 
+    # Fix a misspelled command, like "gti" instead of "git" ("did you mean").
+    bind --preset $argv alt-m correct-command
+
     # Allow reading manpages by pressing f1 (many GUI applications) or Alt+h (like in zsh).
     bind --preset $argv f1 __fish_man_page
     $legacy_bind --preset $argv -k f1 __fish_man_page
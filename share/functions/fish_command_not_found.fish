@@ -13,6 +13,10 @@ end
 
 function __fish_default_command_not_found_handler
     printf (_ "fish: Unknown command: %s\n") (string escape -- $argv[1]) >&2
+    set -l suggestions (type --suggest -- $argv[1])
+    if set -q suggestions[1]
+        printf (_ "Did you mean: %s?\n") (string join ", " -- $suggestions) >&2
+    end
 end
 
 # If an old handler already exists, defer to that.
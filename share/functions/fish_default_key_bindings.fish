@@ -62,6 +62,11 @@ function fish_default_key_bindings -d "emacs-like key binds"
     bind --preset $argv alt-b prevd-or-backward-word
     bind --preset $argv alt-f nextd-or-forward-word
 
+    # alt-3 ctrl-w deletes three path components.
+    for digit in 0 1 2 3 4 5 6 7 8 9
+        bind --preset $argv alt-$digit digit-argument
+    end
+
     bind --preset $argv alt-\< beginning-of-buffer
     bind --preset $argv alt-\> end-of-buffer
 
@@ -131,65 +131,20 @@ function fish_vi_key_bindings --description 'vi-like key bindings for fish'
     bind -s --preset -M insert ctrl-h backward-delete-char
     bind -s --preset -M default ctrl-h backward-char
 
-    bind -s --preset d,d kill-whole-line
+    # A count repeats the following command, or multiplies the count of an operator's motion.
+    # "0" continues a count, and otherwise moves to the start of the line.
+    for digit in 1 2 3 4 5 6 7 8 9
+        bind -s --preset $digit digit-argument
+    end
+
+    # The d, c and y operators read their motion, like "w", "3e", "f," or "iw", themselves.
+    bind -s --preset d vi-delete
     bind -s --preset D kill-line
-    bind -s --preset d,\$ kill-line
-    bind -s --preset d,\^ backward-kill-line
-    bind -s --preset d,0 backward-kill-line
-    bind -s --preset d,w kill-word
-    bind -s --preset d,W kill-bigword
-    bind -s --preset d,i,w forward-single-char forward-single-char backward-word kill-word
-    bind -s --preset d,i,W forward-single-char forward-single-char backward-bigword kill-bigword
-    bind -s --preset d,a,w forward-single-char forward-single-char backward-word kill-word
-    bind -s --preset d,a,W forward-single-char forward-single-char backward-bigword kill-bigword
-    bind -s --preset d,e kill-word
-    bind -s --preset d,E kill-bigword
-    bind -s --preset d,b backward-kill-word
-    bind -s --preset d,B backward-kill-bigword
-    bind -s --preset d,g,e backward-kill-word
-    bind -s --preset d,g,E backward-kill-bigword
-    bind -s --preset d,f begin-selection forward-jump kill-selection end-selection
-    bind -s --preset d,t begin-selection forward-jump backward-char kill-selection end-selection
-    bind -s --preset d,F begin-selection backward-jump kill-selection end-selection
-    bind -s --preset d,T begin-selection backward-jump forward-single-char kill-selection end-selection
-    bind -s --preset d,h backward-char delete-char
-    bind -s --preset d,l delete-char
-    bind -s --preset d,i,b jump-till-matching-bracket and jump-till-matching-bracket and begin-selection jump-till-matching-bracket kill-selection end-selection
-    bind -s --preset d,a,b jump-to-matching-bracket and jump-to-matching-bracket and begin-selection jump-to-matching-bracket kill-selection end-selection
-    bind -s --preset d,i backward-jump-till and repeat-jump-reverse and begin-selection repeat-jump kill-selection end-selection
-    bind -s --preset d,a backward-jump and repeat-jump-reverse and begin-selection repeat-jump kill-selection end-selection
-    bind -s --preset 'd,;' begin-selection repeat-jump kill-selection end-selection
-    bind -s --preset 'd,comma' begin-selection repeat-jump-reverse kill-selection end-selection
 
     bind -s --preset -m insert s delete-char repaint-mode
     bind -s --preset -m insert S kill-inner-line repaint-mode
-    bind -s --preset -m insert c,c kill-inner-line repaint-mode
+    bind -s --preset c vi-change and 'set fish_bind_mode insert' repaint-mode
     bind -s --preset -m insert C kill-line repaint-mode
-    bind -s --preset -m insert c,\$ kill-line repaint-mode
-    bind -s --preset -m insert c,\^ backward-kill-line repaint-mode
-    bind -s --preset -m insert c,0 backward-kill-line repaint-mode
-    bind -s --preset -m insert c,w kill-word repaint-mode
-    bind -s --preset -m insert c,W kill-bigword repaint-mode
-    bind -s --preset -m insert c,i,w forward-single-char forward-single-char backward-word kill-word repaint-mode
-    bind -s --preset -m insert c,i,W forward-single-char forward-single-char backward-bigword kill-bigword repaint-mode
-    bind -s --preset -m insert c,a,w forward-single-char forward-single-char backward-word kill-word repaint-mode
-    bind -s --preset -m insert c,a,W forward-single-char forward-single-char backward-bigword kill-bigword repaint-mode
-    bind -s --preset -m insert c,e kill-word repaint-mode
-    bind -s --preset -m insert c,E kill-bigword repaint-mode
-    bind -s --preset -m insert c,b backward-kill-word repaint-mode
-    bind -s --preset -m insert c,B backward-kill-bigword repaint-mode
-    bind -s --preset -m insert c,g,e backward-kill-word repaint-mode
-    bind -s --preset -m insert c,g,E backward-kill-bigword repaint-mode
-    bind -s --preset -m insert c,f begin-selection forward-jump kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,t begin-selection forward-jump backward-char kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,F begin-selection backward-jump kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,T begin-selection backward-jump forward-single-char kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,h backward-char begin-selection kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,l begin-selection kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,i,b jump-till-matching-bracket and jump-till-matching-bracket and begin-selection jump-till-matching-bracket kill-selection end-selection
-    bind -s --preset -m insert c,a,b jump-to-matching-bracket and jump-to-matching-bracket and begin-selection jump-to-matching-bracket kill-selection end-selection
-    bind -s --preset -m insert c,i backward-jump-till and repeat-jump-reverse and begin-selection repeat-jump kill-selection end-selection repaint-mode
-    bind -s --preset -m insert c,a backward-jump and repeat-jump-reverse and begin-selection repeat-jump kill-selection end-selection repaint-mode
 
     bind -s --preset '~' togglecase-char forward-single-char
     bind -s --preset g,u downcase-word
@@ -198,36 +153,11 @@ function fish_vi_key_bindings --description 'vi-like key bindings for fish'
     bind -s --preset J end-of-line delete-char
     bind -s --preset K 'man (commandline -t) 2>/dev/null; or echo -n \a'
 
-    bind -s --preset yy kill-whole-line yank
     for seq in '",*,y,y' '",*,Y' '",+,y,y' '",+,Y'
         bind -s --preset $seq fish_clipboard_copy
     end
+    bind -s --preset y vi-yank
     bind -s --preset Y kill-whole-line yank
-    bind -s --preset y,\$ kill-line yank
-    bind -s --preset y,\^ backward-kill-line yank
-    bind -s --preset y,0 backward-kill-line yank
-    bind -s --preset y,w kill-word yank
-    bind -s --preset y,W kill-bigword yank
-    bind -s --preset y,i,w forward-single-char forward-single-char backward-word kill-word yank
-    bind -s --preset y,i,W forward-single-char forward-single-char backward-bigword kill-bigword yank
-    bind -s --preset y,a,w forward-single-char forward-single-char backward-word kill-word yank
-    bind -s --preset y,a,W forward-single-char forward-single-char backward-bigword kill-bigword yank
-    bind -s --preset y,e kill-word yank
-    bind -s --preset y,E kill-bigword yank
-    bind -s --preset y,b backward-kill-word yank
-    bind -s --preset y,B backward-kill-bigword yank
-    bind -s --preset y,g,e backward-kill-word yank
-    bind -s --preset y,g,E backward-kill-bigword yank
-    bind -s --preset y,f begin-selection forward-jump kill-selection yank end-selection
-    bind -s --preset y,t begin-selection forward-jump-till kill-selection yank end-selection
-    bind -s --preset y,F begin-selection backward-jump kill-selection yank end-selection
-    bind -s --preset y,T begin-selection backward-jump-till kill-selection yank end-selection
-    bind -s --preset y,h backward-char begin-selection kill-selection yank end-selection
-    bind -s --preset y,l begin-selection kill-selection yank end-selection
-    bind -s --preset y,i,b jump-till-matching-bracket and jump-till-matching-bracket and begin-selection jump-till-matching-bracket kill-selection yank end-selection
-    bind -s --preset y,a,b jump-to-matching-bracket and jump-to-matching-bracket and begin-selection jump-to-matching-bracket kill-selection yank end-selection
-    bind -s --preset y,i backward-jump-till and repeat-jump-reverse and begin-selection repeat-jump kill-selection yank end-selection
-    bind -s --preset y,a backward-jump and repeat-jump-reverse and begin-selection repeat-jump kill-selection yank end-selection
 
     bind -s --preset % jump-to-matching-bracket
     bind -s --preset f forward-jump
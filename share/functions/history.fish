@@ -3,9 +3,10 @@
 #
 function history --description "display or manipulate interactive command history"
     set -l cmd history
-    set -l options --exclusive 'c,e,p' --exclusive 'S,D,M,V,X'
-    set -a options h/help c/contains e/exact p/prefix
+    set -l options --exclusive 'c,e,p,regex' --exclusive 'S,D,M,V,X'
+    set -a options h/help c/contains e/exact p/prefix regex
     set -a options C/case-sensitive R/reverse z/null 't/show-time=?' 'n#max'
+    set -a options 'status=' 'cwd=?' 'session=?' show-details 'since=' 'until=' 'format='
     # The following options are deprecated and will be removed in the next major release.
     # Note that they do not have usable short flags.
     set -a options S-search D-delete M-merge V-save X-clear
@@ -38,6 +39,31 @@ function history --description "display or manipulate interactive command histor
     and set -l search_mode --contains
     set -q _flag_exact
     and set -l search_mode --exact
+    set -q _flag_regex
+    and set -l search_mode --regex
+
+    set -l filters
+    set -q _flag_status
+    and set -a filters --status=$_flag_status
+    if set -q _flag_cwd[1]
+        set -a filters --cwd=$_flag_cwd
+    else if set -q _flag_cwd
+        set -a filters --cwd
+    end
+    if set -q _flag_session[1]
+        set -a filters --session=$_flag_session
+    else if set -q _flag_session
+        set -a filters --session
+    end
+    set -q _flag_show_details
+    and set -a filters --show-details
+    set -q _flag_since
+    and set -a filters --since=$_flag_since
+    set -q _flag_until
+    and set -a filters --until=$_flag_until
+    set -l format
+    set -q _flag_format
+    and set format --format=$_flag_format
 
     if set -q _flag_delete
         set hist_cmd delete
@@ -57,7 +83,7 @@ function history --description "display or manipulate interactive command histor
     # command. This allows the flags to appear before or after the subcommand.
     if not set -q hist_cmd[1]
         and set -q argv[1]
-        if contains $argv[1] search delete merge save clear clear-session append
+        if contains $argv[1] search delete merge save clear clear-session append check repair export import
             set hist_cmd $argv[1]
             set -e argv[1]
         end
@@ -86,12 +112,18 @@ function history --description "display or manipulate interactive command histor
                 not set -qx LV # ask the pager lv not to strip colors
                 and set -fx LV -c
 
-                builtin history search $search_mode $show_time $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv | $pager
+                builtin history search $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv | $pager
             else
-                builtin history search $search_mode $show_time $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+                builtin history search $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
             end
 
         case delete # interactively delete history
+            # Items are deleted by their text alone, so let the builtin reject any filters.
+            if set -q filters[1]
+                builtin history delete $filters -- $argv
+                return
+            end
+
             set -l searchterm $argv
             if not set -q argv[1]
                 read -P"Search term: " searchterm
@@ -176,12 +208,13 @@ function history --description "display or manipulate interactive command histor
             end
 
         case save # save our interactive command history to the persistent history
-            builtin history save $search_mode $show_time $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+            builtin history save $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
         case merge # merge the persistent interactive command history with our history
-            builtin history merge $search_mode $show_time $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+            builtin history merge $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
         case clear # clear the interactive command history
             if test -n "$search_mode"
                 or set -q show_time[1]
+                or set -q filters[1]
                 printf (_ "%ls: %ls: subcommand takes no options\n") history $hist_cmd >&2
                 return 1
             end
@@ -199,8 +232,16 @@ function history --description "display or manipulate interactive command histor
                 printf (_ "You did not say 'yes' so I will not clear your command history\n")
             end
         case clear-session # clears only session
-            builtin history clear-session $search_mode $show_time $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+            builtin history clear-session $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
             and printf (_ "Command history for session cleared!\n")
+        case check # report damaged entries in the history file
+            builtin history check $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+        case repair # rewrite the history file without damaged entries
+            builtin history repair $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null -- $argv
+        case export # write the history in a format other programs can read
+            builtin history export $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null $format -- $argv
+        case import # add commands from other shells or fish installations to the history
+            builtin history import $search_mode $show_time $filters $max_count $_flag_case_sensitive $_flag_reverse $_flag_null $format -- $argv
         case append
             set -l newitem $argv
             if not set -q argv[1]
@@ -208,6 +208,21 @@ pub fn has_match(&self, token: &wstr, position: Position, cmd: &wstr) -> bool {
             .any(|abbr| abbr.matches(token, position, cmd))
     }
 
+    /// Return the shortest literal key that is longer than `prefix` and starts with it, of the
+    /// abbreviations that could expand at the given position. This is used for autosuggestions.
+    pub fn complete_key(&self, prefix: &wstr, position: Position, cmd: &wstr) -> Option<&wstr> {
+        self.abbrs
+            .iter()
+            .filter(|abbr| {
+                !abbr.is_regex()
+                    && abbr.key.len() > prefix.len()
+                    && abbr.key.starts_with(prefix)
+                    && abbr.matches(&abbr.key, position, cmd)
+            })
+            .map(|abbr| abbr.key.as_utfstr())
+            .min_by_key(|key| key.len())
+    }
+
     /// Add an abbreviation. Any abbreviation with the same name is replaced.
     pub fn add(&mut self, abbr: Abbreviation) {
         assert!(!abbr.name.is_empty(), "Invalid name");
@@ -277,6 +292,14 @@ pub fn abbrs_match(token: &wstr, position: Position, cmd: &wstr) -> Vec<Replacer
         .collect()
 }
 
+/// Return the shortest abbreviation key that extends `prefix`, using the global set.
+pub fn abbrs_complete_key(prefix: &wstr, position: Position, cmd: &wstr) -> Option<WString> {
+    with_abbrs(|set| {
+        set.complete_key(prefix, position, cmd)
+            .map(|key| key.to_owned())
+    })
+}
+
 #[test]
 #[serial]
 fn rename_abbrs() {
@@ -315,3 +338,41 @@ fn rename_abbrs() {
         assert!(!abbrs_g.erase(L!("gcc")));
     })
 }
+
+#[test]
+fn complete_abbr_keys() {
+    use crate::abbrs::{Abbreviation, AbbreviationSet, Position};
+    use crate::wchar::prelude::*;
+
+    let mut set = AbbreviationSet::default();
+    let mut add = |name: &wstr, position: Position| {
+        set.add(Abbreviation::new(
+            name.into(),
+            name.into(),
+            L!("replacement").into(),
+            position,
+            false,
+        ))
+    };
+    add(L!("gco"), Position::Command);
+    add(L!("gcommit"), Position::Command);
+    add(L!("yin"), Position::Anywhere);
+
+    assert_eq!(
+        set.complete_key(L!("gc"), Position::Command, L!("")),
+        Some(L!("gco"))
+    );
+    assert_eq!(
+        set.complete_key(L!("gcom"), Position::Command, L!("")),
+        Some(L!("gcommit"))
+    );
+    assert_eq!(
+        set.complete_key(L!("gcommit"), Position::Command, L!("")),
+        None
+    );
+    assert_eq!(set.complete_key(L!("gc"), Position::Anywhere, L!("")), None);
+    assert_eq!(
+        set.complete_key(L!("y"), Position::Anywhere, L!("echo")),
+        Some(L!("yin"))
+    );
+}
@@ -1,8 +1,21 @@
 //! Implementation of the history builtin.
 
+use crate::common::{read_blocked, str2wcstring};
+use crate::env::Environment;
+use crate::fds::wopen_cloexec;
 use crate::history::in_private_mode;
-use crate::history::{self, history_session_id, History};
+use crate::history::{
+    self, current_session_id, history_session_id, matches_secret_pattern, parse_history,
+    parse_history_time, History, HistoryFilter, HistoryFormat, MalformedRecord, PersistenceMode,
+};
+use crate::nix::isatty;
+use crate::path::path_apply_working_directory;
+use crate::re::build_regex;
 use crate::reader::commandline_get_state;
+use crate::wutil::normalize_path;
+use nix::{fcntl::OFlag, sys::stat::Mode};
+use std::io::Read;
+use std::time::SystemTime;
 
 use super::prelude::*;
 
@@ -17,6 +30,10 @@ enum HistCmd {
     None,
     ClearSession,
     Append,
+    Check,
+    Repair,
+    Export,
+    Import,
 }
 
 impl HistCmd {
@@ -30,6 +47,10 @@ fn to_wstr(&self) -> &'static wstr {
             HistCmd::None => panic!(),
             HistCmd::ClearSession => L!("clear-session"),
             HistCmd::Append => L!("append"),
+            HistCmd::Check => L!("check"),
+            HistCmd::Repair => L!("repair"),
+            HistCmd::Export => L!("export"),
+            HistCmd::Import => L!("import"),
         }
     }
 }
@@ -45,6 +66,10 @@ fn try_from(val: &wstr) -> Result<Self, ()> {
             _ if val == "save" => Ok(HistCmd::Save),
             _ if val == "clear-session" => Ok(HistCmd::ClearSession),
             _ if val == "append" => Ok(HistCmd::Append),
+            _ if val == "check" => Ok(HistCmd::Check),
+            _ if val == "repair" => Ok(HistCmd::Repair),
+            _ if val == "export" => Ok(HistCmd::Export),
+            _ if val == "import" => Ok(HistCmd::Import),
             _ => Err(()),
         }
     }
@@ -54,7 +79,10 @@ fn try_from(val: &wstr) -> Result<Self, ()> {
 struct HistoryCmdOpts {
     hist_cmd: HistCmd,
     search_type: Option<history::SearchType>,
+    filter: HistoryFilter,
     show_time_format: Option<String>,
+    show_details: bool,
+    format: Option<HistoryFormat>,
     max_items: Option<usize>,
     print_help: bool,
     case_sensitive: bool,
@@ -67,12 +95,21 @@ struct HistoryCmdOpts {
 /// supported at least until fish 3.0 and possibly longer to avoid breaking everyones
 /// config.fish and other scripts.
 const short_options: &wstr = L!(":CRcehmn:pt::z");
+const STATUS_SHORT: char = '\x06';
+const CWD_SHORT: char = '\x07';
+const SESSION_SHORT: char = '\x08';
+const SHOW_DETAILS_SHORT: char = '\x09';
+const REGEX_SHORT: char = '\x0a';
+const SINCE_SHORT: char = '\x0b';
+const UNTIL_SHORT: char = '\x0c';
+const FORMAT_SHORT: char = '\x0d';
 const longopts: &[WOption] = &[
     wopt(L!("prefix"), ArgType::NoArgument, 'p'),
     wopt(L!("contains"), ArgType::NoArgument, 'c'),
     wopt(L!("help"), ArgType::NoArgument, 'h'),
     wopt(L!("show-time"), ArgType::OptionalArgument, 't'),
     wopt(L!("exact"), ArgType::NoArgument, 'e'),
+    wopt(L!("regex"), ArgType::NoArgument, REGEX_SHORT),
     wopt(L!("max"), ArgType::RequiredArgument, 'n'),
     wopt(L!("null"), ArgType::NoArgument, 'z'),
     wopt(L!("case-sensitive"), ArgType::NoArgument, 'C'),
@@ -82,6 +119,13 @@ struct HistoryCmdOpts {
     wopt(L!("clear"), ArgType::NoArgument, '\x04'),
     wopt(L!("merge"), ArgType::NoArgument, '\x05'),
     wopt(L!("reverse"), ArgType::NoArgument, 'R'),
+    wopt(L!("status"), ArgType::RequiredArgument, STATUS_SHORT),
+    wopt(L!("cwd"), ArgType::OptionalArgument, CWD_SHORT),
+    wopt(L!("session"), ArgType::OptionalArgument, SESSION_SHORT),
+    wopt(L!("show-details"), ArgType::NoArgument, SHOW_DETAILS_SHORT),
+    wopt(L!("since"), ArgType::RequiredArgument, SINCE_SHORT),
+    wopt(L!("until"), ArgType::RequiredArgument, UNTIL_SHORT),
+    wopt(L!("format"), ArgType::RequiredArgument, FORMAT_SHORT),
 ];
 
 /// Remember the history subcommand and disallow selecting more than one history subcommand.
@@ -110,7 +154,13 @@ fn check_for_unexpected_hist_args(
     args: &[&wstr],
     streams: &mut IoStreams,
 ) -> bool {
-    if opts.search_type.is_some() || opts.show_time_format.is_some() || opts.null_terminate {
+    if opts.search_type.is_some()
+        || opts.show_time_format.is_some()
+        || opts.show_details
+        || !opts.filter.is_empty()
+        || opts.format.is_some()
+        || opts.null_terminate
+    {
         let subcmd_str = opts.hist_cmd.to_wstr();
         streams.err.append(wgettext_fmt!(
             "%ls: %ls: subcommand takes no options\n",
@@ -133,6 +183,49 @@ fn check_for_unexpected_hist_args(
     false
 }
 
+/// Check that every pattern is a valid regex, and report the first one that is not.
+/// Returns false if there was an invalid one.
+fn check_regexes(
+    cmd: &wstr,
+    patterns: &[&wstr],
+    case_sensitive: bool,
+    streams: &mut IoStreams,
+) -> bool {
+    for pattern in patterns {
+        if let Err(e) = build_regex(pattern, !case_sensitive) {
+            streams.err.append(wgettext_fmt!(
+                "%ls: Regular expression compile error: %ls\n",
+                cmd,
+                &WString::from(e.error_message())
+            ));
+            streams.err.append(sprintf!("%ls: %ls\n", cmd, pattern));
+            streams
+                .err
+                .append(sprintf!("%ls: %*ls\n", cmd, e.offset().unwrap_or(0), "^"));
+            return false;
+        }
+    }
+    true
+}
+
+/// Read a history file to import, or standard input if the path is "-".
+fn read_history_file(path: &wstr, streams: &IoStreams) -> std::io::Result<Vec<u8>> {
+    let mut contents = vec![];
+    if path == "-" {
+        let mut buf = [0; 4096];
+        loop {
+            let amt = read_blocked(streams.stdin_fd, &mut buf)?;
+            if amt == 0 {
+                break;
+            }
+            contents.extend_from_slice(&buf[..amt]);
+        }
+    } else {
+        wopen_cloexec(path, OFlag::O_RDONLY, Mode::empty())?.read_to_end(&mut contents)?;
+    }
+    Ok(contents)
+}
+
 fn parse_cmd_opts(
     opts: &mut HistoryCmdOpts,
     optind: &mut usize,
@@ -184,9 +277,77 @@ fn parse_cmd_opts(
             'e' => {
                 opts.search_type = Some(history::SearchType::Exact);
             }
+            REGEX_SHORT => {
+                opts.search_type = Some(history::SearchType::Regex);
+            }
             't' => {
                 opts.show_time_format = Some(w.woptarg.unwrap_or(L!("# %c%n")).to_string());
             }
+            STATUS_SHORT => match fish_wcstoi(w.woptarg.unwrap()) {
+                Ok(x) => opts.filter.exit_status = Some(x),
+                Err(_) => {
+                    streams.err.append(wgettext_fmt!(
+                        BUILTIN_ERR_NOT_NUMBER,
+                        cmd,
+                        w.woptarg.unwrap()
+                    ));
+                    return STATUS_INVALID_ARGS;
+                }
+            },
+            CWD_SHORT => {
+                // Relative directories, and the default, are relative to $PWD.
+                let pwd = parser.vars().get_pwd_slash();
+                let dir = match w.woptarg {
+                    Some(dir) => path_apply_working_directory(dir, &pwd),
+                    None => pwd,
+                };
+                opts.filter.cwd = Some(normalize_path(&dir, false));
+            }
+            SESSION_SHORT => match w.woptarg {
+                None => opts.filter.session_id = Some(current_session_id()),
+                Some(arg) => match u64::from_str_radix(&arg.to_string(), 16) {
+                    Ok(x) => opts.filter.session_id = Some(x),
+                    Err(_) => {
+                        streams.err.append(wgettext_fmt!(
+                            "%ls: %ls: invalid session identifier\n",
+                            cmd,
+                            arg
+                        ));
+                        return STATUS_INVALID_ARGS;
+                    }
+                },
+            },
+            SHOW_DETAILS_SHORT => {
+                opts.show_details = true;
+            }
+            SINCE_SHORT | UNTIL_SHORT => {
+                let arg = w.woptarg.unwrap();
+                let Some(time) = parse_history_time(arg, SystemTime::now()) else {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: %ls: invalid date or duration\n",
+                        cmd,
+                        arg
+                    ));
+                    return STATUS_INVALID_ARGS;
+                };
+                if opt == SINCE_SHORT {
+                    opts.filter.since = Some(time);
+                } else {
+                    opts.filter.until = Some(time);
+                }
+            }
+            FORMAT_SHORT => {
+                let arg = w.woptarg.unwrap();
+                let Some(format) = HistoryFormat::from_name(arg) else {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: %ls: invalid history format\n",
+                        cmd,
+                        arg
+                    ));
+                    return STATUS_INVALID_ARGS;
+                };
+                opts.format = Some(format);
+            }
             'n' => match fish_wcstol(w.woptarg.unwrap()) {
                 Ok(x) => opts.max_items = Some(x as _), // todo!("historical behavior is to cast")
                 Err(_) => {
@@ -269,11 +430,18 @@ pub fn history(parser: &Parser, streams: &mut IoStreams, args: &mut [&wstr]) ->
     let mut status = STATUS_CMD_OK;
     match opts.hist_cmd {
         HistCmd::None | HistCmd::Search => {
+            if opts.search_type == Some(history::SearchType::Regex)
+                && !check_regexes(cmd, args, opts.case_sensitive, streams)
+            {
+                return STATUS_INVALID_ARGS;
+            }
             if !history.search(
                 opts.search_type
                     .unwrap_or(history::SearchType::ContainsGlob),
                 args,
+                &opts.filter,
                 opts.show_time_format.as_deref(),
+                opts.show_details,
                 opts.max_items.unwrap_or(usize::MAX),
                 opts.case_sensitive,
                 opts.null_terminate,
@@ -301,6 +469,12 @@ pub fn history(parser: &Parser, streams: &mut IoStreams, args: &mut [&wstr]) ->
                 ));
                 return STATUS_INVALID_ARGS;
             }
+            if !opts.filter.is_empty() || opts.show_details {
+                streams.err.append(wgettext!(
+                    "builtin history delete does not support --status, --cwd, --session, --since, --until or --show-details\n"
+                ));
+                return STATUS_INVALID_ARGS;
+            }
 
             for delete_string in args {
                 history.remove(delete_string);
@@ -342,8 +516,149 @@ pub fn history(parser: &Parser, streams: &mut IoStreams, args: &mut [&wstr]) ->
         }
         HistCmd::Append => {
             for &arg in args {
-                history.add_commandline(arg.to_owned());
+                // Like commands that are run, those that look like they contain secrets are only
+                // kept in memory.
+                let persist_mode = if matches_secret_pattern(parser.vars(), arg) {
+                    PersistenceMode::Memory
+                } else {
+                    PersistenceMode::Disk
+                };
+                history.add_commandline(arg.to_owned(), persist_mode);
+            }
+        }
+        HistCmd::Check => {
+            if check_for_unexpected_hist_args(&opts, cmd, args, streams) {
+                return STATUS_INVALID_ARGS;
+            }
+            let records = match history.check() {
+                Ok(records) => records,
+                Err(err) => {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: could not read history file: %s\n",
+                        cmd,
+                        err.to_string()
+                    ));
+                    return STATUS_CMD_ERROR;
+                }
+            };
+            for (offset, record) in &records {
+                let what = match record {
+                    MalformedRecord::StrayLine => wgettext!("line is not part of an entry"),
+                    MalformedRecord::NulBytes => wgettext!("line starts with NUL bytes"),
+                    MalformedRecord::RepeatedPrefix => wgettext!("repeated '- cmd:' prefix"),
+                    MalformedRecord::MissingCommand => wgettext!("entry has no command"),
+                    MalformedRecord::BadTimestamp => wgettext!("invalid timestamp"),
+                    MalformedRecord::Truncated => wgettext!("last line is incomplete"),
+                };
+                streams
+                    .out
+                    .append(wgettext_fmt!("offset %lu: %ls\n", *offset, what));
+            }
+            if !records.is_empty() {
+                status = STATUS_CMD_ERROR;
+            }
+        }
+        HistCmd::Repair => {
+            if check_for_unexpected_hist_args(&opts, cmd, args, streams) {
+                return STATUS_INVALID_ARGS;
+            }
+            if in_private_mode(parser.vars()) {
+                streams.err.append(wgettext_fmt!(
+                    "%ls: can't repair history in private mode\n",
+                    cmd
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+            match history.repair() {
+                Ok(Some(backup)) => {
+                    streams.out.append(wgettext_fmt!(
+                        "%ls: saved the original history file as %ls\n",
+                        cmd,
+                        backup
+                    ));
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: could not repair history file: %s\n",
+                        cmd,
+                        err.to_string()
+                    ));
+                    status = STATUS_CMD_ERROR;
+                }
+            }
+        }
+        HistCmd::Export => {
+            let format = opts.format.take().unwrap_or(HistoryFormat::Fish);
+            if check_for_unexpected_hist_args(&opts, cmd, args, streams) {
+                return STATUS_INVALID_ARGS;
+            }
+            let Some(format) = format.for_export() else {
+                streams.err.append(wgettext_fmt!(
+                    "%ls: export: only the fish and json formats are supported\n",
+                    cmd
+                ));
+                return STATUS_INVALID_ARGS;
+            };
+            streams.out.append(str2wcstring(&history.export(format)));
+        }
+        HistCmd::Import => {
+            let format = opts.format.take().unwrap_or(HistoryFormat::Fish);
+            // Arguments are files to import.
+            if check_for_unexpected_hist_args(&opts, cmd, &[], streams) {
+                return STATUS_INVALID_ARGS;
+            }
+            if in_private_mode(parser.vars()) {
+                streams.err.append(wgettext_fmt!(
+                    "%ls: can't import history in private mode\n",
+                    cmd
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+            if args.is_empty() && isatty(streams.stdin_fd) {
+                // Don't implicitly read from the terminal.
+                streams.err.append(wgettext_fmt!(
+                    "%ls: missing filename argument or input redirection\n",
+                    cmd
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+            let paths = if args.is_empty() {
+                &[L!("-")][..]
+            } else {
+                args
+            };
+            let now = SystemTime::now();
+            let mut items = vec![];
+            for &path in paths {
+                let contents = match read_history_file(path, streams) {
+                    Ok(contents) => contents,
+                    Err(err) => {
+                        streams.err.append(wgettext_fmt!(
+                            "%ls: %ls: %s\n",
+                            cmd,
+                            path,
+                            err.to_string()
+                        ));
+                        return STATUS_CMD_ERROR;
+                    }
+                };
+                match parse_history(format, &contents, now) {
+                    Ok(parsed) => items.extend(parsed),
+                    Err(offset) => {
+                        streams.err.append(wgettext_fmt!(
+                            "%ls: %ls: invalid history entry at offset %lu\n",
+                            cmd,
+                            path,
+                            offset
+                        ));
+                        return STATUS_CMD_ERROR;
+                    }
+                }
             }
+            // Commands that would not have been saved if they were run here stay out, too.
+            items.retain(|item| !matches_secret_pattern(parser.vars(), item.str()));
+            history.import(items);
         }
     }
 
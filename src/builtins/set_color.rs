@@ -1,7 +1,7 @@
 // Implementation of the set_color builtin.
 
 use super::prelude::*;
-use crate::color::RgbColor;
+use crate::color::{RgbColor, UnderlineStyle};
 use crate::common::str2wcstring;
 use crate::output::{self, Outputter};
 use crate::terminal::{self, Term};
@@ -11,19 +11,22 @@ fn print_modifiers(
     outp: &mut Outputter,
     term: &Term,
     bold: bool,
-    underline: bool,
+    underline: Option<UnderlineStyle>,
     italics: bool,
     dim: bool,
     reverse: bool,
+    strikethrough: bool,
+    blink: bool,
     bg: RgbColor,
 ) {
     let Term {
         enter_bold_mode,
-        enter_underline_mode,
         enter_italics_mode,
         enter_dim_mode,
         enter_reverse_mode,
         enter_standout_mode,
+        enter_strikethrough_mode,
+        enter_blink_mode,
         exit_attribute_mode,
         ..
     } = term;
@@ -31,8 +34,8 @@ fn print_modifiers(
         outp.tputs_if_some(enter_bold_mode);
     }
 
-    if underline {
-        outp.tputs_if_some(enter_underline_mode);
+    if let Some(style) = underline {
+        outp.write_underline(style, term);
     }
 
     if italics {
@@ -49,6 +52,15 @@ fn print_modifiers(
             outp.tputs_if_some(enter_standout_mode);
         }
     }
+
+    if strikethrough {
+        outp.tputs_if_some(enter_strikethrough_mode);
+    }
+
+    if blink {
+        outp.tputs_if_some(enter_blink_mode);
+    }
+
     if !bg.is_none() && bg.is_normal() {
         outp.tputs_if_some(exit_attribute_mode);
     }
@@ -59,10 +71,12 @@ fn print_colors(
     streams: &mut IoStreams,
     args: &[&wstr],
     bold: bool,
-    underline: bool,
+    underline: Option<UnderlineStyle>,
     italics: bool,
     dim: bool,
     reverse: bool,
+    strikethrough: bool,
+    blink: bool,
     bg: RgbColor,
 ) {
     let outp = &mut output::Outputter::new_buffering();
@@ -80,7 +94,18 @@ fn print_colors(
     for color_name in args {
         if streams.out_is_terminal() {
             if let Some(term) = term.as_ref() {
-                print_modifiers(outp, term, bold, underline, italics, dim, reverse, bg);
+                print_modifiers(
+                    outp,
+                    term,
+                    bold,
+                    underline,
+                    italics,
+                    dim,
+                    reverse,
+                    strikethrough,
+                    blink,
+                    bg,
+                );
             }
             let color = RgbColor::from_wstr(color_name).unwrap_or(RgbColor::NONE);
             outp.set_color(color, RgbColor::NONE);
@@ -103,15 +128,18 @@ fn print_colors(
     streams.out.append(str2wcstring(contents));
 }
 
-const SHORT_OPTIONS: &wstr = L!(":b:hoidrcu");
+const SHORT_OPTIONS: &wstr = L!(":b:hoidrcus");
 const LONG_OPTIONS: &[WOption] = &[
     wopt(L!("background"), ArgType::RequiredArgument, 'b'),
     wopt(L!("help"), ArgType::NoArgument, 'h'),
     wopt(L!("bold"), ArgType::NoArgument, 'o'),
-    wopt(L!("underline"), ArgType::NoArgument, 'u'),
+    wopt(L!("underline"), ArgType::OptionalArgument, 'u'),
+    wopt(L!("underline-color"), ArgType::RequiredArgument, '\x01'),
     wopt(L!("italics"), ArgType::NoArgument, 'i'),
     wopt(L!("dim"), ArgType::NoArgument, 'd'),
     wopt(L!("reverse"), ArgType::NoArgument, 'r'),
+    wopt(L!("strikethrough"), ArgType::NoArgument, 's'),
+    wopt(L!("blink"), ArgType::NoArgument, '\x02'),
     wopt(L!("print-colors"), ArgType::NoArgument, 'c'),
 ];
 
@@ -127,11 +155,14 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
     }
 
     let mut bgcolor = None;
+    let mut underline_color_name = None;
     let mut bold = false;
-    let mut underline = false;
+    let mut underline = None;
     let mut italics = false;
     let mut dim = false;
     let mut reverse = false;
+    let mut strikethrough = false;
+    let mut blink = false;
     let mut print = false;
 
     let mut w = WGetopter::new(SHORT_OPTIONS, LONG_OPTIONS, argv);
@@ -149,11 +180,31 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
             'i' => italics = true,
             'd' => dim = true,
             'r' => reverse = true,
-            'u' => underline = true,
+            's' => strikethrough = true,
+            '\x02' => blink = true,
+            'u' => {
+                let Some(style) = w.woptarg else {
+                    underline = Some(UnderlineStyle::Single);
+                    continue;
+                };
+                underline = UnderlineStyle::from_wstr(style);
+                if underline.is_none() {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: Unknown underline style '%ls'\n",
+                        argv[0],
+                        style
+                    ));
+                    return STATUS_INVALID_ARGS;
+                }
+            }
+            '\x01' => {
+                assert!(w.woptarg.is_some(), "Arg should have been set");
+                underline_color_name = w.woptarg;
+            }
             'c' => print = true,
             ':' => {
-                // We don't error here because "-b" is the only option that requires an argument,
-                // and we don't error for missing colors.
+                // We don't error here because "-b" and "--underline-color" are the only options
+                // that require an argument, and we don't error for missing colors.
                 return STATUS_INVALID_ARGS;
             }
             '?' => {
@@ -182,6 +233,19 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
         return STATUS_INVALID_ARGS;
     }
 
+    let underline_color = match underline_color_name {
+        Some(name) => match RgbColor::from_wstr(name) {
+            Some(color) => Some(color),
+            None => {
+                streams
+                    .err
+                    .append(wgettext_fmt!("%ls: Unknown color '%ls'\n", argv[0], name));
+                return STATUS_INVALID_ARGS;
+            }
+        },
+        None => None,
+    };
+
     if print {
         // Hack: Explicitly setting a background of "normal" crashes
         // for --print-colors. Because it's not interesting in terms of display,
@@ -190,7 +254,18 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
             bg = RgbColor::from_wstr(L!("")).unwrap_or(RgbColor::NONE);
         }
         let args = &argv[wopt_index..argc];
-        print_colors(streams, args, bold, underline, italics, dim, reverse, bg);
+        print_colors(
+            streams,
+            args,
+            bold,
+            underline,
+            italics,
+            dim,
+            reverse,
+            strikethrough,
+            blink,
+            bg,
+        );
         return STATUS_CMD_OK;
     }
 
@@ -225,7 +300,18 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
     };
 
     let outp = &mut output::Outputter::new_buffering();
-    print_modifiers(outp, &term, bold, underline, italics, dim, reverse, bg);
+    print_modifiers(
+        outp,
+        &term,
+        bold,
+        underline,
+        italics,
+        dim,
+        reverse,
+        strikethrough,
+        blink,
+        bg,
+    );
     if bgcolor.is_some() && bg.is_normal() {
         outp.tputs(exit_attribute_mode);
     }
@@ -243,6 +329,9 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
     if bgcolor.is_some() && !bg.is_normal() && !bg.is_reset() {
         outp.write_color(bg, false /* is_fg */);
     }
+    if let Some(underline_color) = underline_color {
+        outp.write_underline_color(underline_color);
+    }
 
     // Output the collected string.
     let contents = outp.contents();
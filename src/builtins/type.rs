@@ -5,6 +5,7 @@
 
 use crate::parse_util::{apply_indents, parse_util_compute_indents};
 use crate::path::{path_get_path, path_get_paths};
+use crate::spelling::command_suggestions;
 
 #[derive(Default)]
 struct type_cmd_opts_t {
@@ -15,6 +16,7 @@ struct type_cmd_opts_t {
     path: bool,
     force_path: bool,
     query: bool,
+    suggest: bool,
 }
 
 pub fn r#type(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> Option<c_int> {
@@ -34,6 +36,7 @@ pub fn r#type(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> O
         wopt(L!("force-path"), ArgType::NoArgument, 'P'),
         wopt(L!("query"), ArgType::NoArgument, 'q'),
         wopt(L!("quiet"), ArgType::NoArgument, 'q'),
+        wopt(L!("suggest"), ArgType::NoArgument, '\x01'),
     ];
 
     let mut w = WGetopter::new(shortopts, longopts, argv);
@@ -46,6 +49,7 @@ pub fn r#type(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> O
             'p' => opts.path = true,
             'P' => opts.force_path = true,
             'q' => opts.query = true,
+            '\x01' => opts.suggest = true,
             'h' => {
                 builtin_print_help(parser, streams, cmd);
                 return STATUS_CMD_OK;
@@ -64,7 +68,13 @@ pub fn r#type(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> O
         }
     }
 
-    if opts.query as i64 + opts.path as i64 + opts.get_type as i64 + opts.force_path as i64 > 1 {
+    if opts.query as i64
+        + opts.path as i64
+        + opts.get_type as i64
+        + opts.force_path as i64
+        + opts.suggest as i64
+        > 1
+    {
         streams.err.append(wgettext_fmt!(BUILTIN_ERR_COMBO, cmd));
         return STATUS_INVALID_ARGS;
     }
@@ -72,6 +82,16 @@ pub fn r#type(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> O
     let mut res = false;
 
     let optind = w.wopt_index;
+    if opts.suggest {
+        for arg in argv.iter().take(argc).skip(optind) {
+            for suggestion in command_suggestions(arg, parser.vars()) {
+                res = true;
+                streams.out.appendln(suggestion);
+            }
+        }
+        return if res { STATUS_CMD_OK } else { STATUS_CMD_ERROR };
+    }
+
     for arg in argv.iter().take(argc).skip(optind) {
         let mut found = 0;
         if !opts.force_path && !opts.no_functions {
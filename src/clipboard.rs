@@ -0,0 +1,107 @@
+//! Access to the terminal's clipboard with OSC 52.
+//!
+//! This works wherever the terminal supports it, including over SSH, unlike the clipboard helper
+//! programs used by `fish_clipboard_copy`. It is controlled by `$fish_terminal_clipboard`: with
+//! "copy", killed text is copied to the clipboard and the active selection to the primary
+//! selection; with "paste", yanking asks the terminal for the clipboard first.
+
+use crate::common::{str2wcstring, wcs2string};
+use crate::env::Environment;
+use crate::global_safety::RelaxedAtomicBool;
+use crate::wchar::prelude::*;
+
+static COPY_ENABLED: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
+static PASTE_ENABLED: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
+
+/// The clipboard that receives killed text, and that yanking reads from.
+pub const CLIPBOARD: char = 'c';
+/// The primary selection, which mirrors the active selection of the command line.
+pub const PRIMARY_SELECTION: char = 'p';
+
+/// Asks the terminal for the contents of the clipboard.
+pub const OSC52_PASTE_QUERY: &[u8] = b"\x1b]52;c;?\x07";
+
+/// Updates the settings from `$fish_terminal_clipboard`.
+pub fn update_terminal_clipboard(vars: &dyn Environment) {
+    let var = vars.get_unless_empty(L!("fish_terminal_clipboard"));
+    let values = var.as_ref().map_or(&[][..], |var| var.as_list());
+    COPY_ENABLED.store(values.iter().any(|value| value == "copy"));
+    PASTE_ENABLED.store(values.iter().any(|value| value == "paste"));
+}
+
+/// Returns whether killed text and the selection are copied to the terminal.
+pub fn copy_enabled() -> bool {
+    COPY_ENABLED.load()
+}
+
+/// Returns whether yanking reads the terminal's clipboard.
+pub fn paste_enabled() -> bool {
+    PASTE_ENABLED.load()
+}
+
+/// Returns the OSC 52 sequence that sets the selection `target` to `text`.
+pub fn osc52_copy_sequence(target: char, text: &wstr) -> Vec<u8> {
+    let mut seq = format!("\x1b]52;{target};").into_bytes();
+    seq.extend_from_slice(base64_encode(&wcs2string(text)).as_bytes());
+    seq.push(b'\x07');
+    seq
+}
+
+/// Parses the body of an OSC 52 response, the part between "\x1b]" and the terminator, like
+/// "52;c;ZmlzaA==". Returns None if this is not a valid response.
+pub fn parse_osc52_response(body: &[u8]) -> Option<WString> {
+    let mut parts = body.splitn(3, |&c| c == b';');
+    if parts.next()? != b"52" {
+        return None;
+    }
+    let _target = parts.next()?;
+    let data = base64_decode(parts.next()?)?;
+    Some(str2wcstring(&data))
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `data` in base64 with padding.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                result.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decodes base64, with or without padding. Returns None on invalid input.
+pub fn base64_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let data = data
+        .strip_suffix(b"==")
+        .or(data.strip_suffix(b"="))
+        .unwrap_or(data);
+    let mut result = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    let mut group = 0_u32;
+    let mut bits = 0;
+    for &c in data {
+        let value = BASE64_ALPHABET.iter().position(|&b| b == c)?;
+        group = group << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+    Some(result)
+}
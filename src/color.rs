@@ -34,13 +34,61 @@ pub enum Type {
 
 bitflags! {
     #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
-    pub struct Flags: u8 {
+    pub struct Flags: u16 {
         const DEFAULT = 0;
         const BOLD = 1<<0;
         const UNDERLINE = 1<<1;
         const ITALICS = 1<<2;
         const DIM = 1<<3;
         const REVERSE = 1<<4;
+        const STRIKETHROUGH = 1<<5;
+        const BLINK = 1<<6;
+        // The style of the underline, if UNDERLINE is set. None of these means a single line.
+        const UNDERLINE_DOUBLE = 1<<7;
+        const UNDERLINE_CURLY = 1<<8;
+        const UNDERLINE_DOTTED = 1<<9;
+        const UNDERLINE_DASHED = 1<<10;
+    }
+}
+
+/// The ways text can be underlined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    /// Parse a style name like "curly".
+    pub fn from_wstr(s: &wstr) -> Option<Self> {
+        let style = if s == "single" {
+            Self::Single
+        } else if s == "double" {
+            Self::Double
+        } else if s == "curly" {
+            Self::Curly
+        } else if s == "dotted" {
+            Self::Dotted
+        } else if s == "dashed" {
+            Self::Dashed
+        } else {
+            return None;
+        };
+        Some(style)
+    }
+
+    /// Returns the flag that records this style, or an empty set for a single line.
+    const fn flag(self) -> Flags {
+        match self {
+            Self::Single => Flags::DEFAULT,
+            Self::Double => Flags::UNDERLINE_DOUBLE,
+            Self::Curly => Flags::UNDERLINE_CURLY,
+            Self::Dotted => Flags::UNDERLINE_DOTTED,
+            Self::Dashed => Flags::UNDERLINE_DASHED,
+        }
     }
 }
 
@@ -142,9 +190,41 @@ pub const fn is_underline(self) -> bool {
         self.flags.contains(Flags::UNDERLINE)
     }
 
-    /// Set whether the color is underline.
+    /// Set whether the color is underline. This keeps the style of an existing underline.
     pub fn set_underline(&mut self, underline: bool) {
-        self.flags.set(Flags::UNDERLINE, underline)
+        if underline != self.is_underline() {
+            self.set_underline_style(underline.then_some(UnderlineStyle::Single));
+        }
+    }
+
+    /// Returns the style of the underline, if the color is underlined.
+    pub fn underline_style(self) -> Option<UnderlineStyle> {
+        if !self.is_underline() {
+            return None;
+        }
+        [
+            UnderlineStyle::Double,
+            UnderlineStyle::Curly,
+            UnderlineStyle::Dotted,
+            UnderlineStyle::Dashed,
+        ]
+        .into_iter()
+        .find(|style| self.flags.contains(style.flag()))
+        .or(Some(UnderlineStyle::Single))
+    }
+
+    /// Set whether and how the color is underlined.
+    pub fn set_underline_style(&mut self, style: Option<UnderlineStyle>) {
+        self.flags.remove(
+            Flags::UNDERLINE
+                | Flags::UNDERLINE_DOUBLE
+                | Flags::UNDERLINE_CURLY
+                | Flags::UNDERLINE_DOTTED
+                | Flags::UNDERLINE_DASHED,
+        );
+        if let Some(style) = style {
+            self.flags.insert(Flags::UNDERLINE | style.flag());
+        }
     }
 
     /// Returns whether the color is italics.
@@ -177,6 +257,26 @@ pub fn set_reverse(&mut self, reverse: bool) {
         self.flags.set(Flags::REVERSE, reverse)
     }
 
+    /// Returns whether the color is struck through.
+    pub const fn is_strikethrough(self) -> bool {
+        self.flags.contains(Flags::STRIKETHROUGH)
+    }
+
+    /// Set whether the color is struck through.
+    pub fn set_strikethrough(&mut self, strikethrough: bool) {
+        self.flags.set(Flags::STRIKETHROUGH, strikethrough)
+    }
+
+    /// Returns whether the color is blinking.
+    pub const fn is_blink(self) -> bool {
+        self.flags.contains(Flags::BLINK)
+    }
+
+    /// Set whether the color is blinking.
+    pub fn set_blink(&mut self, blink: bool) {
+        self.flags.set(Flags::BLINK, blink)
+    }
+
     /// Returns the name index for the given color. Requires that the color be named or RGB.
     pub fn to_name_index(self) -> u8 {
         // TODO: This should look for the nearest color.
@@ -426,7 +526,7 @@ fn term256_color_for_rgb(color: Color24) -> u8 {
 
 #[cfg(test)]
 mod tests {
-    use crate::color::{Color24, Flags, RgbColor, Type};
+    use crate::color::{Color24, Flags, RgbColor, Type, UnderlineStyle};
     use crate::wchar::prelude::*;
 
     #[test]
@@ -444,6 +544,25 @@ fn parse() {
         assert!(RgbColor::from_wstr(L!("mooganta")).is_none());
     }
 
+    #[test]
+    fn underline_style() {
+        let mut color = RgbColor::from_wstr(L!("red")).unwrap();
+        assert_eq!(color.underline_style(), None);
+        color.set_underline(true);
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Single));
+        color.set_underline_style(Some(UnderlineStyle::Curly));
+        assert!(color.is_underline());
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Curly));
+        // Turning on underlining again keeps the style.
+        color.set_underline(true);
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Curly));
+        color.set_underline_style(Some(UnderlineStyle::Dashed));
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Dashed));
+        color.set_underline(false);
+        assert_eq!(color.underline_style(), None);
+        assert_eq!(color, RgbColor::from_wstr(L!("red")).unwrap());
+    }
+
     #[test]
     fn parse_rgb() {
         assert!(RgbColor::from_wstr(L!("##FF00A0")).is_none());
@@ -147,6 +147,8 @@ pub struct Completion {
     pub r#match: StringFuzzyMatch,
     /// Flags determining the completion behavior.
     pub flags: CompleteFlags,
+    /// Indices of the characters matched by a search, to be highlighted in the pager.
+    pub match_positions: Vec<usize>,
 }
 
 impl Default for Completion {
@@ -156,6 +158,7 @@ fn default() -> Self {
             description: Default::default(),
             r#match: StringFuzzyMatch::exact_match(),
             flags: Default::default(),
+            match_positions: vec![],
         }
     }
 }
@@ -182,6 +185,7 @@ pub fn new(
             description,
             r#match,
             flags,
+            match_positions: vec![],
         }
     }
 
@@ -1,15 +1,20 @@
-use crate::common::ToCString;
+use crate::common::{escape_string, EscapeStringStyle, ToCString};
 use crate::complete::complete_invalidate_path;
 use crate::env::{setenv_lock, unsetenv_lock, EnvMode, EnvStack, Environment};
 use crate::env::{DEFAULT_READ_BYTE_LIMIT, READ_BYTE_LIMIT, TERM_HAS_XN};
 use crate::flog::FLOG;
 use crate::function;
-use crate::input_common::{update_wait_on_escape_ms, update_wait_on_sequence_key_ms};
-use crate::output::ColorSupport;
+use crate::input_common::{
+    update_focus_reporting_for_notifications, update_wait_on_escape_ms,
+    update_wait_on_sequence_key_ms,
+};
+use crate::nix::isatty;
+use crate::output::{ColorSupport, Outputter};
 use crate::proc::is_interactive_session;
 use crate::reader::{
     reader_change_cursor_end_mode, reader_change_cursor_selection_mode, reader_change_history,
-    reader_schedule_prompt_repaint, reader_set_autosuggestion_enabled,
+    reader_check_autosuggestion_sources, reader_schedule_prompt_repaint,
+    reader_set_autosuggestion_enabled,
 };
 use crate::screen::screen_set_midnight_commander_hack;
 use crate::screen::LAYOUT_CACHE_SHARED;
@@ -69,10 +74,30 @@
         table.add_anon(L!("fish_function_path"), handle_function_path_change);
         table.add_anon(L!("fish_read_limit"), handle_read_limit_change);
         table.add_anon(L!("fish_history"), handle_fish_history_change);
+        table.add_anon(
+            L!("fish_history_max_items"),
+            handle_fish_history_retention_change,
+        );
+        table.add_anon(
+            L!("fish_history_max_age"),
+            handle_fish_history_retention_change,
+        );
+        table.add_anon(
+            L!("fish_terminal_clipboard"),
+            handle_fish_terminal_clipboard_change,
+        );
+        table.add_anon(
+            L!("fish_notify_threshold"),
+            update_focus_reporting_for_notifications,
+        );
         table.add_anon(
             L!("fish_autosuggestion_enabled"),
             handle_autosuggestion_change,
         );
+        table.add_anon(
+            L!("fish_autosuggestion_sources"),
+            handle_autosuggestion_sources_change,
+        );
         table.add_anon(
             L!("fish_use_posix_spawn"),
             handle_fish_use_posix_spawn_change,
@@ -86,6 +111,7 @@
             L!("fish_cursor_end_mode"),
             handle_fish_cursor_end_mode_change,
         );
+        table.add_anon(L!("PWD"), report_working_directory);
 
         table
     });
@@ -241,6 +267,14 @@ fn handle_fish_history_change(vars: &EnvStack) {
     reader_change_history(&session_id);
 }
 
+fn handle_fish_history_retention_change(vars: &EnvStack) {
+    crate::history::update_history_retention(vars);
+}
+
+fn handle_fish_terminal_clipboard_change(vars: &EnvStack) {
+    crate::clipboard::update_terminal_clipboard(vars);
+}
+
 fn handle_fish_cursor_selection_mode_change(vars: &EnvStack) {
     use crate::reader::CursorSelectionMode;
 
@@ -281,6 +315,10 @@ fn handle_autosuggestion_change(vars: &EnvStack) {
     reader_set_autosuggestion_enabled(vars);
 }
 
+fn handle_autosuggestion_sources_change(vars: &EnvStack) {
+    reader_check_autosuggestion_sources(vars);
+}
+
 fn handle_function_path_change(_: &EnvStack) {
     function::invalidate_path();
 }
@@ -304,6 +342,26 @@ fn handle_term_change(vars: &EnvStack) {
     init_terminal(vars);
 }
 
+/// Tells the terminal the current working directory via OSC 7, so new tabs and splits open in it.
+/// This is only done in interactive sessions whose output goes to a terminal.
+pub fn report_working_directory(vars: &EnvStack) {
+    if !is_interactive_session() || !isatty(libc::STDOUT_FILENO) {
+        return;
+    }
+    let Some(pwd) = vars.get_unless_empty(L!("PWD")) else {
+        return;
+    };
+    let hostname = vars
+        .get(L!("hostname"))
+        .map(|var| var.as_string())
+        .unwrap_or_default();
+    let mut osc = WString::from_str("\x1b]7;file://");
+    osc.push_utfstr(&hostname);
+    osc.push_utfstr(&escape_string(&pwd.as_string(), EscapeStringStyle::Url));
+    osc.push('\x07');
+    Outputter::stdoutput().borrow_mut().write_wstr(&osc);
+}
+
 fn handle_fish_use_posix_spawn_change(vars: &EnvStack) {
     // Note that if the variable is missing or empty we default to true (if allowed).
     if !allow_use_posix_spawn() {
@@ -366,6 +424,10 @@ fn run_inits(vars: &EnvStack) {
     update_wait_on_escape_ms(vars);
     update_wait_on_sequence_key_ms(vars);
     handle_read_limit_change(vars);
+    handle_fish_history_retention_change(vars);
+    handle_fish_terminal_clipboard_change(vars);
+    update_focus_reporting_for_notifications(vars);
+    handle_autosuggestion_sources_change(vars);
     handle_fish_use_posix_spawn_change(vars);
     handle_fish_trace(vars);
 }
@@ -11,7 +11,7 @@
     valid_var_name, valid_var_name_char, ASCII_MAX, EXPAND_RESERVED_BASE, EXPAND_RESERVED_END,
 };
 use crate::complete::complete_wrap_map;
-use crate::env::Environment;
+use crate::env::{EnvVar, Environment};
 use crate::expand::{
     expand_one, expand_to_command_and_args, ExpandFlags, ExpandResultCode, PROCESS_EXPAND_SELF_STR,
 };
@@ -20,7 +20,7 @@
 use crate::highlight::file_tester::FileTester;
 use crate::history::{all_paths_are_valid, HistoryItem};
 use crate::operation_context::OperationContext;
-use crate::output::{parse_color, Outputter};
+use crate::output::{parse_color, parse_underline_color, Outputter};
 use crate::parse_constants::{
     ParseKeyword, ParseTokenType, ParseTreeFlags, SourceRange, StatementDecoration,
 };
@@ -72,6 +72,7 @@ pub fn colorize(text: &wstr, colors: &[HighlightSpec], vars: &dyn Environment) -
         let color = colors[i];
         if color != last_color {
             outp.set_color(rv.resolve_spec(&color, false, vars), RgbColor::NORMAL);
+            outp.set_underline_color(rv.resolve_underline_color(&color, vars));
             last_color = color;
         }
         outp.writech(c);
@@ -109,6 +110,7 @@ pub fn highlight_shell(
 pub struct HighlightColorResolver {
     fg_cache: HashMap<HighlightSpec, RgbColor>,
     bg_cache: HashMap<HighlightSpec, RgbColor>,
+    underline_color_cache: HashMap<HighlightSpec, RgbColor>,
 }
 
 /// highlight_color_resolver_t resolves highlight specs (like "a command") to actual RGB colors.
@@ -151,11 +153,7 @@ pub fn resolve_spec_uncached(
             highlight.foreground
         };
 
-        let var = vars
-            .get_unless_empty(get_highlight_var_name(role))
-            .or_else(|| vars.get_unless_empty(get_highlight_var_name(get_fallback(role))))
-            .or_else(|| vars.get(get_highlight_var_name(HighlightRole::normal)));
-        if let Some(var) = var {
+        if let Some(var) = Self::color_var(role, vars) {
             result = parse_color(&var, is_background);
         }
 
@@ -169,17 +167,22 @@ pub fn resolve_spec_uncached(
                     // Valid path has an actual color, use it and merge the modifiers.
                     let mut rescol = result2;
                     rescol.set_bold(result.is_bold() || result2.is_bold());
-                    rescol.set_underline(result.is_underline() || result2.is_underline());
+                    rescol.set_underline_style(
+                        result2.underline_style().or(result.underline_style()),
+                    );
                     rescol.set_italics(result.is_italics() || result2.is_italics());
                     rescol.set_dim(result.is_dim() || result2.is_dim());
                     rescol.set_reverse(result.is_reverse() || result2.is_reverse());
+                    rescol
+                        .set_strikethrough(result.is_strikethrough() || result2.is_strikethrough());
+                    rescol.set_blink(result.is_blink() || result2.is_blink());
                     result = rescol;
                 } else {
                     if result2.is_bold() {
                         result.set_bold(true)
                     };
                     if result2.is_underline() {
-                        result.set_underline(true)
+                        result.set_underline_style(result2.underline_style())
                     };
                     if result2.is_italics() {
                         result.set_italics(true)
@@ -190,6 +193,12 @@ pub fn resolve_spec_uncached(
                     if result2.is_reverse() {
                         result.set_reverse(true)
                     };
+                    if result2.is_strikethrough() {
+                        result.set_strikethrough(true)
+                    };
+                    if result2.is_blink() {
+                        result.set_blink(true)
+                    };
                 }
             }
         }
@@ -200,9 +209,43 @@ pub fn resolve_spec_uncached(
 
         result
     }
+
+    /// Return the underline color for a given highlight spec. Like the other text attributes,
+    /// this comes from the foreground role.
+    pub fn resolve_underline_color(
+        &mut self,
+        highlight: &HighlightSpec,
+        vars: &dyn Environment,
+    ) -> RgbColor {
+        *self
+            .underline_color_cache
+            .entry(*highlight)
+            .or_insert_with(|| {
+                let mut result = Self::color_var(highlight.foreground, vars)
+                    .map_or(RgbColor::NORMAL, |var| parse_underline_color(&var));
+                if highlight.valid_path {
+                    if let Some(var) = vars.get(L!("fish_color_valid_path")) {
+                        let result2 = parse_underline_color(&var);
+                        if !result2.is_normal() {
+                            result = result2;
+                        }
+                    }
+                }
+                result
+            })
+    }
+
+    /// Return the variable that holds the color of a role, falling back to related roles.
+    fn color_var(role: HighlightRole, vars: &dyn Environment) -> Option<EnvVar> {
+        vars.get_unless_empty(get_highlight_var_name(role))
+            .or_else(|| vars.get_unless_empty(get_highlight_var_name(get_fallback(role))))
+            .or_else(|| vars.get(get_highlight_var_name(HighlightRole::normal)))
+    }
 }
 
-fn command_is_valid(
+/// Returns whether `cmd` is something that can be run with the given decoration: a builtin,
+/// function, abbreviation, executable or directory to cd into.
+pub fn command_is_valid(
     cmd: &wstr,
     decoration: StatementDecoration,
     working_directory: &wstr,
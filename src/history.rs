@@ -24,7 +24,9 @@
 //! considered preferable to hanging the the shell waiting for a lock.
 
 use crate::{
-    common::cstr2wcstring, env::EnvVar, wcstringutil::trim,
+    common::cstr2wcstring,
+    env::EnvVar,
+    wcstringutil::{join_strings, trim},
     wutil::fileid::file_id_for_path_or_error,
 };
 use std::{
@@ -47,6 +49,8 @@
 use libc::{fchmod, fchown, flock, LOCK_EX, LOCK_SH, LOCK_UN};
 use lru::LruCache;
 use nix::{fcntl::OFlag, sys::stat::Mode};
+use once_cell::sync::Lazy;
+use pcre2::utf32::Regex;
 use rand::Rng;
 
 use crate::{
@@ -69,19 +73,23 @@
     path::{
         path_get_config, path_get_data, path_get_data_remoteness, path_is_valid, DirRemoteness,
     },
+    re::build_regex,
     threads::{assert_is_background_thread, iothread_perform},
+    universal_notifier::{create_file_watch, UniversalNotifier},
     util::{find_subslice, get_rng},
     wchar::prelude::*,
     wcstringutil::subsequence_in_string,
     wildcard::{wildcard_match, ANY_STRING},
     wutil::fstat,
     wutil::{
-        file_id_for_fd, file_id_for_path, wgettext_fmt, wrealpath, wrename, wstat, wunlink, FileId,
-        INVALID_FILE_ID,
+        file_id_for_fd, file_id_for_path, fish_wcstol, fish_wcstoul, wgettext_fmt, wrealpath,
+        wrename, wstat, wunlink, FileId, INVALID_FILE_ID,
     },
 };
 
 mod file;
+mod fuzzy;
+mod interchange;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SearchType {
@@ -99,6 +107,8 @@ pub enum SearchType {
     PrefixGlob,
     /// Search for commands containing the given string as a subsequence
     ContainsSubsequence,
+    /// Search for commands matching the given PCRE2 regular expression.
+    Regex,
     /// Matches everything.
     MatchEverything,
 }
@@ -120,17 +130,30 @@ pub enum SearchDirection {
     Backward,
 }
 
-use self::file::time_to_seconds;
+pub use self::file::MalformedRecord;
+use self::file::{
+    decode_items, find_item_start, find_malformed_records, time_from_seconds, time_to_seconds,
+};
+use self::fuzzy::rank_fuzzy_matches;
+pub use self::fuzzy::{fuzzy_match, FuzzyMatch, RankedCommand};
+use self::interchange::{append_history_item_to_json_buffer, parse_bash_history};
+pub use self::interchange::{parse_history, ExportFormat, HistoryFormat};
 
 // Our history format is intended to be valid YAML. Here it is:
 //
 //   - cmd: ssh blah blah blah
 //     when: 2348237
+//     status: 0
+//     duration: 1532
+//     cwd: /home/me/project
+//     session: 5b1e0c9a7d3f2e41
 //     paths:
 //       - /path/to/something
 //       - /path/to/something_else
 //
 //   Newlines are replaced by \n. Backslashes are replaced by \\.
+//   The duration is in milliseconds and the session is in hex. All keys but "cmd" and "when" are
+//   optional, and unknown keys are ignored when reading.
 
 /// This is the history session ID we use by default if the user has not set env var fish_history.
 const DFLT_FISH_HISTORY_SESSION_ID: &wstr = L!("fish");
@@ -152,6 +175,98 @@ pub enum SearchDirection {
 
 pub const VACUUM_FREQUENCY: usize = 25;
 
+/// Limits on which items are kept when the history file is rewritten.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HistoryRetention {
+    /// The maximum number of items to keep, from `$fish_history_max_items`.
+    pub max_items: Option<NonZeroUsize>,
+    /// The maximum age of items to keep, from `$fish_history_max_age`.
+    pub max_age: Option<Duration>,
+}
+
+impl HistoryRetention {
+    /// Removes the items that should not be kept as of `now`. `items` must be sorted by timestamp.
+    fn apply(&self, items: &mut Vec<HistoryItem>, now: SystemTime) {
+        if let Some(cutoff) = self.max_age.and_then(|max_age| now.checked_sub(max_age)) {
+            items.retain(|item| item.timestamp() >= cutoff);
+        }
+        if let Some(max_items) = self.max_items {
+            let excess = items.len().saturating_sub(max_items.get());
+            items.drain(..excess);
+        }
+    }
+}
+
+/// The retention limits in effect. This is shared among all history instances.
+static HISTORY_RETENTION: Mutex<HistoryRetention> = Mutex::new(HistoryRetention {
+    max_items: None,
+    max_age: None,
+});
+
+/// Sets the limits on which items are kept when history files are rewritten.
+pub fn set_history_retention(retention: HistoryRetention) {
+    *HISTORY_RETENTION.lock().unwrap() = retention;
+}
+
+/// Parses a duration like "90d". The suffix must be one of s, m, h, d or w, for seconds, minutes,
+/// hours, days or weeks. If `default_unit` is given, a plain number is also accepted and counted
+/// in that many seconds.
+pub fn parse_duration(s: &wstr, default_unit: Option<u64>) -> Option<Duration> {
+    let s = trim(s.to_owned(), None);
+    let (digits, unit) = match s.as_char_slice().last()? {
+        's' => (&s[..s.len() - 1], 1),
+        'm' => (&s[..s.len() - 1], 60),
+        'h' => (&s[..s.len() - 1], 60 * 60),
+        'd' => (&s[..s.len() - 1], 24 * 60 * 60),
+        'w' => (&s[..s.len() - 1], 7 * 24 * 60 * 60),
+        _ => (&s[..], default_unit?),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let count = fish_wcstoul(digits).ok()?;
+    Some(Duration::from_secs(count.checked_mul(unit)?))
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Updates the retention limits from `$fish_history_max_items` and `$fish_history_max_age`. Unset,
+/// empty or zero values mean no limit.
+pub fn update_history_retention(vars: &dyn Environment) {
+    let max_items = vars
+        .get_unless_empty(L!("fish_history_max_items"))
+        .and_then(|var| {
+            let value = var.as_string();
+            match fish_wcstoul(&value).ok().map(usize::try_from) {
+                Some(Ok(max_items)) => Some(NonZeroUsize::new(max_items)),
+                _ => {
+                    FLOG!(warning, "Ignoring invalid $fish_history_max_items");
+                    None
+                }
+            }
+        })
+        .flatten();
+    let max_age = match vars.get_unless_empty(L!("fish_history_max_age")) {
+        None => None,
+        Some(var) => match parse_duration(&var.as_string(), Some(SECONDS_PER_DAY)) {
+            Some(max_age) => Some(max_age).filter(|age| !age.is_zero()),
+            None => {
+                FLOG!(warning, "Ignoring invalid $fish_history_max_age");
+                None
+            }
+        },
+    };
+    set_history_retention(HistoryRetention { max_items, max_age });
+}
+
+/// A random identifier for this shell process, which is recorded in the history items it adds.
+static SESSION_ID: Lazy<SessionId> = Lazy::new(|| get_rng().gen());
+
+/// Returns the identifier recorded in history items added by this shell process.
+pub fn current_session_id() -> SessionId {
+    *SESSION_ID
+}
+
 /// If the size of `buffer` is at least `min_size`, output the contents `buffer` to `fd`,
 /// and clear the string.
 fn flush_to_fd(buffer: &mut Vec<u8>, fd: RawFd, min_size: usize) -> std::io::Result<()> {
@@ -204,12 +319,14 @@ fn add_item(&mut self, item: HistoryItem) {
             return;
         }
 
-        // See if it's in the cache. If it is, update the timestamp. If not, we create a new node
-        // and add it. Note that calling get_node promotes the node to the front.
+        // See if it's in the cache. If it is, keep only the newest occurrence of the command. If
+        // not, we create a new node and add it. Note that calling get_mut promotes the node to the
+        // front.
         let key = item.str();
         if let Some(node) = self.get_mut(key) {
-            node.creation_timestamp = SystemTime::max(node.timestamp(), item.timestamp());
-            // What to do about paths here? Let's just ignore them.
+            if item.timestamp() >= node.timestamp() {
+                *node = item;
+            }
         } else {
             self.put(key.to_owned(), item);
         }
@@ -234,6 +351,7 @@ fn history_filename(session_id: &wstr, suffix: &wstr) -> Option<WString> {
 
 pub type PathList = Vec<WString>;
 pub type HistoryIdentifier = u64;
+pub type SessionId = u64;
 
 #[derive(Clone, Debug)]
 pub struct HistoryItem {
@@ -247,6 +365,14 @@ pub struct HistoryItem {
     identifier: HistoryIdentifier,
     /// Whether to write this item to disk.
     persist_mode: PersistenceMode,
+    /// The exit status of the command, if it is known.
+    exit_status: Option<i32>,
+    /// How long the command took to run, if it is known.
+    duration: Option<Duration>,
+    /// The working directory the command was run in.
+    cwd: Option<WString>,
+    /// The shell session that ran the command.
+    session_id: Option<SessionId>,
 }
 
 impl HistoryItem {
@@ -264,6 +390,10 @@ pub fn new(
             required_paths: vec![],
             identifier: ident,
             persist_mode,
+            exit_status: None,
+            duration: None,
+            cwd: None,
+            session_id: None,
         }
     }
 
@@ -278,7 +408,8 @@ pub fn is_empty(&self) -> bool {
     }
 
     /// Returns whether our contents matches a search term.
-    pub fn matches_search(&self, term: &wstr, typ: SearchType, case_sensitive: bool) -> bool {
+    /// Regex searches need a compiled pattern, so they are matched by [`HistorySearch`] instead.
+    fn matches_search(&self, term: &wstr, typ: SearchType, case_sensitive: bool) -> bool {
         // Note that 'term' has already been lowercased when constructing the
         // search object if we're doing a case insensitive search.
         let content_to_match = if case_sensitive {
@@ -315,6 +446,7 @@ pub fn matches_search(&self, term: &wstr, typ: SearchType, case_sensitive: bool)
                 wildcard_match(content_to_match.as_ref(), &pat, false)
             }
             SearchType::ContainsSubsequence => subsequence_in_string(term, &content_to_match),
+            SearchType::Regex => unreachable!("regex searches are matched by HistorySearch"),
             SearchType::MatchEverything => true,
         }
     }
@@ -329,6 +461,47 @@ pub fn should_write_to_disk(&self) -> bool {
         self.persist_mode == PersistenceMode::Disk
     }
 
+    /// Returns the exit status of the command, if it is known.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    /// Returns how long the command took to run, if it is known.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Returns the working directory the command was run in, if it is known.
+    pub fn cwd(&self) -> Option<&wstr> {
+        self.cwd.as_deref()
+    }
+
+    /// Returns the identifier of the shell session that ran the command, if it is known.
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.session_id
+    }
+
+    /// Set the exit status and duration of the command.
+    pub fn set_command_result(&mut self, exit_status: i32, duration: Duration) {
+        self.exit_status = Some(exit_status);
+        self.duration = Some(duration);
+    }
+
+    /// Set the working directory and session the command was run in.
+    pub fn set_run_context(&mut self, cwd: Option<WString>, session_id: Option<SessionId>) {
+        self.cwd = cwd;
+        self.session_id = session_id;
+    }
+
+    /// Take the exit status, duration, working directory and session from another run of the same
+    /// command.
+    fn copy_run_details_from(&mut self, other: &HistoryItem) {
+        self.exit_status = other.exit_status;
+        self.duration = other.duration;
+        self.cwd.clone_from(&other.cwd);
+        self.session_id = other.session_id;
+    }
+
     /// Get the list of arguments which referred to files.
     /// This is used for autosuggestion hinting.
     pub fn get_required_paths(&self) -> &[WString] {
@@ -349,7 +522,10 @@ fn merge(&mut self, item: &HistoryItem) -> bool {
             return false;
         }
 
-        // Ok, merge this item.
+        // Ok, merge this item. The details of the most recent run win.
+        if item.creation_timestamp >= self.creation_timestamp {
+            self.copy_run_details_from(item);
+        }
         self.creation_timestamp = self.creation_timestamp.max(item.creation_timestamp);
         if self.required_paths.len() < item.required_paths.len() {
             self.required_paths = item.required_paths.clone();
@@ -385,6 +561,9 @@ struct HistoryImpl {
     file_contents: Option<HistoryFileContents>,
     /// The file ID of the history file.
     history_file_id: FileId, // INVALID_FILE_ID
+    /// The offset in the history file up to which we have seen its items. Items after it, such as
+    /// those that other shells append, are picked up by incorporate_file_changes().
+    shared_offset: u64, // 0
     /// The boundary timestamp distinguishes old items from new items. Items whose timestamps are <=
     /// the boundary are considered "old". Items whose timestamps are > the boundary are new, and are
     /// ignored by this instance (unless they came from this instance). The timestamp may be adjusted
@@ -398,6 +577,14 @@ struct HistoryImpl {
     loaded_old: bool, // false
     /// List of old items, as offsets into out mmap data.
     old_item_offsets: Vec<usize>,
+    /// The identifier of a command whose exit status and duration we are waiting for.
+    awaiting_command_result: Option<HistoryIdentifier>,
+    /// Whether automatic saving is disabled until the awaited result is known, so that the item
+    /// is written once, together with it.
+    holding_for_command_result: bool,
+    /// Items imported while a command was running, probably the import itself. They are added
+    /// once it finishes, so rewriting the file does not disturb the command's own item.
+    deferred_imports: Vec<HistoryItem>,
 }
 
 /// If set, we gave up on file locking because it took too long.
@@ -475,14 +662,29 @@ fn next_identifier(&mut self) -> HistoryIdentifier {
     /// Figure out the offsets of our file contents.
     fn populate_from_file_contents(&mut self) {
         self.old_item_offsets.clear();
+        self.shared_offset = 0;
         if let Some(file_contents) = &self.file_contents {
+            let contents = file_contents.contents();
             let mut cursor = 0;
-            while let Some(offset) =
-                file_contents.offset_of_next_item(&mut cursor, Some(self.boundary_timestamp))
-            {
+            let mut first_skipped = None;
+            loop {
+                let start = cursor;
+                let offset =
+                    file_contents.offset_of_next_item(&mut cursor, Some(self.boundary_timestamp));
+                // Items from the first one we skip for being newer than our boundary are left for
+                // incorporate_file_changes(). The cursor may be left inside an item, so look for
+                // the start of an item in between.
+                if first_skipped.is_none() {
+                    let end = offset.unwrap_or(contents.len());
+                    first_skipped = find_item_start(&contents[start..end]).map(|pos| start + pos);
+                }
+                let Some(offset) = offset else {
+                    break;
+                };
                 // Remember this item.
                 self.old_item_offsets.push(offset);
             }
+            self.shared_offset = first_skipped.unwrap_or(cursor) as u64;
         }
 
         FLOGF!(history, "Loaded %lu old items", self.old_item_offsets.len());
@@ -572,8 +774,14 @@ fn remove_ephemeral_items(&mut self) {
     }
 
     /// Given the fd of an existing history file, write a new history file to `dst_fd`.
+    /// Unless `apply_retention` is false, items beyond the retention limits are dropped.
     /// Returns false on error, true on success
-    fn rewrite_to_temporary_file(&self, existing_file: Option<&mut File>, dst: &mut File) -> bool {
+    fn rewrite_to_temporary_file(
+        &self,
+        existing_file: Option<&mut File>,
+        dst: &mut File,
+        apply_retention: bool,
+    ) -> bool {
         // We are reading FROM existing_fd and writing TO dst_fd
 
         // Make an LRU cache to save only the last N elements.
@@ -628,6 +836,12 @@ fn rewrite_to_temporary_file(&self, existing_file: Option<&mut File>, dst: &mut
         let mut items: Vec<_> = lru.into_iter().map(|(_key, item)| item).collect();
         items.sort_by_key(HistoryItem::timestamp);
 
+        // Drop items that are too old or too many, keeping the most recent ones.
+        if apply_retention {
+            let retention = *HISTORY_RETENTION.lock().unwrap();
+            retention.apply(&mut items, SystemTime::now());
+        }
+
         // Write them out.
         let mut err = None;
         let mut buffer = Vec::with_capacity(HISTORY_OUTPUT_BUFFER_SIZE + 128);
@@ -651,8 +865,9 @@ fn rewrite_to_temporary_file(&self, existing_file: Option<&mut File>, dst: &mut
         }
     }
 
-    /// Saves history by rewriting the file.
-    fn save_internal_via_rewrite(&mut self) {
+    /// Saves history by rewriting the file, applying the retention limits if `apply_retention`.
+    /// Returns false on error, true on success
+    fn save_internal_via_rewrite(&mut self, apply_retention: bool) -> bool {
         FLOGF!(
             history,
             "Saving %lu items via rewrite",
@@ -663,10 +878,10 @@ fn save_internal_via_rewrite(&mut self) {
         // To do this, we speculatively write a file, and then lock and see if our original file changed
         // Repeat until we succeed or give up
         let Some(possibly_indirect_target_name) = history_filename(&self.name, L!("")) else {
-            return;
+            return false;
         };
         let Some(tmp_name_template) = history_filename(&self.name, L!(".XXXXXX")) else {
-            return;
+            return false;
         };
 
         // If the history file is a symlink, we want to rewrite the real file so long as we can find it.
@@ -675,7 +890,7 @@ fn save_internal_via_rewrite(&mut self) {
 
         // Make our temporary file
         let Some((mut tmp_file, tmp_name)) = create_temporary_file(&tmp_name_template) else {
-            return;
+            return false;
         };
         let mut done = false;
         for _i in 0..MAX_SAVE_TRIES {
@@ -698,7 +913,11 @@ fn save_internal_via_rewrite(&mut self) {
                 .unwrap_or(INVALID_FILE_ID);
 
             // Open any target file, but do not lock it right away
-            if !self.rewrite_to_temporary_file(target_file_before.ok().as_mut(), &mut tmp_file) {
+            if !self.rewrite_to_temporary_file(
+                target_file_before.ok().as_mut(),
+                &mut tmp_file,
+                apply_retention,
+            ) {
                 // Failed to write, no good
                 break;
             }
@@ -808,6 +1027,7 @@ fn save_internal_via_rewrite(&mut self) {
             // file.
             self.clear_file_state();
         }
+        done
     }
 
     /// Saves history by appending to the file.
@@ -860,12 +1080,12 @@ fn save_internal_via_appending(&mut self) -> bool {
                 if file_id != self.history_file_id {
                     file_changed = true;
                 }
-                history_file = Some(file);
+                history_file = Some((file, file_id.size));
                 break;
             }
         }
 
-        if let Some(history_file) = history_file {
+        if let Some((history_file, size_before)) = history_file {
             // We (hopefully successfully) took the exclusive lock. Append to the file.
             // Note that this is sketchy for a few reasons:
             //   - Another shell may have appended its own items with a later timestamp, so our file may
@@ -917,6 +1137,11 @@ fn save_internal_via_appending(&mut self) -> bool {
             // We don't update the mapping since we only appended to the file, and everything we
             // appended remains in our new_items
             self.history_file_id = file_id_for_fd(history_file.as_fd());
+            // If nobody else appended since we last looked, there is nothing to pick up from what
+            // we just wrote.
+            if size_before == self.shared_offset {
+                self.shared_offset = self.history_file_id.size;
+            }
 
             ok = res.is_ok();
 
@@ -962,7 +1187,7 @@ fn save(&mut self, vacuum: bool) {
         }
         if !ok {
             // We did not or could not append; rewrite the file ("vacuum" it).
-            self.save_internal_via_rewrite();
+            self.save_internal_via_rewrite(true);
         }
     }
 
@@ -1011,11 +1236,15 @@ fn new(name: WString) -> Self {
             deleted_items: HashMap::new(),
             file_contents: None,
             history_file_id: INVALID_FILE_ID,
+            shared_offset: 0,
             boundary_timestamp: SystemTime::now(),
             last_identifier: 0,
             countdown_to_vacuum: None,
             loaded_old: false,
             old_item_offsets: Vec::new(),
+            awaiting_command_result: None,
+            holding_for_command_result: false,
+            deferred_imports: vec![],
         }
     }
 
@@ -1075,6 +1304,37 @@ fn remove(&mut self, str_to_remove: &wstr) {
     /// Resolves any pending history items, so that they may be returned in history searches.
     fn resolve_pending(&mut self) {
         self.has_pending_item = false;
+        self.awaiting_command_result = None;
+        self.stop_holding_for_command_result();
+        self.add_deferred_imports();
+    }
+
+    /// Records the exit status and duration of the command we are waiting for, and saves its item
+    /// together with them.
+    fn record_command_result(&mut self, exit_status: i32, duration: Duration) {
+        let Some(ident) = self.awaiting_command_result.take() else {
+            return;
+        };
+        // It is likely to be the last item, unless it was removed in the meantime.
+        if let Some(item) = self
+            .new_items
+            .iter_mut()
+            .rev()
+            .find(|item| item.identifier == ident)
+        {
+            item.set_command_result(exit_status, duration);
+        }
+        self.stop_holding_for_command_result();
+    }
+
+    /// Lets the item of the awaited command be saved, with or without its result.
+    fn stop_holding_for_command_result(&mut self) {
+        if !self.holding_for_command_result {
+            return;
+        }
+        self.holding_for_command_result = false;
+        self.enable_automatic_saving();
+        self.save_unless_disabled();
     }
 
     /// Enable / disable automatic saving. Main thread only!
@@ -1092,6 +1352,7 @@ fn enable_automatic_saving(&mut self) {
     fn clear(&mut self) {
         self.new_items.clear();
         self.deleted_items.clear();
+        self.deferred_imports.clear();
         self.first_unwritten_new_item_index = 0;
         self.old_item_offsets.clear();
         if let Some(filename) = history_filename(&self.name, L!("")) {
@@ -1164,27 +1425,42 @@ fn populate_from_config_path(&mut self) {
     /// `#`s for comments. Ignore a few commands that are bash-specific. It makes no attempt to
     /// handle multiline commands. We can't actually parse bash syntax and the bash history file
     /// does not unambiguously encode multiline commands.
-    fn populate_from_bash<R: BufRead>(&mut self, contents: R) {
-        // Process the entire history file until EOF is observed.
+    fn populate_from_bash<R: BufRead>(&mut self, mut contents: R) {
+        // Process the entire history file until EOF is observed, or an error.
+        let mut buffer = vec![];
+        let _ = contents.read_to_end(&mut buffer);
         // Pretend all items were created at this time.
         let when = self.timestamp_now();
-        for line in contents.split(b'\n') {
-            let Ok(line) = line else {
-                break;
-            };
-            let wide_line = trim(str2wcstring(&line), None);
-            // Add this line if it doesn't contain anything we know we can't handle.
-            if should_import_bash_history_line(&wide_line) {
-                self.add(
-                    HistoryItem::new(wide_line, when, 0, PersistenceMode::Disk),
-                    /*pending=*/ false,
-                    /*do_save=*/ false,
-                );
-            }
+        for item in parse_bash_history(&buffer, when) {
+            self.add(item, /*pending=*/ false, /*do_save=*/ false);
         }
         self.save_unless_disabled();
     }
 
+    /// Add items imported from elsewhere, and rewrite the history file so that they are ordered
+    /// among the existing items by their timestamps. If a command is running, this waits until
+    /// it has finished.
+    fn import(&mut self, items: Vec<HistoryItem>) {
+        self.deferred_imports.extend(items);
+        self.add_deferred_imports();
+    }
+
+    /// Adds the items of [`Self::import`], unless a command is still running.
+    fn add_deferred_imports(&mut self) {
+        if self.deferred_imports.is_empty()
+            || self.has_pending_item
+            || self.awaiting_command_result.is_some()
+        {
+            return;
+        }
+        for item in std::mem::take(&mut self.deferred_imports) {
+            self.add(item, /*pending=*/ false, /*do_save=*/ false);
+        }
+        self.save(true);
+        // Reread the rewritten file so our own view of the history is ordered the same way.
+        self.incorporate_external_changes();
+    }
+
     /// Incorporates the history of other shells into this history.
     fn incorporate_external_changes(&mut self) {
         // To incorporate new items, we simply update our timestamp to now, so that items from previous
@@ -1211,6 +1487,193 @@ fn incorporate_external_changes(&mut self) {
         }
     }
 
+    /// Incorporates the items that other shells have appended to the history file since we last
+    /// read it. Only the appended bytes are read. If the file was replaced instead, for example
+    /// because another shell vacuumed it, our view of it is dropped, to be loaded again when
+    /// needed. Unlike [`Self::incorporate_external_changes`], this keeps the items that are not
+    /// written to disk. Returns whether anything changed.
+    fn incorporate_file_changes(&mut self) -> bool {
+        // Without our view of the file, there is nothing to update; it is read when needed.
+        if !self.loaded_old {
+            return false;
+        }
+        let Some(filename) = history_filename(&self.name, L!("")) else {
+            return false;
+        };
+        let Ok(mut file) = wopen_cloexec(&filename, OFlag::O_RDONLY, Mode::empty()) else {
+            return false;
+        };
+        let file_id = file_id_for_fd(file.as_fd());
+        if file_id.dev_inode != self.history_file_id.dev_inode || file_id.size < self.shared_offset
+        {
+            return self.forget_file_state();
+        }
+        if file_id.size == self.shared_offset {
+            return false;
+        }
+
+        // Take a read lock so we do not see an item that is being appended only in part. As in
+        // load_old_if_needed(), we proceed if locking fails.
+        let locked = unsafe { Self::maybe_lock_file(&mut file, LOCK_SH) };
+        // Another shell may have appended more in the meantime.
+        let file_id = file_id_for_fd(file.as_fd());
+        let mut appended = vec![];
+        let res = file
+            .seek(SeekFrom::Start(self.shared_offset))
+            .and_then(|_| file.read_to_end(&mut appended));
+        if locked {
+            unsafe {
+                Self::unlock_file(&mut file);
+            }
+        }
+        if res.is_err() {
+            return false;
+        }
+        // Leave a trailing incomplete line for next time.
+        let complete_len = appended
+            .iter()
+            .rposition(|&c| c == b'\n')
+            .map_or(0, |pos| pos + 1);
+        appended.truncate(complete_len);
+        self.shared_offset += complete_len as u64;
+        // If we have seen everything, appending to the file does not need to reload it.
+        if self.shared_offset == file_id.size {
+            self.history_file_id = file_id;
+        }
+
+        let mut changed = false;
+        for item in decode_items(&appended) {
+            if self.deleted_items.contains_key(item.str()) {
+                continue;
+            }
+            let when = time_to_seconds(item.timestamp());
+            if self.new_items.iter().rev().any(|known| {
+                known.contents == item.contents && time_to_seconds(known.timestamp()) == when
+            }) {
+                continue;
+            }
+            // Other shells' items go before our pending and unwritten ones, and count as written.
+            let pos = self
+                .first_unwritten_new_item_index
+                .min(self.new_items.len() - usize::from(self.has_pending_item));
+            self.new_items.insert(pos, item);
+            self.first_unwritten_new_item_index += 1;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Drops our view of a history file that was replaced, so that it is loaded again together
+    /// with the items of other shells. Returns whether anything changed.
+    fn forget_file_state(&mut self) -> bool {
+        // Leave the command that is being run alone.
+        if self.has_pending_item || self.awaiting_command_result.is_some() {
+            return false;
+        }
+        // Write our own items first, so they are interleaved with those from other shells.
+        self.save(false);
+        if self.first_unwritten_new_item_index < self.new_items.len() {
+            return false;
+        }
+        let new_timestamp = SystemTime::now();
+        if new_timestamp > self.boundary_timestamp {
+            self.boundary_timestamp = new_timestamp;
+        }
+        self.clear_file_state();
+
+        // Everything we wrote will be read back from the file.
+        self.new_items.retain(|item| !item.should_write_to_disk());
+        self.first_unwritten_new_item_index = self.new_items.len();
+        true
+    }
+
+    /// Returns the byte offsets and kinds of the malformed records in the history file.
+    fn check(&self) -> std::io::Result<Vec<(usize, MalformedRecord)>> {
+        let Some(filename) = history_filename(&self.name, L!("")) else {
+            return Ok(vec![]);
+        };
+        let mut file = match wopen_cloexec(&filename, OFlag::O_RDONLY, Mode::empty()) {
+            Ok(file) => file,
+            Err(nix::Error::ENOENT) => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        Ok(find_malformed_records(&contents))
+    }
+
+    /// If the history file has malformed records, copies it to a new backup file, and then rewrites
+    /// it, dropping anything that could not be decoded. The retention limits are not applied, so
+    /// no readable item is lost. Returns the path of the backup, or None if there was nothing to
+    /// repair.
+    fn repair(&mut self) -> std::io::Result<Option<WString>> {
+        let Some(filename) = history_filename(&self.name, L!("")) else {
+            return Ok(None);
+        };
+        let mut file = match wopen_cloexec(&filename, OFlag::O_RDONLY, Mode::empty()) {
+            Ok(file) => file,
+            Err(nix::Error::ENOENT) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        // Read the file under a lock, so the backup does not catch another shell mid-append.
+        let locked = unsafe { Self::maybe_lock_file(&mut file, LOCK_SH) };
+        let mut contents = vec![];
+        let res = file.read_to_end(&mut contents);
+        if locked {
+            unsafe {
+                Self::unlock_file(&mut file);
+            }
+        }
+        res?;
+        if find_malformed_records(&contents).is_empty() {
+            return Ok(None);
+        }
+
+        let backup_name = self.write_backup(&contents)?;
+        if !self.save_internal_via_rewrite(false) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "could not rewrite the history file",
+            ));
+        }
+        Ok(Some(backup_name))
+    }
+
+    /// Writes `contents` to a new backup file next to the history file, named after the current
+    /// time so that earlier backups are never overwritten. Returns the path of the backup.
+    fn write_backup(&self, contents: &[u8]) -> std::io::Result<WString> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        for attempt in 0..MAX_SAVE_TRIES {
+            let suffix = if attempt == 0 {
+                sprintf!(".%d.bak", now)
+            } else {
+                sprintf!(".%d-%d.bak", now, attempt)
+            };
+            let Some(backup_name) = history_filename(&self.name, &suffix) else {
+                break;
+            };
+            match wopen_cloexec(
+                &backup_name,
+                OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL,
+                HISTORY_FILE_MODE,
+            ) {
+                Ok(mut backup) => {
+                    backup.write_all(contents)?;
+                    return Ok(backup_name);
+                }
+                Err(nix::Error::EEXIST) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "could not find a free name for the backup file",
+        ))
+    }
+
     /// Gets all the history into a list. This is intended for the $history environment variable.
     /// This may be long!
     fn get_history(&mut self) -> Vec<WString> {
@@ -1430,6 +1893,185 @@ fn do_1_history_search(
     }
 }
 
+/// Constraints on the details of history items returned by [`History::search`].
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    /// Only match commands that exited with this status.
+    pub exit_status: Option<i32>,
+    /// Only match commands that were run in this directory or one of its subdirectories.
+    pub cwd: Option<WString>,
+    /// Only match commands that were run in this session.
+    pub session_id: Option<SessionId>,
+    /// Only match commands that were run at or after this time.
+    pub since: Option<SystemTime>,
+    /// Only match commands that were run before this time.
+    pub until: Option<SystemTime>,
+}
+
+impl HistoryFilter {
+    /// Returns whether the filter has no constraints.
+    pub fn is_empty(&self) -> bool {
+        self.exit_status.is_none()
+            && self.cwd.is_none()
+            && self.session_id.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+    }
+
+    /// Returns whether `item` satisfies all constraints.
+    pub fn matches(&self, item: &HistoryItem) -> bool {
+        if self.exit_status.is_some() && item.exit_status() != self.exit_status {
+            return false;
+        }
+        if self.session_id.is_some() && item.session_id() != self.session_id {
+            return false;
+        }
+        if self.since.is_some_and(|since| item.timestamp() < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| item.timestamp() >= until) {
+            return false;
+        }
+        if let Some(dir) = &self.cwd {
+            let Some(cwd) = item.cwd() else {
+                return false;
+            };
+            if !path_is_within(cwd, dir) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Returns whether `path` is `dir` or is inside it.
+fn path_is_within(path: &wstr, dir: &wstr) -> bool {
+    let dir = dir.as_char_slice();
+    let dir = dir.strip_suffix(&['/']).unwrap_or(dir);
+    let path = path.as_char_slice();
+    let Some(rest) = path.strip_prefix(dir) else {
+        return false;
+    };
+    rest.is_empty() || rest[0] == '/'
+}
+
+/// Ranks how close `cwd`, the directory an item was run in, is to `dir`. This is `usize::MAX` if
+/// it is `dir` itself, greater the closer it is if it is a parent of `dir`, and 0 otherwise.
+pub fn directory_affinity(cwd: Option<&wstr>, dir: &wstr) -> usize {
+    let Some(cwd) = cwd else {
+        return 0;
+    };
+    let cwd = cwd.as_char_slice();
+    let cwd = cwd.strip_suffix(&['/']).unwrap_or(cwd);
+    let dir = dir.as_char_slice();
+    let dir = dir.strip_suffix(&['/']).unwrap_or(dir);
+    if cwd == dir {
+        usize::MAX
+    } else if path_is_within(wstr::from_char_slice(dir), wstr::from_char_slice(cwd)) {
+        1 + cwd.len()
+    } else {
+        0
+    }
+}
+
+/// Formats the exit status, duration, working directory and session of a history item as a
+/// comment line. Returns an empty string if none of them are known.
+fn format_history_details(item: &HistoryItem) -> WString {
+    let mut parts = vec![];
+    if let Some(exit_status) = item.exit_status() {
+        parts.push(sprintf!("status: %d", exit_status));
+    }
+    if let Some(duration) = item.duration() {
+        parts.push(sprintf!("duration: %.3fs", duration.as_secs_f64()));
+    }
+    if let Some(session_id) = item.session_id() {
+        parts.push(sprintf!("session: %016x", session_id));
+    }
+    // The directory goes last since it may contain spaces.
+    if let Some(cwd) = item.cwd() {
+        parts.push(sprintf!("cwd: %ls", cwd));
+    }
+    if parts.is_empty() {
+        return WString::new();
+    }
+    let mut result = L!("# ").to_owned();
+    result.push_utfstr(&join_strings(&parts, ' '));
+    result.push('\n');
+    result
+}
+
+/// Parses a point in time for `history search --since/--until`, relative to `now`. This may be
+/// a duration before now like "2h" or "3d", "@" followed by seconds since the epoch, or a date in
+/// UTC with an optional time like "2024-05-01" or "2024-05-01 14:30".
+pub fn parse_history_time(spec: &wstr, now: SystemTime) -> Option<SystemTime> {
+    if let Some(duration) = parse_duration(spec, None) {
+        return now.checked_sub(duration);
+    }
+    if let Some(seconds) = spec.strip_prefix('@') {
+        return fish_wcstol(seconds).ok().map(time_from_seconds);
+    }
+
+    let (date, time) = match spec.chars().position(|c| c == ' ') {
+        Some(pos) => (&spec[..pos], Some(&spec[pos + 1..])),
+        None => (spec, None),
+    };
+    let [year, month, day] = parse_time_fields::<3>(date, '-', &[4, 2, 2])?;
+    let days_in_month = match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    let (hour, minute, second) = match time {
+        None => (0, 0, 0),
+        Some(time) => match parse_time_fields::<3>(time, ':', &[2, 2, 2]) {
+            Some([hour, minute, second]) => (hour, minute, second),
+            None => {
+                let [hour, minute] = parse_time_fields::<2>(time, ':', &[2, 2])?;
+                (hour, minute, 0)
+            }
+        },
+    };
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let seconds = ((days_from_civil(year, month, day) * 24 + hour) * 60 + minute) * 60 + second;
+    Some(time_from_seconds(seconds))
+}
+
+/// Splits `s` at `separator` into exactly `N` fields of the given numbers of digits.
+fn parse_time_fields<const N: usize>(
+    s: &wstr,
+    separator: char,
+    widths: &[usize; N],
+) -> Option<[i64; N]> {
+    let mut fields = [0; N];
+    let mut parts = s.split(separator);
+    for (field, &width) in fields.iter_mut().zip(widths) {
+        let part = parts.next()?;
+        if part.len() != width || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        *field = fish_wcstol(part).ok()?;
+    }
+    parts.next().is_none().then_some(fields)
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Count years from March, so that the leap day is at the end of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
 /// Formats a single history record, including a trailing newline.
 ///
 /// Returns nothing. The only possible failure involves formatting the timestamp. If that happens we
@@ -1437,6 +2079,7 @@ fn do_1_history_search(
 fn format_history_record(
     item: &HistoryItem,
     show_time_format: Option<&str>,
+    show_details: bool,
     null_terminate: bool,
 ) -> WString {
     let mut result = WString::new();
@@ -1464,6 +2107,10 @@ fn format_history_record(
         }
     }
 
+    if show_details {
+        result.push_utfstr(&format_history_details(item));
+    }
+
     result.push_utfstr(item.str());
     result.push(if null_terminate { '\0' } else { '\n' });
     result
@@ -1521,10 +2168,11 @@ pub fn add(&self, item: HistoryItem, pending: bool /*=false*/) {
         self.imp().add(item, pending, true)
     }
 
-    pub fn add_commandline(&self, s: WString) {
+    /// Add a command line with the current timestamp and the given persistence.
+    pub fn add_commandline(&self, s: WString, persist_mode: PersistenceMode) {
         let mut imp = self.imp();
         let when = imp.timestamp_now();
-        let item = HistoryItem::new(s, when, 0, PersistenceMode::Disk);
+        let item = HistoryItem::new(s, when, 0, persist_mode);
         imp.add(item, false, true)
     }
 
@@ -1582,6 +2230,7 @@ pub fn add_pending_with_file_detection(
 
         // Find all arguments that look like they could be file paths.
         let mut needs_sync_write = false;
+        let mut skip_file_detection = false;
         let ast = Ast::parse(s, ParseTreeFlags::empty(), None);
 
         let mut potential_paths = Vec::new();
@@ -1595,9 +2244,7 @@ pub fn add_pending_with_file_detection(
                 // Hack hack hack - if the command is likely to trigger an exit, then don't do
                 // background file detection, because we won't be able to write it to our history file
                 // before we exit.
-                // Also skip it for 'echo'. This is because echo doesn't take file paths, but also
-                // because the history file test wants to find the commands in the history file
-                // immediately after running them, so it can't tolerate the asynchronous file detection.
+                // Also skip it for 'echo', because echo doesn't take file paths.
                 if stmt.decoration() == StatementDecoration::exec {
                     needs_sync_write = true;
                 }
@@ -1605,22 +2252,40 @@ pub fn add_pending_with_file_detection(
                 let source = stmt.command.source(s);
                 let command = unescape_string(source, UnescapeStringStyle::default());
                 let command = command.as_deref().unwrap_or(source);
-                if [L!("exit"), L!("reboot"), L!("restart"), L!("echo")].contains(&command) {
+                if [L!("exit"), L!("reboot"), L!("restart")].contains(&command) {
                     needs_sync_write = true;
+                } else if command == "echo" {
+                    skip_file_detection = true;
                 }
             }
         }
 
         // If we got a path, we'll perform file detection for autosuggestion hinting.
-        let wants_file_detection = !potential_paths.is_empty() && !needs_sync_write;
+        let wants_file_detection =
+            !potential_paths.is_empty() && !needs_sync_write && !skip_file_detection;
         let mut imp = self.imp();
 
         // Make our history item.
         let when = imp.timestamp_now();
         let identifier = imp.next_identifier();
-        let item = HistoryItem::new(s.to_owned(), when, identifier, persist_mode);
+        let mut item = HistoryItem::new(s.to_owned(), when, identifier, persist_mode);
+        item.set_run_context(
+            vars.get(L!("PWD")).map(|var| var.as_string()),
+            Some(current_session_id()),
+        );
         let to_disk = persist_mode == PersistenceMode::Disk;
 
+        // Remember the item so that its exit status and duration can be recorded once the command
+        // has finished. See record_command_result().
+        // Unless we are about to exit, hold back saving like for file detection, so that the item
+        // is written only once, together with its result.
+        imp.awaiting_command_result = Some(identifier);
+        imp.stop_holding_for_command_result();
+        if to_disk && !needs_sync_write {
+            imp.holding_for_command_result = true;
+            imp.disable_automatic_saving();
+        }
+
         if wants_file_detection {
             imp.disable_automatic_saving();
 
@@ -1656,6 +2321,12 @@ pub fn resolve_pending(&self) {
         self.imp().resolve_pending()
     }
 
+    /// Records the exit status and duration of the command most recently added with
+    /// [`Self::add_pending_with_file_detection`], and saves it.
+    pub fn record_command_result(&self, exit_status: i32, duration: Duration) {
+        self.imp().record_command_result(exit_status, duration)
+    }
+
     /// Saves history.
     pub fn save(&self) {
         self.imp().save(false)
@@ -1667,7 +2338,9 @@ pub fn search(
         self: &Arc<Self>,
         search_type: SearchType,
         search_args: &[&wstr],
+        filter: &HistoryFilter,
         show_time_format: Option<&str>,
+        show_details: bool,
         max_items: usize,
         case_sensitive: bool,
         null_terminate: bool,
@@ -1684,8 +2357,12 @@ pub fn search(
             if remaining == 0 {
                 return ControlFlow::Break(());
             }
+            if !filter.matches(item) {
+                return ControlFlow::Continue(());
+            }
             remaining -= 1;
-            let formatted_record = format_history_record(item, show_time_format, null_terminate);
+            let formatted_record =
+                format_history_record(item, show_time_format, show_details, null_terminate);
             if reverse {
                 // We need to collect this for later.
                 collected.push(formatted_record);
@@ -1768,11 +2445,53 @@ pub fn populate_from_bash<R: BufRead>(&self, contents: R) {
         self.imp().populate_from_bash(contents)
     }
 
+    /// Import items, as returned by [`parse_history`], and save them. While a command is running,
+    /// this happens once it has finished.
+    pub fn import(&self, items: Vec<HistoryItem>) {
+        self.imp().import(items)
+    }
+
+    /// Format the commands that are saved to the history file, oldest first, for exporting.
+    /// Only the most recent run of each command is included.
+    pub fn export(&self, format: ExportFormat) -> Vec<u8> {
+        let mut seen = HashSet::new();
+        let mut items: Vec<_> = (1..)
+            .map_while(|idx| self.item_at_index(idx))
+            .filter(|item| item.should_write_to_disk() && seen.insert(item.str().to_owned()))
+            .collect();
+        items.reverse();
+        let mut buffer = vec![];
+        for item in &items {
+            match format {
+                ExportFormat::Fish => append_history_item_to_buffer(item, &mut buffer),
+                ExportFormat::Json => append_history_item_to_json_buffer(item, &mut buffer),
+            }
+        }
+        buffer
+    }
+
     /// Incorporates the history of other shells into this history.
     pub fn incorporate_external_changes(&self) {
         self.imp().incorporate_external_changes()
     }
 
+    /// Incorporates the items that other shells have added to the history file since we last read
+    /// it. This is cheap if the file is unchanged. Returns whether anything changed.
+    pub fn incorporate_file_changes(&self) -> bool {
+        self.imp().incorporate_file_changes()
+    }
+
+    /// Returns the byte offsets and kinds of the malformed records in the history file.
+    pub fn check(&self) -> std::io::Result<Vec<(usize, MalformedRecord)>> {
+        self.imp().check()
+    }
+
+    /// Rewrites the history file without malformed records, keeping a backup of the original.
+    /// Returns the path of the backup, or None if there is no history file.
+    pub fn repair(&self) -> std::io::Result<Option<WString>> {
+        self.imp().repair()
+    }
+
     /// Gets all the history into a list. This is intended for the $history environment variable.
     /// This may be long!
     pub fn get_history(&self) -> Vec<WString> {
@@ -1799,6 +2518,14 @@ pub fn item_at_index(&self, idx: usize) -> Option<HistoryItem> {
     pub fn size(&self) -> usize {
         self.imp().size()
     }
+
+    /// Return the distinct commands containing `needle` as a subsequence, ranked by the quality
+    /// of the match and by how often and how recently they were run, best first.
+    pub fn fuzzy_search(&self, needle: &wstr, flags: SearchFlags) -> Vec<RankedCommand> {
+        let case_sensitive = !flags.contains(SearchFlags::IGNORE_CASE);
+        let items = (1..).map_while(|idx| self.item_at_index(idx));
+        rank_fuzzy_matches(needle, case_sensitive, items, SystemTime::now())
+    }
 }
 
 bitflags! {
@@ -1812,6 +2539,10 @@ pub struct SearchFlags: u32 {
     }
 }
 
+/// How many matches [`HistorySearch::go_to_closest_match`] looks at before settling for the closest
+/// one so far. Older matches are unlikely to be wanted, and looking at all of them is slow.
+pub const CLOSEST_MATCH_CANDIDATES: usize = 256;
+
 /// Support for searching a history backwards.
 /// Note this does NOT de-duplicate; it is the caller's responsibility to do so.
 pub struct HistorySearch {
@@ -1831,6 +2562,8 @@ pub struct HistorySearch {
     current_index: usize, // 0
     /// If deduping, the items we've seen.
     deduper: HashSet<WString>,
+    /// For regex searches, the compiled search term, or None if it is invalid.
+    regex: Option<Regex>,
 }
 
 impl HistorySearch {
@@ -1860,9 +2593,12 @@ pub fn new_with(
             current_item: None,
             current_index: starting_index,
             deduper: HashSet::new(),
+            regex: None,
         };
 
-        if search.ignores_case() {
+        if search_type == SearchType::Regex {
+            search.regex = build_regex(&search.orig_term, search.ignores_case()).ok();
+        } else if search.ignores_case() {
             search.canon_term = search.canon_term.to_lowercase();
         }
 
@@ -1874,6 +2610,21 @@ pub fn original_term(&self) -> &wstr {
         &self.orig_term
     }
 
+    /// Returns the history in which we are searching.
+    pub fn history(&self) -> &Arc<History> {
+        &self.history
+    }
+
+    /// Returns our search type.
+    pub fn search_type(&self) -> SearchType {
+        self.search_type
+    }
+
+    /// Returns our flags.
+    pub fn flags(&self) -> SearchFlags {
+        self.flags
+    }
+
     pub fn prepare_to_search_after_deletion(&mut self) {
         assert!(self.current_index != 0);
         self.current_index -= 1;
@@ -1914,7 +2665,7 @@ pub fn go_to_next_match(&mut self, direction: SearchDirection) -> bool {
             };
 
             // Look for an item that matches and (if deduping) that we haven't seen before.
-            if !item.matches_search(&self.canon_term, self.search_type, !self.ignores_case()) {
+            if !self.matches(&item) {
                 continue;
             }
 
@@ -1930,6 +2681,88 @@ pub fn go_to_next_match(&mut self, direction: SearchDirection) -> bool {
         }
     }
 
+    /// Searches backwards for the match that was run closest to `dir`: commands run in `dir` itself
+    /// are preferred over those run in one of its parents (the closer the better), which are
+    /// preferred over all others. Recency only breaks ties. Matches for which `accept` returns
+    /// false are skipped. Returns `true` if one was found.
+    ///
+    /// This does not deduplicate, as an older occurrence of a command may have been run closer.
+    /// Only the most recent [`CLOSEST_MATCH_CANDIDATES`] matches are considered, whether accepted or
+    /// not, and `accept` is only called for those that would be closer than the best so far, as it
+    /// may be slow.
+    pub fn go_to_closest_match(
+        &mut self,
+        dir: &wstr,
+        accept: impl FnMut(&HistoryItem) -> bool,
+        cancel_check: impl Fn() -> bool,
+    ) -> bool {
+        self.closest_match_pass(dir, accept, cancel_check, /*find_most_recent=*/ false)
+            .0
+    }
+
+    /// Like [`go_to_closest_match`](Self::go_to_closest_match), but also returns the most recent
+    /// accepted match, which is found in the same pass.
+    pub fn go_to_closest_match_and_find_most_recent(
+        &mut self,
+        dir: &wstr,
+        accept: impl FnMut(&HistoryItem) -> bool,
+        cancel_check: impl Fn() -> bool,
+    ) -> (bool, Option<HistoryItem>) {
+        self.closest_match_pass(dir, accept, cancel_check, /*find_most_recent=*/ true)
+    }
+
+    fn closest_match_pass(
+        &mut self,
+        dir: &wstr,
+        mut accept: impl FnMut(&HistoryItem) -> bool,
+        cancel_check: impl Fn() -> bool,
+        find_most_recent: bool,
+    ) -> (bool, Option<HistoryItem>) {
+        let mut best: Option<(usize, usize, HistoryItem)> = None;
+        let mut most_recent = None;
+        let mut index = self.current_index;
+        let mut candidates = 0;
+        while candidates < CLOSEST_MATCH_CANDIDATES {
+            if cancel_check() {
+                return (false, None);
+            }
+            index += 1;
+            let Some(item) = self.history.item_at_index(index) else {
+                break;
+            };
+            if !self.matches(&item) {
+                continue;
+            }
+            candidates += 1;
+
+            // Only an item from a closer directory can beat the one we have, as it is more recent.
+            let affinity = directory_affinity(item.cwd(), dir);
+            // Until one is accepted, there is no best one, so the most recent is found here too.
+            if best
+                .as_ref()
+                .is_some_and(|(best_affinity, _, _)| affinity <= *best_affinity)
+                || !accept(&item)
+            {
+                continue;
+            }
+            if find_most_recent && most_recent.is_none() {
+                most_recent = Some(item.clone());
+            }
+            best = Some((affinity, index, item));
+            if affinity == usize::MAX {
+                break;
+            }
+        }
+
+        let Some((_, index, item)) = best else {
+            self.current_item = None;
+            return (false, most_recent);
+        };
+        self.current_item = Some(item);
+        self.current_index = index;
+        (true, most_recent)
+    }
+
     /// Move current index so there is `value` matches in between new and old indexes
     pub fn search_forward(&mut self, value: usize) {
         while self.go_to_next_match(SearchDirection::Forward) && self.deduper.len() <= value {}
@@ -1968,6 +2801,18 @@ pub fn ignores_case(&self) -> bool {
     fn dedup(&self) -> bool {
         !self.flags.contains(SearchFlags::NO_DEDUP)
     }
+
+    /// Returns whether `item` matches our search term.
+    fn matches(&self, item: &HistoryItem) -> bool {
+        if self.search_type != SearchType::Regex {
+            return item.matches_search(&self.canon_term, self.search_type, !self.ignores_case());
+        }
+        // Lowercasing would change the meaning of the pattern, so PCRE2 handles case. The regex is
+        // compiled once, when the search is created.
+        self.regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(item.str().as_char_slice()).unwrap_or(false))
+    }
 }
 
 /// Saves the new history to disk.
@@ -2078,6 +2923,89 @@ pub fn in_private_mode(vars: &dyn Environment) -> bool {
     vars.get_unless_empty(L!("fish_private_mode")).is_some()
 }
 
+/// The path of the history file that we watch for writes by other shells, and the watch itself.
+static HISTORY_FILE_WATCH: Mutex<(Option<WString>, Option<Box<dyn UniversalNotifier>>)> =
+    Mutex::new((None, None));
+
+/// Watches the file of the given history for writes by other shells, or stops watching if there is
+/// none. The watch fd is added to the reader's select() set; see [`history_file_watch_fd`].
+pub fn watch_history_file(history: Option<&History>) {
+    let path = history.and_then(|history| history_filename(&history.imp().name, L!("")));
+    let mut watch = HISTORY_FILE_WATCH.lock().unwrap();
+    if watch.0 != path {
+        watch.1 = path.as_deref().and_then(create_file_watch);
+        watch.0 = path;
+    }
+}
+
+/// Returns the fd that becomes readable when the watched history file is written, if any.
+pub fn history_file_watch_fd() -> Option<RawFd> {
+    HISTORY_FILE_WATCH
+        .lock()
+        .unwrap()
+        .1
+        .as_ref()?
+        .notification_fd()
+}
+
+/// The fd returned by [`history_file_watch_fd`] is readable; drain it. Returns whether the history
+/// file was written.
+pub fn history_file_watch_became_readable(fd: RawFd) -> bool {
+    HISTORY_FILE_WATCH
+        .lock()
+        .unwrap()
+        .1
+        .as_ref()
+        .is_some_and(|watch| watch.notification_fd_became_readable(fd))
+}
+
+/// Returns whether `$fish_history_share` asks for picking up commands from other shells as soon as
+/// they are run.
+pub fn history_sharing_enabled(vars: &dyn Environment) -> bool {
+    vars.get_unless_empty(L!("fish_history_share"))
+        .is_some_and(|var| var.as_string() != "0")
+}
+
+/// Returns whether `text` matches one of the regular expressions in
+/// `$fish_history_secret_patterns`. Such commands are kept out of the history file.
+pub fn matches_secret_pattern(vars: &dyn Environment, text: &wstr) -> bool {
+    let Some(patterns) = vars.get_unless_empty(L!("fish_history_secret_patterns")) else {
+        return false;
+    };
+    let patterns = patterns.as_list();
+
+    // Only compile the patterns again when the variable changes.
+    let mut cache = SECRET_PATTERNS.lock().unwrap();
+    if !cache.as_ref().is_some_and(|(cached, _)| cached == patterns) {
+        let regexes = patterns
+            .iter()
+            .filter_map(|pattern| match build_regex(pattern, false) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    FLOG!(
+                        warning,
+                        wgettext_fmt!(
+                            "Ignoring invalid pattern '%ls' in $fish_history_secret_patterns: %ls",
+                            pattern,
+                            &WString::from(err.error_message())
+                        )
+                    );
+                    None
+                }
+            })
+            .collect();
+        *cache = Some((patterns.to_vec(), regexes));
+    }
+    let (_, regexes) = cache.as_ref().unwrap();
+    regexes
+        .iter()
+        .any(|regex| regex.is_match(text.as_char_slice()).unwrap_or(false))
+}
+
+/// The value of `$fish_history_secret_patterns` that was last used, and its valid patterns,
+/// compiled.
+static SECRET_PATTERNS: Mutex<Option<(Vec<WString>, Vec<Regex>)>> = Mutex::new(None);
+
 /// Whether to force the read path instead of mmap. This is useful for testing.
 static NEVER_MMAP: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 
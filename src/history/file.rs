@@ -206,6 +206,22 @@ pub fn append_history_item_to_buffer(item: &HistoryItem, buffer: &mut Vec<u8>) {
     buffer.extend(&cmd);
     buffer.push(b'\n');
     writeln!(buffer, "  when: {}", time_to_seconds(item.timestamp())).unwrap();
+    if let Some(exit_status) = item.exit_status() {
+        writeln!(buffer, "  status: {}", exit_status).unwrap();
+    }
+    if let Some(duration) = item.duration() {
+        writeln!(buffer, "  duration: {}", duration.as_millis()).unwrap();
+    }
+    if let Some(cwd) = item.cwd() {
+        let mut cwd = wcs2string(cwd);
+        escape_yaml_fish_2_0(&mut cwd);
+        buffer.extend(b"  cwd: ");
+        buffer.extend(&cwd);
+        buffer.push(b'\n');
+    }
+    if let Some(session_id) = item.session_id() {
+        writeln!(buffer, "  session: {:x}", session_id).unwrap();
+    }
 
     let paths = item.get_required_paths();
     if !paths.is_empty() {
@@ -380,6 +396,10 @@ fn decode_item_fish_2_0(mut data: &[u8]) -> Option<HistoryItem> {
     let mut indent = None;
     let mut when = UNIX_EPOCH;
     let mut paths = Vec::new();
+    let mut exit_status = None;
+    let mut duration = None;
+    let mut cwd = None;
+    let mut session_id = None;
     loop {
         let (advance, line) = read_line(data);
 
@@ -404,6 +424,21 @@ fn decode_item_fish_2_0(mut data: &[u8]) -> Option<HistoryItem> {
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(0),
             );
+        } else if *key == *b"status" {
+            exit_status = std::str::from_utf8(&value)
+                .ok()
+                .and_then(|s| s.parse().ok());
+        } else if *key == *b"duration" {
+            duration = std::str::from_utf8(&value)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_millis);
+        } else if *key == *b"cwd" {
+            cwd = Some(str2wcstring(&value));
+        } else if *key == *b"session" {
+            session_id = std::str::from_utf8(&value)
+                .ok()
+                .and_then(|s| u64::from_str_radix(s, 16).ok());
         } else if *key == *b"paths" {
             // Read lines starting with " - " until we can't read any more.
             loop {
@@ -428,10 +463,32 @@ fn decode_item_fish_2_0(mut data: &[u8]) -> Option<HistoryItem> {
 
     let mut result = HistoryItem::new(cmd, when, 0, PersistenceMode::Disk);
     result.set_required_paths(paths);
+    // Imported commands may have a duration but no exit status.
+    result.exit_status = exit_status;
+    result.duration = duration;
+    result.set_run_context(cwd, session_id);
     Some(result)
 }
 
-fn time_from_seconds(offset: i64) -> SystemTime {
+/// Decode all items in the contents of a history file, oldest first.
+pub fn decode_items(contents: &[u8]) -> Vec<HistoryItem> {
+    let mut items = vec![];
+    let mut cursor = 0;
+    while let Some(offset) = offset_of_next_item_fish_2_0(contents, &mut cursor, None) {
+        items.extend(decode_item_fish_2_0(&contents[offset..]));
+    }
+    items
+}
+
+/// Returns the offset of the first line in `contents` that starts an item.
+pub fn find_item_start(contents: &[u8]) -> Option<usize> {
+    if contents.starts_with(b"- cmd") {
+        return Some(0);
+    }
+    subslice_position(contents, b"\n- cmd").map(|pos| pos + 1)
+}
+
+pub fn time_from_seconds(offset: i64) -> SystemTime {
     if let Ok(n) = u64::try_from(offset) {
         UNIX_EPOCH + Duration::from_secs(n)
     } else {
@@ -471,6 +528,74 @@ fn complete_lines(s: &[u8]) -> impl Iterator<Item = &[u8]> {
     lines
 }
 
+/// A kind of damage found in a history file by [`find_malformed_records`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MalformedRecord {
+    /// A line that neither starts an item nor belongs to one.
+    StrayLine,
+    /// A line starting with NUL bytes, as left behind by crashes on some filesystems.
+    NulBytes,
+    /// An item with a repeated "- cmd:" prefix, as written by fish 1.x.
+    RepeatedPrefix,
+    /// An item whose command is only a timestamp, as written by fish 1.x.
+    MissingCommand,
+    /// An item whose timestamp cannot be parsed.
+    BadTimestamp,
+    /// A last line without a terminating newline, as left behind by an interrupted write.
+    Truncated,
+}
+
+/// Returns the byte offsets and kinds of the malformed lines in the given history file contents.
+/// These are the lines that the decoder skips or only partially understands.
+pub fn find_malformed_records(contents: &[u8]) -> Vec<(usize, MalformedRecord)> {
+    let mut result = vec![];
+    // Whether we are in the interior of an item. Interior lines of damaged items are not
+    // reported separately.
+    let mut in_item = false;
+    let mut offset = 0;
+    while offset < contents.len() {
+        let (advance, mut line) = read_line(&contents[offset..]);
+        let complete = contents[offset + advance - 1] == b'\n';
+        let problem = if !complete {
+            Some(MalformedRecord::Truncated)
+        } else if line.starts_with(b" ") {
+            if !in_item {
+                Some(MalformedRecord::StrayLine)
+            } else if trim_start(line).starts_with(b"when:") && parse_timestamp(line).is_none() {
+                Some(MalformedRecord::BadTimestamp)
+            } else {
+                None
+            }
+        } else if line.starts_with(b"%") || line.starts_with(b"---") || line.starts_with(b"...") {
+            in_item = false;
+            None
+        } else {
+            in_item = true;
+            let mut repeated = false;
+            while line.starts_with(b"- cmd: - cmd: ") {
+                line = line.strip_prefix(b"- cmd: ").unwrap();
+                repeated = true;
+            }
+            if line.starts_with(b"\0") {
+                Some(MalformedRecord::NulBytes)
+            } else if line.starts_with(b"- cmd:    when:") {
+                Some(MalformedRecord::MissingCommand)
+            } else if !line.starts_with(b"- cmd") {
+                Some(MalformedRecord::StrayLine)
+            } else if repeated {
+                Some(MalformedRecord::RepeatedPrefix)
+            } else {
+                None
+            }
+        };
+        if let Some(problem) = problem {
+            result.push((offset, problem));
+        }
+        offset += advance;
+    }
+    result
+}
+
 /// Support for iteratively locating the offsets of history items.
 /// Pass the file contents and a mutable reference to a `cursor`, initially 0.
 /// If `cutoff_timestamp` is given, skip items created at or after that timestamp.
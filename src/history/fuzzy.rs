@@ -0,0 +1,254 @@
+//! Ranked fuzzy matching of history items, as used by the history pager.
+//!
+//! Scoring follows the approach of fzf: every matched character earns points, with bonuses for
+//! matching at word boundaries and for runs of consecutive characters, and penalties for the gaps
+//! in between. The best-scoring alignment is found with a small dynamic program. The match score
+//! is then combined with the "frecency" of the command, i.e. how often and how recently it was
+//! run, so that among similar matches the commands the user actually relies on come first.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use super::HistoryItem;
+use crate::wchar::prelude::*;
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+/// Bonus for matching the first character of a word.
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+/// Bonus for matching at a camelCase or letter-to-digit transition.
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY - 1;
+/// Minimum bonus for each character continuing a consecutive run.
+/// This is chosen so that a run always beats the same characters separated by a gap.
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+/// The first character of the search string matters most, so its bonus is amplified.
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+/// Points per doubling of the frecency of a command.
+const FRECENCY_WEIGHT: i64 = 2;
+
+/// A fuzzy match of a search string in a command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// The quality of the match; higher is better.
+    pub score: i64,
+    /// The indices of the matched characters in the command, in ascending order.
+    pub positions: Vec<usize>,
+}
+
+/// A command matching a fuzzy history search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankedCommand {
+    /// The command.
+    pub text: WString,
+    /// The match score combined with the frecency of the command; higher is better.
+    pub score: i64,
+    /// The indices of the matched characters in the command.
+    pub positions: Vec<usize>,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+/// Return the bonus for matching the character at index `idx` of `haystack`.
+fn position_bonus(haystack: &[char], idx: usize) -> i64 {
+    let c = haystack[idx];
+    if !is_word_char(c) {
+        return 0;
+    }
+    let Some(&prev) = idx.checked_sub(1).and_then(|i| haystack.get(i)) else {
+        return BONUS_BOUNDARY;
+    };
+    if !is_word_char(prev) {
+        BONUS_BOUNDARY
+    } else if (prev.is_lowercase() && c.is_uppercase()) || (!prev.is_numeric() && c.is_numeric()) {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// The tables of the dynamic program in [`fuzzy_match_with`], kept around so that matching many
+/// commands does not allocate for each of them.
+#[derive(Default)]
+struct FuzzyTables {
+    bonus: Vec<i64>,
+    score: Vec<Option<i64>>,
+    run_bonus: Vec<i64>,
+    from: Vec<usize>,
+}
+
+/// Find the best-scoring way to match the characters of `needle`, in order, in `haystack`.
+/// Return None if `needle` is not a subsequence of `haystack`.
+pub fn fuzzy_match(needle: &wstr, haystack: &wstr, case_sensitive: bool) -> Option<FuzzyMatch> {
+    fuzzy_match_with(
+        &mut FuzzyTables::default(),
+        needle,
+        haystack,
+        case_sensitive,
+    )
+}
+
+/// Like [`fuzzy_match`], but reusing the given tables.
+fn fuzzy_match_with(
+    tables: &mut FuzzyTables,
+    needle: &wstr,
+    haystack: &wstr,
+    case_sensitive: bool,
+) -> Option<FuzzyMatch> {
+    let needle = needle.as_char_slice();
+    let haystack = haystack.as_char_slice();
+    let (n, m) = (needle.len(), haystack.len());
+    if n == 0 {
+        return Some(FuzzyMatch::default());
+    }
+    if n > m {
+        return None;
+    }
+    let chars_match =
+        |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+
+    // Cheaply reject commands that cannot match at all, which is most of them.
+    let mut remaining = needle.iter();
+    let mut want = remaining.next();
+    for &c in haystack {
+        if want.is_some_and(|&w| chars_match(w, c)) {
+            want = remaining.next();
+        }
+    }
+    if want.is_some() {
+        return None;
+    }
+
+    let FuzzyTables {
+        bonus,
+        score,
+        run_bonus,
+        from,
+    } = tables;
+    bonus.clear();
+    bonus.extend((0..m).map(|j| position_bonus(haystack, j)));
+    // For needle[i] matched at haystack[j], stored at i * m + j:
+    // the best score of needle[..=i], the bonus of the consecutive run it ends,
+    // and where needle[i - 1] was matched.
+    score.clear();
+    score.resize(n * m, None);
+    run_bonus.clear();
+    run_bonus.resize(n * m, 0);
+    from.clear();
+    from.resize(n * m, 0);
+    for i in 0..n {
+        // The best way to match needle[i - 1] somewhere before j - 1, paying for the gap up to j.
+        let mut gap: Option<(i64, usize)> = None;
+        for j in i..m {
+            if i > 0 && j >= 2 {
+                if let Some(gap) = gap.as_mut() {
+                    gap.0 += SCORE_GAP_EXTENSION;
+                }
+                if let Some(prev) = score[(i - 1) * m + j - 2] {
+                    if gap.map_or(true, |(best, _)| prev + SCORE_GAP_START >= best) {
+                        gap = Some((prev + SCORE_GAP_START, j - 2));
+                    }
+                }
+            }
+            if !chars_match(needle[i], haystack[j]) {
+                continue;
+            }
+            let idx = i * m + j;
+            if i == 0 {
+                score[idx] = Some(SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+                run_bonus[idx] = bonus[j];
+                continue;
+            }
+            if let Some((gapped, k)) = gap {
+                score[idx] = Some(gapped + SCORE_MATCH + bonus[j]);
+                run_bonus[idx] = bonus[j];
+                from[idx] = k;
+            }
+            if let Some(prev) = score[idx - m - 1] {
+                let consecutive_bonus = run_bonus[idx - m - 1].max(bonus[j]).max(BONUS_CONSECUTIVE);
+                let consecutive = prev + SCORE_MATCH + consecutive_bonus;
+                if score[idx].map_or(true, |s| consecutive >= s) {
+                    score[idx] = Some(consecutive);
+                    run_bonus[idx] = consecutive_bonus;
+                    from[idx] = j - 1;
+                }
+            }
+        }
+    }
+
+    let (mut j, best) = (0..m)
+        .filter_map(|j| score[(n - 1) * m + j].map(|s| (j, s)))
+        .max_by_key(|&(j, s)| (s, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = j;
+        j = from[i * m + j];
+    }
+    Some(FuzzyMatch {
+        score: best,
+        positions,
+    })
+}
+
+/// Return the weight of a single use of a command that is `age` old.
+fn recency_weight(age: Duration) -> u64 {
+    const DAY: u64 = 24 * 60 * 60;
+    match age.as_secs() / DAY {
+        0..=3 => 100,
+        4..=13 => 70,
+        14..=30 => 50,
+        31..=89 => 30,
+        _ => 10,
+    }
+}
+
+/// Match `needle` against the commands of `items`, which must be ordered from newest to oldest,
+/// and return the distinct matching commands, best first.
+/// Each use of a command adds to its frecency, which breaks ties between similar matches;
+/// remaining ties are broken by recency.
+pub fn rank_fuzzy_matches(
+    needle: &wstr,
+    case_sensitive: bool,
+    items: impl Iterator<Item = HistoryItem>,
+    now: SystemTime,
+) -> Vec<RankedCommand> {
+    // Maps each command seen so far to its index in `matches`, or None if it did not match.
+    let mut seen: HashMap<WString, Option<usize>> = HashMap::new();
+    let mut matches: Vec<(RankedCommand, u64)> = vec![];
+    let mut tables = FuzzyTables::default();
+    for item in items {
+        let weight = recency_weight(now.duration_since(item.timestamp()).unwrap_or_default());
+        if let Some(idx) = seen.get(item.str()) {
+            if let Some(idx) = *idx {
+                matches[idx].1 += weight;
+            }
+            continue;
+        }
+        let Some(m) = fuzzy_match_with(&mut tables, needle, item.str(), case_sensitive) else {
+            seen.insert(item.str().to_owned(), None);
+            continue;
+        };
+        seen.insert(item.str().to_owned(), Some(matches.len()));
+        let ranked = RankedCommand {
+            text: item.str().to_owned(),
+            score: m.score,
+            positions: m.positions,
+        };
+        matches.push((ranked, weight));
+    }
+
+    let mut result: Vec<RankedCommand> = matches
+        .into_iter()
+        .map(|(mut ranked, frecency)| {
+            ranked.score += FRECENCY_WEIGHT * i64::from(u64::BITS - frecency.leading_zeros());
+            ranked
+        })
+        .collect();
+    // The sort is stable, so equal scores stay ordered by recency.
+    result.sort_by_key(|ranked| std::cmp::Reverse(ranked.score));
+    result
+}
@@ -0,0 +1,479 @@
+//! Exporting history to, and importing history from, formats other than our history file.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    time::{Duration, SystemTime},
+};
+
+use super::file::{decode_items, time_from_seconds, time_to_seconds};
+use super::{should_import_bash_history_line, HistoryItem, PersistenceMode};
+use crate::common::str2wcstring;
+use crate::wchar::decode_byte_from_char;
+use crate::wchar::prelude::*;
+use crate::wcstringutil::trim;
+
+/// Formats that history can be exported to or imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// Our own history file format.
+    Fish,
+    /// One JSON object per command, with the same fields as our history file.
+    Json,
+    /// The bash history file: one command per line, without timestamps.
+    Bash,
+    /// The zsh history file, with or without the timestamps of its EXTENDED_HISTORY option.
+    Zsh,
+}
+
+impl HistoryFormat {
+    pub fn from_name(name: &wstr) -> Option<Self> {
+        match name {
+            _ if name == "fish" => Some(HistoryFormat::Fish),
+            _ if name == "json" => Some(HistoryFormat::Json),
+            _ if name == "bash" => Some(HistoryFormat::Bash),
+            _ if name == "zsh" => Some(HistoryFormat::Zsh),
+            _ => None,
+        }
+    }
+
+    /// Returns the format to export history in, if it can be exported in this one.
+    /// Exports to the formats of other shells would lose information.
+    pub fn for_export(self) -> Option<ExportFormat> {
+        match self {
+            HistoryFormat::Fish => Some(ExportFormat::Fish),
+            HistoryFormat::Json => Some(ExportFormat::Json),
+            HistoryFormat::Bash | HistoryFormat::Zsh => None,
+        }
+    }
+}
+
+/// Formats that history can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Our own history file format.
+    Fish,
+    /// One JSON object per command, with the same fields as our history file.
+    Json,
+}
+
+/// Append a string to a buffer as a JSON string literal. Bytes that are not valid UTF-8, which we
+/// keep as code points in a private use area, are written as escapes of those code points, and
+/// turned back into the bytes by [`JsonParser::parse_string`].
+fn append_json_string(s: &wstr, buffer: &mut Vec<u8>) {
+    buffer.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => buffer.extend(b"\\\""),
+            '\\' => buffer.extend(b"\\\\"),
+            '\n' => buffer.extend(b"\\n"),
+            '\t' => buffer.extend(b"\\t"),
+            '\r' => buffer.extend(b"\\r"),
+            '\0'..='\x1f' | '\x7f' => write!(buffer, "\\u{:04x}", u32::from(c)).unwrap(),
+            _ if decode_byte_from_char(c).is_some() => {
+                write!(buffer, "\\u{:04x}", u32::from(c)).unwrap()
+            }
+            _ => buffer.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    buffer.push(b'"');
+}
+
+/// Append a history item to a buffer as a line of JSON, in preparation for exporting it.
+/// The fields are named like the keys in the history file.
+pub fn append_history_item_to_json_buffer(item: &HistoryItem, buffer: &mut Vec<u8>) {
+    assert!(item.should_write_to_disk(), "Item should not be persisted");
+
+    buffer.extend(b"{\"cmd\":");
+    append_json_string(item.str(), buffer);
+    write!(buffer, ",\"when\":{}", time_to_seconds(item.timestamp())).unwrap();
+    if let Some(exit_status) = item.exit_status() {
+        write!(buffer, ",\"status\":{}", exit_status).unwrap();
+    }
+    if let Some(duration) = item.duration() {
+        write!(buffer, ",\"duration\":{}", duration.as_millis()).unwrap();
+    }
+    if let Some(cwd) = item.cwd() {
+        buffer.extend(b",\"cwd\":");
+        append_json_string(cwd, buffer);
+    }
+    if let Some(session_id) = item.session_id() {
+        write!(buffer, ",\"session\":\"{:x}\"", session_id).unwrap();
+    }
+    let paths = item.get_required_paths();
+    if !paths.is_empty() {
+        buffer.extend(b",\"paths\":[");
+        for (i, path) in paths.iter().enumerate() {
+            if i > 0 {
+                buffer.push(b',');
+            }
+            append_json_string(path, buffer);
+        }
+        buffer.push(b']');
+    }
+    buffer.extend(b"}\n");
+}
+
+enum JsonValue {
+    /// null, true or false, none of which any history field takes.
+    Literal,
+    Number(f64),
+    String(Vec<u8>),
+    Array(Vec<JsonValue>),
+    Object(Vec<(Vec<u8>, JsonValue)>),
+}
+
+/// A minimal JSON parser, sufficient for reading back history entries.
+struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    /// Nesting deeper than this is not plausible for history entries.
+    const MAX_DEPTH: usize = 32;
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `c`, after any whitespace.
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_literal(&mut self, literal: &[u8]) -> bool {
+        if self.data[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Option<JsonValue> {
+        if depth > Self::MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                let mut fields = vec![];
+                if self.eat(b'}') {
+                    return Some(JsonValue::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.parse_string()?;
+                    if !self.eat(b':') {
+                        return None;
+                    }
+                    fields.push((key, self.parse_value(depth + 1)?));
+                    if self.eat(b'}') {
+                        return Some(JsonValue::Object(fields));
+                    }
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut values = vec![];
+                if self.eat(b']') {
+                    return Some(JsonValue::Array(values));
+                }
+                loop {
+                    values.push(self.parse_value(depth + 1)?);
+                    if self.eat(b']') {
+                        return Some(JsonValue::Array(values));
+                    }
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+            }
+            b'"' => self.parse_string().map(JsonValue::String),
+            b'-' | b'0'..=b'9' => self.parse_number().map(JsonValue::Number),
+            _ if self.eat_literal(b"null")
+                || self.eat_literal(b"true")
+                || self.eat_literal(b"false") =>
+            {
+                Some(JsonValue::Literal)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<f64> {
+        let start = self.pos;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let digits = self.data.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+
+    /// Parse a string literal into its bytes, which are UTF-8 unless the input was not.
+    fn parse_string(&mut self) -> Option<Vec<u8>> {
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.pos += 1;
+        let mut result = vec![];
+        loop {
+            let c = self.peek()?;
+            self.pos += 1;
+            match c {
+                b'"' => return Some(result),
+                b'\\' => {
+                    let escaped = self.peek()?;
+                    self.pos += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => result.push(escaped),
+                        b'b' => result.push(b'\x08'),
+                        b'f' => result.push(b'\x0c'),
+                        b'n' => result.push(b'\n'),
+                        b'r' => result.push(b'\r'),
+                        b't' => result.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            // Characters outside the BMP are written as surrogate pairs. A high
+                            // surrogate must be followed by a low one; lone surrogates are
+                            // rejected by char::from_u32() below.
+                            if (0xd800..0xdc00).contains(&code) {
+                                if !self.eat_literal(b"\\u") {
+                                    return None;
+                                }
+                                let low = self.parse_hex4()?;
+                                if !(0xdc00..=0xdfff).contains(&low) {
+                                    return None;
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c = char::from_u32(code)?;
+                            match decode_byte_from_char(c) {
+                                Some(byte) => result.push(byte),
+                                None => result.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                            }
+                        }
+                        _ => return None,
+                    }
+                }
+                _ => result.push(c),
+            }
+        }
+    }
+}
+
+fn history_item_from_json(value: JsonValue, now: SystemTime) -> Option<HistoryItem> {
+    let JsonValue::Object(fields) = value else {
+        return None;
+    };
+    let mut cmd = None;
+    let mut when = now;
+    let mut paths = vec![];
+    let mut exit_status = None;
+    let mut duration = None;
+    let mut cwd = None;
+    let mut session_id = None;
+    for (key, value) in fields {
+        match (key.as_slice(), value) {
+            (b"cmd", JsonValue::String(s)) => cmd = Some(str2wcstring(&s)),
+            (b"when", JsonValue::Number(n)) => when = time_from_seconds(n as i64),
+            (b"status", JsonValue::Number(n)) => exit_status = Some(n as i32),
+            (b"duration", JsonValue::Number(n)) if n >= 0.0 => {
+                duration = Some(Duration::from_millis(n as u64))
+            }
+            (b"cwd", JsonValue::String(s)) => cwd = Some(str2wcstring(&s)),
+            (b"session", JsonValue::String(s)) => {
+                session_id = std::str::from_utf8(&s)
+                    .ok()
+                    .and_then(|s| u64::from_str_radix(s, 16).ok())
+            }
+            (b"paths", JsonValue::Array(values)) => {
+                paths = values
+                    .into_iter()
+                    .filter_map(|value| match value {
+                        JsonValue::String(s) => Some(str2wcstring(&s)),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            // Ignore unknown fields, and nulls for missing details.
+            _ => (),
+        }
+    }
+
+    let mut result = HistoryItem::new(cmd?, when, 0, PersistenceMode::Disk);
+    result.set_required_paths(paths);
+    result.exit_status = exit_status;
+    result.duration = duration;
+    result.set_run_context(cwd, session_id);
+    Some(result)
+}
+
+/// Parse exported JSON history: a sequence of objects like those written by
+/// [`append_history_item_to_json_buffer`], optionally enclosed in an array.
+/// Items without a timestamp are taken to have been run at `now`.
+/// Returns the byte offset of the first invalid item on error.
+pub fn parse_json_history(contents: &[u8], now: SystemTime) -> Result<Vec<HistoryItem>, usize> {
+    let mut parser = JsonParser {
+        data: contents,
+        pos: 0,
+    };
+    let in_array = parser.eat(b'[');
+    let mut items = vec![];
+    loop {
+        if in_array {
+            if parser.eat(b']') {
+                break;
+            }
+            if !items.is_empty() && !parser.eat(b',') {
+                return Err(parser.pos);
+            }
+        } else {
+            parser.skip_whitespace();
+            if parser.peek().is_none() {
+                break;
+            }
+        }
+        parser.skip_whitespace();
+        let offset = parser.pos;
+        let item = parser
+            .parse_value(0)
+            .and_then(|value| history_item_from_json(value, now))
+            .ok_or(offset)?;
+        items.push(item);
+    }
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.pos);
+    }
+    Ok(items)
+}
+
+/// Zsh "metafies" some bytes in its history file: they are written as this byte, followed by the
+/// original byte xor 32.
+const ZSH_META: u8 = 0x83;
+
+fn zsh_unmetafy(contents: &[u8]) -> Cow<'_, [u8]> {
+    if !contents.contains(&ZSH_META) {
+        return Cow::Borrowed(contents);
+    }
+    let mut result = Vec::with_capacity(contents.len());
+    let mut bytes = contents.iter();
+    while let Some(&c) = bytes.next() {
+        if c == ZSH_META {
+            if let Some(&next) = bytes.next() {
+                result.push(next ^ 32);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Split off the `: <start time>:<elapsed seconds>;` prefix zsh writes with EXTENDED_HISTORY.
+fn parse_zsh_extended_prefix(line: &[u8]) -> Option<(i64, u64, &[u8])> {
+    let rest = line.strip_prefix(b": ")?;
+    let colon = rest.iter().position(|&c| c == b':')?;
+    let semicolon = colon + rest[colon..].iter().position(|&c| c == b';')?;
+    let when = std::str::from_utf8(&rest[..colon])
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let elapsed = std::str::from_utf8(&rest[colon + 1..semicolon])
+        .ok()?
+        .parse()
+        .ok()?;
+    Some((when, elapsed, &rest[semicolon + 1..]))
+}
+
+/// Parse a zsh history file. Commands without a timestamp are taken to have been run at `now`.
+pub fn parse_zsh_history(contents: &[u8], now: SystemTime) -> Vec<HistoryItem> {
+    let contents = zsh_unmetafy(contents);
+    let mut items = vec![];
+    let mut lines = contents.split(|&c| c == b'\n');
+    while let Some(line) = lines.next() {
+        let mut line = Cow::Borrowed(line);
+        // Newlines within a command are escaped with a backslash.
+        while line.last() == Some(&b'\\') {
+            let Some(next) = lines.next() else {
+                break;
+            };
+            let line = line.to_mut();
+            *line.last_mut().unwrap() = b'\n';
+            line.extend(next);
+        }
+        let (when, duration, command) = match parse_zsh_extended_prefix(&line) {
+            Some((when, elapsed, command)) => (
+                time_from_seconds(when),
+                Some(Duration::from_secs(elapsed)),
+                command,
+            ),
+            None => (now, None, &line[..]),
+        };
+        if command.is_empty() {
+            continue;
+        }
+        let mut item = HistoryItem::new(str2wcstring(command), when, 0, PersistenceMode::Disk);
+        item.duration = duration;
+        items.push(item);
+    }
+    items
+}
+
+/// Parse a bash history file. Bash does not record when commands were run, so they are all taken
+/// to have been run at `now`. Multiline commands cannot be told apart from separate commands.
+pub fn parse_bash_history(contents: &[u8], now: SystemTime) -> Vec<HistoryItem> {
+    contents
+        .split(|&c| c == b'\n')
+        .map(|line| trim(str2wcstring(line), None))
+        // Skip lines containing anything we know we can't handle.
+        .filter(|line| should_import_bash_history_line(line))
+        .map(|line| HistoryItem::new(line, now, 0, PersistenceMode::Disk))
+        .collect()
+}
+
+/// Parse history in the given format, oldest first, for importing. Commands without a timestamp
+/// are taken to have been run at `now`.
+/// Returns the byte offset of the first invalid item if the history cannot be parsed.
+pub fn parse_history(
+    format: HistoryFormat,
+    contents: &[u8],
+    now: SystemTime,
+) -> Result<Vec<HistoryItem>, usize> {
+    match format {
+        HistoryFormat::Fish => Ok(decode_items(contents)),
+        HistoryFormat::Json => parse_json_history(contents, now),
+        HistoryFormat::Bash => Ok(parse_bash_history(contents, now)),
+        HistoryFormat::Zsh => Ok(parse_zsh_history(contents, now)),
+    }
+}
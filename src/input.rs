@@ -153,8 +153,10 @@ const fn make_md(name: &'static wstr, code: ReadlineCmd) -> InputFunctionMetadat
     make_md(L!("clear-screen"), ReadlineCmd::ClearScreenAndRepaint),
     make_md(L!("complete"), ReadlineCmd::Complete),
     make_md(L!("complete-and-search"), ReadlineCmd::CompleteAndSearch),
+    make_md(L!("correct-command"), ReadlineCmd::CorrectCommand),
     make_md(L!("delete-char"), ReadlineCmd::DeleteChar),
     make_md(L!("delete-or-exit"), ReadlineCmd::DeleteOrExit),
+    make_md(L!("digit-argument"), ReadlineCmd::DigitArgument),
     make_md(L!("down-line"), ReadlineCmd::DownLine),
     make_md(L!("downcase-word"), ReadlineCmd::DowncaseWord),
     make_md(L!("end-of-buffer"), ReadlineCmd::EndOfBuffer),
@@ -178,6 +180,8 @@ const fn make_md(name: &'static wstr, code: ReadlineCmd) -> InputFunctionMetadat
     make_md(L!("history-pager"), ReadlineCmd::HistoryPager),
     #[allow(deprecated)]
     make_md(L!("history-pager-delete"), ReadlineCmd::HistoryPagerDelete),
+    make_md(L!("history-pager-toggle-fuzzy"), ReadlineCmd::HistoryPagerToggleFuzzy),
+    make_md(L!("history-pager-toggle-regex"), ReadlineCmd::HistoryPagerToggleRegex),
     make_md(L!("history-prefix-search-backward"), ReadlineCmd::HistoryPrefixSearchBackward),
     make_md(L!("history-prefix-search-forward"), ReadlineCmd::HistoryPrefixSearchForward),
     make_md(L!("history-search-backward"), ReadlineCmd::HistorySearchBackward),
@@ -216,6 +220,9 @@ const fn make_md(name: &'static wstr, code: ReadlineCmd) -> InputFunctionMetadat
     make_md(L!("undo"), ReadlineCmd::Undo),
     make_md(L!("up-line"), ReadlineCmd::UpLine),
     make_md(L!("upcase-word"), ReadlineCmd::UpcaseWord),
+    make_md(L!("vi-change"), ReadlineCmd::ViChange),
+    make_md(L!("vi-delete"), ReadlineCmd::ViDelete),
+    make_md(L!("vi-yank"), ReadlineCmd::ViYank),
     make_md(L!("yank"), ReadlineCmd::Yank),
     make_md(L!("yank-pop"), ReadlineCmd::YankPop),
 ];
@@ -442,6 +449,10 @@ fn uvar_change_notified(&mut self) {
         self.parser.sync_uvars_and_fire(true /* always */);
     }
 
+    fn history_change_notified(&mut self) {
+        self.push_back(CharEvent::Implicit(ImplicitEvent::HistoryChanged));
+    }
+
     fn ioport_notified(&mut self) {
         iothread_service_main(self);
     }
@@ -882,12 +893,15 @@ fn mapping_execute(&mut self, m: &InputMapping) {
                 Some(code) => {
                     self.function_push_args(code);
                     // At this point, the sequence is only used for reinserting the keys into
-                    // the event queue for self-insert. Modifiers make no sense here so drop them.
+                    // the event queue for self-insert, where modifiers make no sense, so drop them.
+                    // The exception is digit-argument, which is typically bound to alt-digits.
                     CharEvent::from_readline_seq(
                         code,
                         m.seq
                             .iter()
-                            .filter(|key| key.modifiers.is_none())
+                            .filter(|key| {
+                                code == ReadlineCmd::DigitArgument || key.modifiers.is_none()
+                            })
                             .map(|key| key.codepoint)
                             .collect(),
                     )
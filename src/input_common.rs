@@ -1,5 +1,6 @@
 use libc::STDOUT_FILENO;
 
+use crate::clipboard::parse_osc52_response;
 use crate::common::{
     fish_reserved_codepoint, is_windows_subsystem_for_linux, read_blocked, shell_modes,
     str2wcstring, write_loop, ScopeGuard, WSL,
@@ -9,6 +10,7 @@
 use crate::flog::{FloggableDebug, FLOG};
 use crate::fork_exec::flog_safe::FLOG_SAFE;
 use crate::global_safety::RelaxedAtomicBool;
+use crate::history::{history_file_watch_became_readable, history_file_watch_fd};
 use crate::key::{
     self, alt, canonicalize_control_char, canonicalize_keyed_control_char, char_to_symbol, ctrl,
     function_key, shift, Key, Modifiers, ViewportPosition,
@@ -67,6 +69,8 @@ pub enum ReadlineCmd {
     HistoryPager,
     #[deprecated]
     HistoryPagerDelete,
+    HistoryPagerToggleRegex,
+    HistoryPagerToggleFuzzy,
     DeleteChar,
     BackwardDeleteChar,
     KillLine,
@@ -123,6 +127,7 @@ pub enum ReadlineCmd {
     FuncAnd,
     FuncOr,
     ExpandAbbr,
+    CorrectCommand,
     DeleteOrExit,
     Exit,
     ClearCommandline,
@@ -133,6 +138,10 @@ pub enum ReadlineCmd {
     BeginUndoGroup,
     EndUndoGroup,
     RepeatJump,
+    ViDelete,
+    ViChange,
+    ViYank,
+    DigitArgument,
     ClearScreenAndRepaint,
     ScrollbackPush,
     // NOTE: This one has to be last.
@@ -199,6 +208,10 @@ pub enum ImplicitEvent {
     MouseLeftClickContinuation(ViewportPosition, ViewportPosition),
     /// Push prompt to top.
     ScrollbackPushContinuation(usize),
+    /// The contents of the terminal's clipboard, in response to an OSC 52 query.
+    ClipboardContents(WString),
+    /// Another shell wrote to the history file.
+    HistoryChanged,
 }
 
 #[derive(Debug, Clone)]
@@ -297,9 +310,9 @@ pub fn from_check_exit() -> CharEvent {
 static WAIT_ON_SEQUENCE_KEY_MS: AtomicUsize = AtomicUsize::new(WAIT_ON_SEQUENCE_KEY_INFINITE);
 
 /// Internal function used by readch to read one byte.
-/// This calls select() on three fds: input (e.g. stdin), the ioport notifier fd (for main thread
-/// requests), and the uvar notifier. This returns either the byte which was read, or one of the
-/// special values below.
+/// This calls select() on four fds: input (e.g. stdin), the ioport notifier fd (for main thread
+/// requests), the uvar notifier and the history file watch. This returns either the byte which was
+/// read, or one of the special values below.
 enum ReadbResult {
     // A byte was successfully read.
     Byte(u8),
@@ -313,6 +326,9 @@ enum ReadbResult {
     // Our uvar notifier reported a change (either through poll() or its fd).
     UvarNotified,
 
+    // The history file watch reported that another shell wrote to the history file.
+    HistoryNotified,
+
     // Our ioport reported a change, so service main thread requests.
     IOPortNotified,
 
@@ -337,6 +353,12 @@ fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
             fdset.add(notifier_fd);
         }
 
+        // Get the history file watch fd (possibly none).
+        let history_fd = history_file_watch_fd();
+        if let Some(history_fd) = history_fd {
+            fdset.add(history_fd);
+        }
+
         // Here's where we call select().
         let select_res = fdset.check_readable(if blocking {
             Timeout::Forever
@@ -356,7 +378,7 @@ fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
 
         if blocking {
             // select() did not return an error, so we may have a readable fd.
-            // The priority order is: uvars, stdin, ioport.
+            // The priority order is: uvars, history, stdin, ioport.
             // Check to see if we want a universal variable barrier.
             if let Some(notifier_fd) = notifier_fd {
                 if fdset.test(notifier_fd) && notifier.notification_fd_became_readable(notifier_fd)
@@ -364,6 +386,11 @@ fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
                     return ReadbResult::UvarNotified;
                 }
             }
+            if let Some(history_fd) = history_fd {
+                if fdset.test(history_fd) && history_file_watch_became_readable(history_fd) {
+                    return ReadbResult::HistoryNotified;
+                }
+            }
         }
 
         // Check stdin.
@@ -440,8 +467,19 @@ pub fn update_wait_on_sequence_key_ms(vars: &EnvStack) {
     }
 }
 
+// Update whether focus reporting is wanted for notifications in response to the
+// fish_notify_threshold user variable being set.
+pub fn update_focus_reporting_for_notifications(vars: &EnvStack) {
+    FOCUS_REPORTING_FOR_NOTIFICATIONS
+        .store(vars.get_unless_empty(L!("fish_notify_threshold")).is_some());
+}
+
 static TERMINAL_PROTOCOLS: AtomicBool = AtomicBool::new(false);
 static BRACKETED_PASTE: AtomicBool = AtomicBool::new(false);
+/// Whether focus reporting is needed to tell if a notification should be sent.
+static FOCUS_REPORTING_FOR_NOTIFICATIONS: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
+/// Whether focus reporting was enabled along with bracketed paste, so it is disabled with it.
+static FOCUS_REPORTING: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 
 pub(crate) static SCROLL_FORWARD_SUPPORTED: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 pub(crate) static CURSOR_UP_SUPPORTED: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
@@ -519,7 +557,8 @@ pub fn terminal_protocols_enable_ifn() {
     if !BRACKETED_PASTE.load(Ordering::Relaxed) {
         BRACKETED_PASTE.store(true, Ordering::Release);
         let _ = write_loop(&STDOUT_FILENO, b"\x1b[?2004h");
-        if IS_TMUX.load() {
+        if IS_TMUX.load() || FOCUS_REPORTING_FOR_NOTIFICATIONS.load() {
+            FOCUS_REPORTING.store(true);
             let _ = write_loop(&STDOUT_FILENO, "\x1b[?1004h".as_bytes()); // focus reporting
         }
         did_write.store(true);
@@ -554,7 +593,7 @@ pub(crate) fn terminal_protocols_disable_ifn() {
     if BRACKETED_PASTE.load(Ordering::Acquire) {
         BRACKETED_PASTE.store(false, Ordering::Release);
         let _ = write_loop(&STDOUT_FILENO, b"\x1b[?2004l");
-        if IS_TMUX.load() {
+        if FOCUS_REPORTING.swap(false) {
             let _ = write_loop(&STDOUT_FILENO, "\x1b[?1004l".as_bytes());
         }
         did_write.store(true);
@@ -646,6 +685,9 @@ pub enum Queried {
 pub enum BlockingWait {
     Startup(Queried),
     CursorPosition(CursorPositionWait),
+    /// Waiting for the response to an OSC 52 clipboard query, which is followed by a Primary DA
+    /// query in case the terminal does not answer it.
+    Clipboard,
 }
 
 /// A trait which knows how to produce a stream of input events.
@@ -712,6 +754,10 @@ fn try_readch(&mut self, blocking: bool) -> Option<CharEvent> {
                     self.uvar_change_notified();
                 }
 
+                ReadbResult::HistoryNotified => {
+                    self.history_change_notified();
+                }
+
                 ReadbResult::IOPortNotified => {
                     self.ioport_notified();
                 }
@@ -859,6 +905,13 @@ fn parse_escape_sequence(
             // potential DCS
             return Some(self.parse_dcs(buffer).unwrap_or(invalid));
         }
+        if !recursive_invocation
+            && next == b']'
+            && *self.blocking_wait() == Some(BlockingWait::Clipboard)
+        {
+            // OSC 52 response. Other OSCs are not expected, so alt-] still works otherwise.
+            return Some(self.parse_osc(buffer).unwrap_or(invalid));
+        }
         match canonicalize_control_char(next) {
             Some(mut key) => {
                 key.modifiers.alt = true;
@@ -1066,7 +1119,7 @@ fn parse_csi(&mut self, buffer: &mut Vec<u8>) -> Option<Key> {
                     return None;
                 };
                 match wait {
-                    BlockingWait::Startup(_) => {}
+                    BlockingWait::Startup(_) | BlockingWait::Clipboard => {}
                     BlockingWait::CursorPosition(_) => {
                         // TODO: re-queue it I guess.
                         FLOG!(
@@ -1365,6 +1418,43 @@ fn parse_dcs(&mut self, buffer: &mut Vec<u8>) -> Option<Key> {
         return None;
     }
 
+    fn parse_osc(&mut self, buffer: &mut Vec<u8>) -> Option<Key> {
+        assert!(buffer.len() == 2);
+        // The response may be too long to arrive in one read, so block until it is complete.
+        // The Primary DA response is sent after it, so it ends the sequence if it is unterminated.
+        loop {
+            let ReadbResult::Byte(next) = readb(self.get_in_fd(), /*blocking=*/ true) else {
+                return None;
+            };
+            buffer.push(next);
+            if next == b'\x07' {
+                buffer.pop();
+                break;
+            }
+            if buffer[buffer.len() - 2] != b'\x1b' {
+                continue;
+            }
+            if next == b'\\' {
+                buffer.pop();
+                buffer.pop();
+                break;
+            }
+            if next == b'[' {
+                FLOG!(reader, "Received unterminated OSC sequence");
+                return self.parse_csi(&mut vec![b'\x1b', b'[']);
+            }
+        }
+        // \e ] 52 ; Pc ; Pd BEL
+        let Some(contents) = parse_osc52_response(&buffer[2..]) else {
+            return invalid_sequence(buffer);
+        };
+        FLOG!(reader, "Received clipboard contents from the terminal");
+        self.push_front(CharEvent::Implicit(ImplicitEvent::ClipboardContents(
+            contents,
+        )));
+        None
+    }
+
     fn readch_timed_esc(&mut self) -> Option<CharEvent> {
         self.readch_timed(WAIT_ON_ESCAPE_MS.load(Ordering::Relaxed))
     }
@@ -1574,6 +1664,10 @@ fn enqueue_interrupt_key(&mut self) {
     /// The default does nothing.
     fn uvar_change_notified(&mut self) {}
 
+    /// Override point for when select() is interrupted by the history file watch.
+    /// The default does nothing.
+    fn history_change_notified(&mut self) {}
+
     /// Override point for when the ioport is ready.
     /// The default does nothing.
     fn ioport_notified(&mut self) {}
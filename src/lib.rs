@@ -32,6 +32,7 @@
 pub mod ast;
 pub mod autoload;
 pub mod builtins;
+pub mod clipboard;
 pub mod color;
 pub mod complete;
 pub mod editable_line;
@@ -79,10 +80,13 @@
 pub mod proc;
 pub mod re;
 pub mod reader;
+pub mod reader_autopair;
 pub mod reader_history_search;
+pub mod reader_vi;
 pub mod redirection;
 pub mod screen;
 pub mod signal;
+pub mod spelling;
 pub mod terminal;
 pub mod termsize;
 pub mod threads;
@@ -1,5 +1,5 @@
 // Generic output functions.
-use crate::color::RgbColor;
+use crate::color::{RgbColor, UnderlineStyle};
 use crate::common::{self, wcs2string_appending};
 use crate::env::EnvVar;
 use crate::terminal::{self, tparm1, Term};
@@ -117,11 +117,16 @@ pub struct Outputter {
     /// Background.
     last_color2: RgbColor,
 
+    /// Underline color.
+    last_underline_color: RgbColor,
+
     was_bold: bool,
-    was_underline: bool,
+    was_underline: Option<UnderlineStyle>,
     was_italics: bool,
     was_dim: bool,
     was_reverse: bool,
+    was_strikethrough: bool,
+    was_blink: bool,
 }
 
 impl Outputter {
@@ -134,11 +139,14 @@ const fn new_from_fd(fd: RawFd) -> Self {
             fd,
             last_color: RgbColor::NORMAL,
             last_color2: RgbColor::NORMAL,
+            last_underline_color: RgbColor::NORMAL,
             was_bold: false,
-            was_underline: false,
+            was_underline: None,
             was_italics: false,
             was_dim: false,
             was_reverse: false,
+            was_strikethrough: false,
+            was_blink: false,
         }
     }
 
@@ -148,11 +156,14 @@ pub fn new_buffering() -> Self {
     }
 
     fn reset_modes(&mut self) {
+        self.last_underline_color = RgbColor::NORMAL;
         self.was_bold = false;
-        self.was_underline = false;
+        self.was_underline = None;
         self.was_italics = false;
         self.was_dim = false;
         self.was_reverse = false;
+        self.was_strikethrough = false;
+        self.was_blink = false;
     }
 
     fn maybe_flush(&mut self) {
@@ -195,6 +206,67 @@ pub fn write_color(&mut self, color: RgbColor, is_fg: bool) -> bool {
         true
     }
 
+    /// Unconditionally start underlining in the given style. Terminals that don't advertise
+    /// styled underlines get a single one instead.
+    /// Exported for builtin_set_color's usage only.
+    pub fn write_underline(&mut self, style: UnderlineStyle, term: &Term) -> bool {
+        if style != UnderlineStyle::Single {
+            if let Some(set_underline_style) = &term.set_underline_style {
+                let param = match style {
+                    UnderlineStyle::Single => 1,
+                    UnderlineStyle::Double => 2,
+                    UnderlineStyle::Curly => 3,
+                    UnderlineStyle::Dotted => 4,
+                    UnderlineStyle::Dashed => 5,
+                };
+                if self.tputs_if_some(&tparm1(set_underline_style, param)) {
+                    return true;
+                }
+            }
+        }
+        self.tputs_if_some(&term.enter_underline_mode)
+    }
+
+    /// Unconditionally write the underline color, if the terminal advertises colored underlines.
+    /// A special color like RgbColor::NORMAL means the terminal's default, which is the text color.
+    /// Exported for builtin_set_color's usage only.
+    pub fn write_underline_color(&mut self, color: RgbColor) -> bool {
+        let Some(term) = terminal::term() else {
+            return false;
+        };
+        let Some(set_underline_color) = &term.set_underline_color else {
+            return false;
+        };
+        if color.is_special() {
+            self.tputs_bytes(b"\x1B[59m");
+        } else if get_color_support().contains(ColorSupport::TERM_24BIT) && color.is_rgb() {
+            let rgb = color.to_color24();
+            let param = (i32::from(rgb.r) << 16) | (i32::from(rgb.g) << 8) | i32::from(rgb.b);
+            // Some terminfo entries (like kitty's) have a stray "%;" here, which we can't expand.
+            if !self.tputs_if_some(&tparm1(set_underline_color, param)) {
+                write!(self, "\x1B[58:2::{}:{}:{}m", rgb.r, rgb.g, rgb.b)
+                    .expect("Outputter::write should never fail");
+            }
+        } else {
+            write!(self, "\x1B[58:5:{}m", index_for_color(color))
+                .expect("Outputter::write should never fail");
+        }
+        true
+    }
+
+    /// Sets the underline color. Like set_color(), this only writes something if the color
+    /// changed. Since set_color() may reset all attributes, this needs to be called after it.
+    pub fn set_underline_color(&mut self, color: RgbColor) {
+        let color = if color.is_special() {
+            RgbColor::NORMAL
+        } else {
+            color
+        };
+        if self.last_underline_color != color && self.write_underline_color(color) {
+            self.last_underline_color = color;
+        }
+    }
+
     /// Sets the fg and bg color. May be called as often as you like, since if the new color is the same
     /// as the previous, nothing will be written. Negative values for set_color will also be ignored.
     /// Since the terminfo string this function emits can potentially cause the screen to flicker, the
@@ -221,13 +293,15 @@ pub fn set_color(&mut self, mut fg: RgbColor, mut bg: RgbColor) {
         let term: &Term = &term;
         let Term {
             enter_bold_mode,
-            enter_underline_mode,
             exit_underline_mode,
             enter_italics_mode,
             exit_italics_mode,
             enter_dim_mode,
             enter_reverse_mode,
             enter_standout_mode,
+            enter_strikethrough_mode,
+            exit_strikethrough_mode,
+            enter_blink_mode,
             exit_attribute_mode,
             ..
         } = term;
@@ -239,10 +313,12 @@ pub fn set_color(&mut self, mut fg: RgbColor, mut bg: RgbColor) {
         let mut bg_set = false;
         let mut last_bg_set = false;
         let is_bold = fg.is_bold() || bg.is_bold();
-        let is_underline = fg.is_underline() || bg.is_underline();
+        let underline = fg.underline_style().or(bg.underline_style());
         let is_italics = fg.is_italics() || bg.is_italics();
         let is_dim = fg.is_dim() || bg.is_dim();
         let is_reverse = fg.is_reverse() || bg.is_reverse();
+        let is_strikethrough = fg.is_strikethrough() || bg.is_strikethrough();
+        let is_blink = fg.is_blink() || bg.is_blink();
 
         if fg.is_reset() || bg.is_reset() {
             #[allow(unused_assignments)]
@@ -260,8 +336,10 @@ pub fn set_color(&mut self, mut fg: RgbColor, mut bg: RgbColor) {
         if (self.was_bold && !is_bold)
             || (self.was_dim && !is_dim)
             || (self.was_reverse && !is_reverse)
+            || (self.was_blink && !is_blink)
+            || (self.was_strikethrough && !is_strikethrough && exit_strikethrough_mode.is_none())
         {
-            // Only way to exit bold/dim/reverse mode is a reset of all attributes.
+            // Only way to exit bold/dim/reverse/blink mode is a reset of all attributes.
             self.tputs(exit_attribute_mode);
             self.last_color = normal;
             self.last_color2 = normal;
@@ -331,15 +409,20 @@ pub fn set_color(&mut self, mut fg: RgbColor, mut bg: RgbColor) {
             }
         }
 
-        // Lastly, we set bold, underline, italics, dim, and reverse modes correctly.
+        // Lastly, we set bold, underline, italics, dim, reverse, strikethrough and blink modes
+        // correctly.
         if is_bold && !self.was_bold && !bg_set && self.tputs_if_some(enter_bold_mode) {
             self.was_bold = is_bold;
         }
 
-        if !self.was_underline && is_underline && self.tputs_if_some(enter_underline_mode) {
-            self.was_underline = is_underline;
-        } else if self.was_underline && !is_underline && self.tputs_if_some(exit_underline_mode) {
-            self.was_underline = is_underline;
+        if self.was_underline != underline {
+            if let Some(style) = underline {
+                if self.write_underline(style, term) {
+                    self.was_underline = underline;
+                }
+            } else if self.tputs_if_some(exit_underline_mode) {
+                self.was_underline = underline;
+            }
         }
 
         if self.was_italics && !is_italics && self.tputs_if_some(exit_italics_mode) {
@@ -361,6 +444,23 @@ pub fn set_color(&mut self, mut fg: RgbColor, mut bg: RgbColor) {
                 self.was_reverse = is_reverse;
             }
         }
+
+        if self.was_strikethrough
+            && !is_strikethrough
+            && self.tputs_if_some(exit_strikethrough_mode)
+        {
+            self.was_strikethrough = is_strikethrough;
+        } else if !self.was_strikethrough
+            && is_strikethrough
+            && self.tputs_if_some(enter_strikethrough_mode)
+        {
+            self.was_strikethrough = is_strikethrough;
+        }
+
+        if is_blink && !self.was_blink && self.tputs_if_some(enter_blink_mode) {
+            self.was_blink = is_blink;
+        }
+        // N.B. there is no exit_blink_mode in terminfo, it's handled by exit_attribute_mode above.
     }
 
     /// Write a wide character to the receiver.
@@ -385,6 +485,12 @@ pub fn contents(&self) -> &[u8] {
         &self.contents
     }
 
+    /// Discard the "output" contents after the first `len` bytes, for output that is buffered but
+    /// turned out to be unnecessary.
+    pub fn truncate_contents(&mut self, len: usize) {
+        self.contents.truncate(len);
+    }
+
     /// Output any buffered data to the given `fd`.
     fn flush_to(&mut self, fd: RawFd) {
         if fd >= 0 && !self.contents.is_empty() {
@@ -494,20 +600,25 @@ pub fn best_color(candidates: &[RgbColor], support: ColorSupport) -> RgbColor {
 #[allow(clippy::collapsible_else_if)]
 pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
     let mut is_bold = false;
-    let mut is_underline = false;
+    let mut underline = None;
     let mut is_italics = false;
     let mut is_dim = false;
     let mut is_reverse = false;
+    let mut is_strikethrough = false;
+    let mut is_blink = false;
 
     let mut candidates: Vec<RgbColor> = Vec::new();
 
     let prefix = L!("--background=");
 
     let mut next_is_background = false;
+    let mut next_is_underline_color = false;
     let mut color_name = WString::new();
     for next in var.as_list() {
         color_name.clear();
-        if is_background {
+        if next_is_underline_color {
+            next_is_underline_color = false;
+        } else if is_background {
             if color_name.is_empty() && next_is_background {
                 color_name = next.to_owned();
                 next_is_background = false;
@@ -530,7 +641,15 @@ pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
             if next == "--bold" || next == "-o" {
                 is_bold = true;
             } else if next == "--underline" || next == "-u" {
-                is_underline = true;
+                underline = Some(UnderlineStyle::Single);
+            } else if let Some(style) = next.strip_prefix(L!("--underline=")) {
+                underline = UnderlineStyle::from_wstr(style).or(underline);
+            } else if next == "--underline-color" {
+                next_is_underline_color = true;
+            } else if next == "--strikethrough" || next == "-s" {
+                is_strikethrough = true;
+            } else if next == "--blink" {
+                is_blink = true;
             } else if next == "--italics" || next == "-i" {
                 is_italics = true;
             } else if next == "--dim" || next == "-d" {
@@ -555,9 +674,39 @@ pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
         result = RgbColor::NORMAL;
     }
     result.set_bold(is_bold);
-    result.set_underline(is_underline);
+    result.set_underline_style(underline);
     result.set_italics(is_italics);
     result.set_dim(is_dim);
     result.set_reverse(is_reverse);
+    result.set_strikethrough(is_strikethrough);
+    result.set_blink(is_blink);
     result
 }
+
+/// Return the underline color given with `--underline-color` in a color variable, or
+/// RgbColor::NORMAL if there is none.
+pub fn parse_underline_color(var: &EnvVar) -> RgbColor {
+    let prefix = L!("--underline-color=");
+    let mut candidates: Vec<RgbColor> = Vec::new();
+    let mut next_is_underline_color = false;
+    for next in var.as_list() {
+        let color_name = if next_is_underline_color {
+            next_is_underline_color = false;
+            next.as_utfstr()
+        } else if let Some(color_name) = next.strip_prefix(prefix) {
+            color_name
+        } else {
+            next_is_underline_color = next == "--underline-color";
+            continue;
+        };
+        if let Some(color) = RgbColor::from_wstr(color_name) {
+            candidates.push(color);
+        }
+    }
+    let result = best_color(&candidates, get_color_support());
+    if result.is_none() {
+        RgbColor::NORMAL
+    } else {
+        result
+    }
+}
@@ -1250,6 +1250,12 @@ fn process_completions_into_infos(lst: &[Completion]) -> Vec<PagerComp> {
                 None,
             );
             assert!(comp_info.comp.last().unwrap().len() >= comp_info.colors.len());
+            for &pos in &comp.match_positions {
+                if let Some(color) = comp_info.colors.get_mut(pos) {
+                    color.foreground = HighlightRole::search_match;
+                    color.background = HighlightRole::search_match;
+                }
+            }
         }
 
         // Append the mangled description.
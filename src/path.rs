@@ -9,6 +9,7 @@
 #[cfg(not(target_os = "linux"))]
 use crate::libc::{MNT_LOCAL, ST_LOCAL};
 use crate::wchar::prelude::*;
+use crate::wutil::dir_iter::DirIter;
 use crate::wutil::{normalize_path, path_normalize_for_cd, waccess, wdirname, wstat};
 use errno::{errno, set_errno, Errno};
 use libc::{EACCES, ENOENT, ENOTDIR, F_OK, X_OK};
@@ -273,6 +274,29 @@ pub fn path_get_paths(cmd: &wstr, vars: &dyn Environment) -> Vec<WString> {
     paths
 }
 
+/// Return the names of the executable files in the directory `dir`, like the ones that
+/// path_get_paths() would find there.
+pub fn path_get_executables(dir: &wstr) -> Vec<WString> {
+    let mut names = vec![];
+    let Ok(mut iter) = DirIter::new(dir) else {
+        return names;
+    };
+    while let Some(entry) = iter.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let mut path = dir.to_owned();
+        append_path_component(&mut path, &entry.name);
+        if path_check_executable(&path).is_ok() {
+            names.push(entry.name.clone());
+        }
+    }
+    names
+}
+
 fn path_get_path_core<S: AsRef<wstr>>(cmd: &wstr, pathsv: &[S]) -> GetPathResult {
     let noent_res = GetPathResult::new(Some(Errno(ENOENT)), WString::new());
     // Test if the given path can be executed.
@@ -1,4 +1,5 @@
 use crate::wchar::prelude::*;
+use pcre2::utf32::{Regex, RegexBuilder};
 
 /// Adjust a pattern so that it is anchored at both beginning and end.
 /// This is a workaround for the fact that PCRE2_ENDANCHORED is unavailable on pre-2017 PCRE2
@@ -14,6 +15,13 @@ pub fn regex_make_anchored(pattern: &wstr) -> WString {
     anchored
 }
 
+/// Compile a user-supplied pattern, for matching against whole strings like history items.
+pub fn build_regex(pattern: &wstr, caseless: bool) -> Result<Regex, pcre2::Error> {
+    RegexBuilder::new()
+        .caseless(caseless)
+        .build(pattern.as_char_slice())
+}
+
 /// Copy a wstr to a Box<[char]>.
 pub fn to_boxed_chars(s: &wstr) -> Box<[char]> {
     let chars = s.as_char_slice();
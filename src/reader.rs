@@ -42,10 +42,11 @@
 
 use errno::{errno, Errno};
 
-use crate::abbrs::abbrs_match;
+use crate::abbrs::{abbrs_complete_key, abbrs_match};
 use crate::ast::{self, Ast, Category, Traversal};
 use crate::builtins::shared::STATUS_CMD_ERROR;
 use crate::builtins::shared::STATUS_CMD_OK;
+use crate::clipboard::{self, osc52_copy_sequence, OSC52_PASTE_QUERY};
 use crate::color::RgbColor;
 use crate::common::restore_term_foreground_process_group_for_exit;
 use crate::common::{
@@ -61,6 +62,7 @@
 use crate::editable_line::{line_at_cursor, range_of_line_at_cursor, Edit, EditableLine};
 use crate::env::EnvStack;
 use crate::env::{EnvMode, Environment, Statuses};
+use crate::env_dispatch::report_working_directory;
 use crate::exec::exec_subshell;
 use crate::expand::expand_one;
 use crate::expand::{expand_string, expand_tilde, ExpandFlags, ExpandResultCode};
@@ -72,11 +74,13 @@
 use crate::future::IsSomeAnd;
 use crate::global_safety::RelaxedAtomicBool;
 use crate::highlight::{
-    autosuggest_validate_from_history, highlight_shell, HighlightRole, HighlightSpec,
+    autosuggest_validate_from_history, command_is_valid, highlight_shell, HighlightRole,
+    HighlightSpec,
 };
 use crate::history::{
-    history_session_id, in_private_mode, History, HistorySearch, PersistenceMode, SearchDirection,
-    SearchType,
+    directory_affinity, history_session_id, history_sharing_enabled, in_private_mode,
+    matches_secret_pattern, watch_history_file, History, HistoryItem, HistorySearch,
+    PersistenceMode, RankedCommand, SearchDirection, SearchType,
 };
 use crate::input::init_input;
 use crate::input_common::kitty_progressive_enhancements_query;
@@ -108,7 +112,7 @@
 use crate::pager::{PageRendering, Pager, SelectionMotion};
 use crate::panic::AT_EXIT;
 use crate::parse_constants::SourceRange;
-use crate::parse_constants::{ParseTreeFlags, ParserTestErrorBits};
+use crate::parse_constants::{ParseTreeFlags, ParserTestErrorBits, StatementDecoration};
 use crate::parse_tree::ParsedSource;
 use crate::parse_util::parse_util_process_extent;
 use crate::parse_util::MaybeParentheses;
@@ -125,13 +129,20 @@
     have_proc_stat, hup_jobs, is_interactive_session, job_reap, jobs_requiring_warning_on_exit,
     print_exit_warning_for_jobs, proc_update_jiffies,
 };
+use crate::reader_autopair::{
+    autopair_deletion, autopair_enabled, autopair_insertion, is_autopair_char, AutopairEdit,
+};
 use crate::reader_history_search::{smartcase_flags, ReaderHistorySearch, SearchMode};
+use crate::reader_vi::{
+    self, TextObject, ViCommand, ViMotion, ViMotionParser, ViOperator, ViParseResult,
+};
 use crate::screen::is_dumb;
 use crate::screen::{screen_clear, screen_force_clear_to_end, CharOffset, Screen};
 use crate::signal::{
     signal_check_cancel, signal_clear_cancel, signal_reset_handlers, signal_set_handlers,
     signal_set_handlers_once,
 };
+use crate::spelling::command_suggestions;
 use crate::termsize::{termsize_invalidate_tty, termsize_last, termsize_update};
 use crate::threads::{
     assert_is_background_thread, assert_is_main_thread, iothread_service_main_with_timeout,
@@ -151,7 +162,7 @@
 };
 use crate::wildcard::wildcard_has;
 use crate::wutil::wstat;
-use crate::wutil::{fstat, perror};
+use crate::wutil::{fish_wcstoul, fstat, perror};
 use crate::{abbrs, event, function};
 
 /// A description of where fish is in the process of exiting.
@@ -189,6 +200,12 @@ enum ExitState {
 /// This is set from a signal handler.
 static SIGHUP_RECEIVED: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 
+/// Whether our terminal window has focus, as far as we know from its focus reports.
+static TERMINAL_FOCUSED: RelaxedAtomicBool = RelaxedAtomicBool::new(true);
+
+/// Whether the terminal has sent a focus report, so we know that it reports focus changes.
+static TERMINAL_REPORTS_FOCUS: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
+
 /// A singleton snapshot of the reader state. This is factored out for thread-safety reasons:
 /// it may be fetched on a background thread.
 fn commandline_state_snapshot() -> MutexGuard<'static, CommandlineState> {
@@ -219,6 +236,16 @@ fn debounce_history_pager() -> &'static Debounce {
     RES.get_or_init(|| Box::new(Debounce::new(HISTORY_PAGER_TIMEOUT)))
 }
 
+/// Get the debouncer for scheduling the evaluation of the prompt with `$fish_async_prompt`.
+/// The prompt itself runs on the main thread, so the background work never hangs.
+fn debounce_async_prompt() -> &'static Debounce {
+    static RES: once_cell::race::OnceBox<Debounce> = once_cell::race::OnceBox::new();
+    RES.get_or_init(|| Box::new(Debounce::new(Duration::ZERO)))
+}
+
+/// The prompt shown with `$fish_async_prompt` until the prompt has been evaluated once.
+const ASYNC_PROMPT_PLACEHOLDER: &wstr = L!("> ");
+
 fn redirect_tty_after_sighup() {
     // If we have received SIGHUP, redirect the tty to avoid a user script triggering SIGTTIN or
     // SIGTTOU.
@@ -288,6 +315,10 @@ pub struct ReaderConfig {
     /// Right prompt command, typically fish_right_prompt.
     pub right_prompt_cmd: WString,
 
+    /// Transient prompt command, typically fish_transient_prompt. If it is a function, it replaces
+    /// both prompts once the command line is submitted.
+    pub transient_prompt_cmd: WString,
+
     /// Name of the event to trigger once we're set up.
     pub event: &'static wstr,
 
@@ -380,14 +411,14 @@ enum Kill {
     Prepend,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-enum JumpDirection {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JumpDirection {
     Forward,
     Backward,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-enum JumpPrecision {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JumpPrecision {
     Till,
     To,
 }
@@ -412,6 +443,9 @@ struct ReadlineLoopState {
 
     /// Maximum number of characters to read.
     nchars: Option<NonZeroUsize>,
+
+    /// The numeric argument given by digit-argument, which repeats the next command.
+    numeric_argument: Option<usize>,
 }
 
 impl ReadlineLoopState {
@@ -423,6 +457,7 @@ fn new() -> Self {
             comp: vec![],
             finished: false,
             nchars: None,
+            numeric_argument: None,
         }
     }
 }
@@ -483,6 +518,15 @@ enum TransientEdit {
     HistorySearch,
 }
 
+/// What exec_prompt does with the left and right prompt when `$fish_async_prompt` is set.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum AsyncPrompt {
+    /// Show the cached prompt, and schedule an evaluation once the reader is idle.
+    Refresh,
+    /// Show the cached prompt.
+    Reuse,
+}
+
 /// A struct describing the state of the interactive reader. These states can be stacked, in case
 /// reader_readline() calls are nested. This happens when the 'read' builtin is used.
 /// ReaderData does not contain a Parser - by itself it cannot execute fish script.
@@ -538,6 +582,11 @@ pub struct ReaderData {
     history_search: ReaderHistorySearch,
     /// In-pager history search.
     history_pager: Option<Range<usize>>,
+    /// How the in-pager history search interprets the search field.
+    history_pager_mode: HistoryPagerMode,
+    /// In fuzzy mode, the commands ranked by the in-pager history search, which `history_pager` is
+    /// a range of ranks into.
+    history_pager_ranked: Option<Arc<RankedHistory>>,
 
     /// The cursor selection mode.
     cursor_selection_mode: CursorSelectionMode,
@@ -561,8 +610,15 @@ pub struct ReaderData {
     /// If this is true, exit reader even if there are running jobs. This happens if we press e.g.
     /// ^D twice.
     did_warn_for_bg_jobs: bool,
+    /// A notification about a long-running command that finished while the terminal window
+    /// seemed focused. It is shown if the next thing the terminal reports is that it lost focus.
+    pending_notification: Option<WString>,
     /// The current contents of the top item in the kill ring.
     kill_item: WString,
+    /// The selected text that was last copied to the terminal's primary selection.
+    copied_selection: WString,
+    /// Whether the current command added to the kill ring.
+    kill_ring_changed: bool,
 
     /// A flag which may be set to force re-execing all prompts and re-rendering.
     /// This may come about when a color like $fish_color... has changed.
@@ -578,6 +634,20 @@ pub struct ReaderData {
     in_flight_highlight_request: WString,
     in_flight_autosuggest_request: WString,
 
+    /// The syntax error shown below the command line with `$fish_parse_error_hint`, or empty.
+    parse_error_hint: WString,
+
+    /// The left and right prompt from the last evaluation with `$fish_async_prompt`, which are
+    /// shown until the prompt is evaluated again.
+    async_prompt_cache: Option<(WString, WString)>,
+    /// The statuses of the command after which the prompt is shown. The async prompt is evaluated
+    /// with these, rather than with those of any bindings run since.
+    async_prompt_statuses: Statuses,
+    /// Whether a scheduled evaluation of the async prompt is due at the next repaint.
+    async_prompt_due: bool,
+    /// Incremented with each scheduled evaluation of the async prompt, so only the latest runs.
+    async_prompt_generation: u64,
+
     rls: Option<ReadlineLoopState>,
 }
 
@@ -606,6 +676,81 @@ impl<'a> Reader<'a> {
     fn vars(&self) -> &dyn Environment {
         self.parser.vars()
     }
+
+    /// Send a desktop notification (via OSC 9) if the command that just finished ran for at least
+    /// $fish_notify_threshold milliseconds and the terminal window is not focused. If the window
+    /// seemed focused, the terminal might not have been able to tell us otherwise while the
+    /// command ran, so wait for its next focus report. If the terminal has never reported focus,
+    /// it may not be able to, so notify right away.
+    fn notify_if_long_running(&mut self, command: &wstr) {
+        self.pending_notification = None;
+        let vars = self.vars();
+        let Some(threshold) = vars
+            .get(L!("fish_notify_threshold"))
+            .and_then(|var| fish_wcstoul(&var.as_string()).ok())
+        else {
+            return;
+        };
+        let Some(duration) = vars
+            .get(ENV_CMD_DURATION)
+            .and_then(|var| fish_wcstoul(&var.as_string()).ok())
+        else {
+            return;
+        };
+        if duration < threshold {
+            return;
+        }
+
+        // Control characters would end the sequence early.
+        let command: WString = command
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        let mut notification = WString::from_str("\x1b]9;");
+        notification.push_utfstr(&wgettext_fmt!(
+            "%ls finished with status %d",
+            command.trim_matches(' '),
+            self.parser.get_last_status()
+        ));
+        notification.push('\x07');
+        if TERMINAL_REPORTS_FOCUS.load() && TERMINAL_FOCUSED.load() {
+            self.pending_notification = Some(notification);
+        } else {
+            Outputter::stdoutput()
+                .borrow_mut()
+                .write_wstr(&notification);
+        }
+    }
+
+    /// Returns whether brackets and quotes typed into the command line are paired.
+    fn autopair_enabled(&self) -> bool {
+        self.conf.syntax_check_ok && autopair_enabled(self.vars())
+    }
+
+    /// Handle typing `c` if it is paired with another character. Returns false if `c` should be
+    /// inserted as usual.
+    fn autopair_insert(&mut self, elt: EditableLineTag, c: char) -> bool {
+        if elt != EditableLineTag::Commandline || !self.autopair_enabled() {
+            return false;
+        }
+        let el = &self.command_line;
+        let pos = el.position();
+        match autopair_insertion(el.text(), pos, c) {
+            None => false,
+            Some(AutopairEdit::StepOver) => {
+                self.update_buff_pos(elt, Some(pos + 1));
+                true
+            }
+            Some(AutopairEdit::InsertPair(closer)) => {
+                // Insert both as a separate edit, so the pair is undone at once.
+                self.push_edit(elt, Edit::new(pos..pos, WString::from_chars([c, closer])));
+                self.command_line_transient_edit = None;
+                self.suppress_autosuggestion = false;
+                self.update_buff_pos(elt, Some(pos + 1));
+                true
+            }
+        }
+    }
 }
 
 /// Read commands from \c fd until encountering EOF.
@@ -664,10 +809,13 @@ fn read_i(parser: &Parser) -> i32 {
     } else {
         conf.left_prompt_cmd = LEFT_PROMPT_FUNCTION_NAME.to_owned();
         conf.right_prompt_cmd = RIGHT_PROMPT_FUNCTION_NAME.to_owned();
+        conf.transient_prompt_cmd = TRANSIENT_PROMPT_FUNCTION_NAME.to_owned();
     }
 
     let mut data = reader_push(parser, &history_session_id(parser.vars()), conf);
     data.import_history_if_necessary();
+    // We might have inherited a working directory from another tab, which the terminal may not know.
+    report_working_directory(parser.vars());
 
     while !check_exit_loop_maybe_warning(Some(&mut data)) {
         RUN_COUNT.fetch_add(1, Ordering::Relaxed);
@@ -677,6 +825,8 @@ fn read_i(parser: &Parser) -> i32 {
         };
 
         if command.is_empty() {
+            // OSC 133 "Command finished", without a status because nothing ran.
+            data.screen.write_bytes(b"\x1b]133;D\x07");
             continue;
         }
 
@@ -684,8 +834,11 @@ fn read_i(parser: &Parser) -> i32 {
         data.update_buff_pos(EditableLineTag::Commandline, None);
         // OSC 133 End of command
         data.screen.write_bytes(b"\x1b]133;C\x07");
+        // Remember the history the command was added to, in case the command switches it.
+        let history = data.history.clone();
         event::fire_generic(parser, L!("fish_preexec").to_owned(), vec![command.clone()]);
-        let eval_res = reader_run_command(parser, &command);
+        let (eval_res, duration) = reader_run_command(parser, &command);
+        history.record_command_result(parser.get_last_status(), duration);
         signal_clear_cancel();
         if !eval_res.no_status {
             STATUS_COUNT.fetch_add(1, Ordering::Relaxed);
@@ -701,6 +854,7 @@ fn read_i(parser: &Parser) -> i32 {
             "\x1b]133;D;{}\x07",
             parser.get_last_status()
         );
+        data.notify_if_long_running(&command);
         event::fire_generic(parser, L!("fish_postexec").to_owned(), vec![command]);
         // Allow any pending history items to be returned in the history array.
         data.history.resolve_pending();
@@ -942,6 +1096,12 @@ fn check_autosuggestion_enabled(vars: &dyn Environment) -> bool {
         .unwrap_or(true)
 }
 
+/// Returns whether `$fish_async_prompt` asks for showing the cached prompt while it is evaluated.
+fn check_async_prompt_enabled(vars: &dyn Environment) -> bool {
+    vars.get_unless_empty(L!("fish_async_prompt"))
+        .is_some_and(|var| var.as_string() != "0")
+}
+
 /// Enable or disable autosuggestions based on the associated variable.
 pub fn reader_set_autosuggestion_enabled(vars: &dyn Environment) {
     // We don't need to _change_ if we're not initialized yet.
@@ -957,6 +1117,22 @@ pub fn reader_set_autosuggestion_enabled(vars: &dyn Environment) {
     }
 }
 
+/// Warn about the unknown sources in `$fish_autosuggestion_sources`, which are ignored.
+pub fn reader_check_autosuggestion_sources(vars: &dyn Environment) {
+    let Some(var) = vars.get(L!("fish_autosuggestion_sources")) else {
+        return;
+    };
+    for name in var.as_list() {
+        if AutosuggestionSource::from_name(name).is_none() {
+            FLOG!(
+                warning,
+                "Ignoring unknown source in $fish_autosuggestion_sources:",
+                name
+            );
+        }
+    }
+}
+
 /// Tell the reader that it needs to re-exec the prompt and repaint.
 /// This may be called in response to e.g. a color variable change.
 pub fn reader_schedule_prompt_repaint() {
@@ -1104,6 +1280,9 @@ pub fn reader_status_count() -> u64 {
 /// The name of the function that prints the fish right prompt (RPROMPT).
 const RIGHT_PROMPT_FUNCTION_NAME: &wstr = L!("fish_right_prompt");
 
+/// The name of the function that prints the prompt that replaces both prompts after executing.
+const TRANSIENT_PROMPT_FUNCTION_NAME: &wstr = L!("fish_transient_prompt");
+
 /// The name of the function to use in place of the left prompt if we're in the debugger context.
 const DEBUG_PROMPT_FUNCTION_NAME: &wstr = L!("fish_breakpoint_prompt");
 
@@ -1191,6 +1370,8 @@ fn new(history: Arc<History>, conf: ReaderConfig) -> Pin<Box<Self>> {
             history,
             history_search: Default::default(),
             history_pager: None,
+            history_pager_mode: HistoryPagerMode::default(),
+            history_pager_ranked: None,
             cursor_selection_mode: CursorSelectionMode::Exclusive,
             cursor_end_mode: CursorEndMode::Exclusive,
             selection: Default::default(),
@@ -1201,13 +1382,21 @@ fn new(history: Arc<History>, conf: ReaderConfig) -> Pin<Box<Self>> {
             cycle_cursor_pos: Default::default(),
             exit_loop_requested: Default::default(),
             did_warn_for_bg_jobs: Default::default(),
+            pending_notification: Default::default(),
             kill_item: Default::default(),
+            copied_selection: Default::default(),
+            kill_ring_changed: Default::default(),
             force_exec_prompt_and_repaint: Default::default(),
             last_jump_target: Default::default(),
             last_jump_direction: JumpDirection::Forward,
             last_jump_precision: JumpPrecision::To,
             in_flight_highlight_request: Default::default(),
+            parse_error_hint: Default::default(),
             in_flight_autosuggest_request: Default::default(),
+            async_prompt_cache: None,
+            async_prompt_statuses: Statuses::just(0),
+            async_prompt_due: false,
+            async_prompt_generation: 0,
             rls: None,
         }))
     }
@@ -1434,6 +1623,41 @@ pub fn request_cursor_position(
         self.save_screen_state();
     }
 
+    /// Ask the terminal for its clipboard before yanking, if enabled. The clipboard contents are
+    /// added to the kill ring when they arrive, and the yank happens with the Primary DA response
+    /// that follows them. Return whether the request was sent.
+    fn request_terminal_clipboard(&mut self) -> bool {
+        if !clipboard::paste_enabled() || is_dumb() {
+            return false;
+        }
+        {
+            let mut wait_guard = self.blocking_wait();
+            if wait_guard.is_some() {
+                return false;
+            }
+            *wait_guard = Some(BlockingWait::Clipboard);
+        }
+        let mut out = Outputter::stdoutput().borrow_mut();
+        out.begin_buffering();
+        let _ = out.write_all(OSC52_PASTE_QUERY);
+        let _ = out.write_all(QUERY_PRIMARY_DEVICE_ATTRIBUTE);
+        out.end_buffering();
+        drop(out);
+        self.save_screen_state();
+        true
+    }
+
+    /// Insert the top of the kill ring.
+    fn yank(&mut self) {
+        let yank_str = kill_yank();
+        self.insert_string(self.active_edit_line_tag(), &yank_str);
+        self.rls_mut().yank_len = yank_str.len();
+        if self.cursor_end_mode == CursorEndMode::Inclusive {
+            let (_elt, el) = self.active_edit_line();
+            self.update_buff_pos(self.active_edit_line_tag(), Some(el.position() - 1));
+        }
+    }
+
     pub fn mouse_left_click(&mut self, cursor: ViewportPosition, click_position: ViewportPosition) {
         FLOG!(
             reader,
@@ -1688,6 +1912,12 @@ fn paint_layout(&mut self, reason: &wstr, is_final_rendering: bool) {
             indents,
             data.position,
             data.pager_search_field_position,
+            // An empty command line has no error, even if the last one had.
+            if self.data.command_line.is_empty() {
+                L!("")
+            } else {
+                &self.data.parse_error_hint
+            },
             self.parser.vars(),
             pager,
             current_page_rendering,
@@ -1728,6 +1958,39 @@ fn kill(&mut self, elt: EditableLineTag, range: Range<usize>, mode: Kill, newv:
             kill_replace(&old, kill_item.clone());
         }
         self.erase_substring(elt, range);
+        self.kill_ring_changed = true;
+    }
+
+    /// Copy `text` to the terminal's `target` selection with OSC 52, if enabled.
+    fn copy_to_terminal_clipboard(&mut self, target: char, text: &wstr) {
+        if !clipboard::copy_enabled() || text.is_empty() || is_dumb() {
+            return;
+        }
+        let _ = Outputter::stdoutput()
+            .borrow_mut()
+            .write_all(&osc52_copy_sequence(target, text));
+        self.save_screen_state();
+    }
+
+    /// Copy the top of the kill ring to the terminal's clipboard if the last command changed it,
+    /// and the active selection to the terminal's primary selection whenever it changes, if
+    /// enabled. This is done once per command, so a repeated kill is only sent once.
+    fn copy_to_terminal(&mut self) {
+        let kill_ring_changed = std::mem::take(&mut self.kill_ring_changed);
+        if !clipboard::copy_enabled() {
+            return;
+        }
+        if kill_ring_changed {
+            self.copy_to_terminal_clipboard(clipboard::CLIPBOARD, &kill_yank());
+        }
+        let selected = self
+            .get_selection()
+            .map(|range| self.command_line.text()[range].to_owned())
+            .unwrap_or_default();
+        if selected != self.copied_selection {
+            self.copy_to_terminal_clipboard(clipboard::PRIMARY_SELECTION, &selected);
+            self.copied_selection = selected;
+        }
     }
 
     /// Insert the characters of the string into the command line buffer and print them to the screen
@@ -2067,6 +2330,49 @@ fn jump_to_matching_bracket(
         return false;
     }
 
+    /// Jump to the bracket matching the one at the cursor, or else to the next closing bracket.
+    fn jump_to_matching_bracket_at_cursor(
+        &mut self,
+        precision: JumpPrecision,
+        elt: EditableLineTag,
+    ) -> bool {
+        let el = self.edit_line(elt);
+        let l_brackets = ['(', '[', '{'];
+        let r_brackets = [')', ']', '}'];
+        let cursor = el.position();
+        let jump_from_pos = match precision {
+            _ if l_brackets.contains(&el.at(cursor)) || r_brackets.contains(&el.at(cursor)) => {
+                Some(cursor)
+            }
+            JumpPrecision::Till if cursor > 0 && l_brackets.contains(&el.at(cursor - 1)) => {
+                Some(cursor - 1)
+            }
+            JumpPrecision::Till if cursor < el.len() && r_brackets.contains(&el.at(cursor + 1)) => {
+                Some(cursor + 1)
+            }
+            _ => None,
+        };
+        match jump_from_pos {
+            Some(jump_from_pos) => {
+                let l_bracket = match el.at(jump_from_pos) {
+                    '(' | ')' => '(',
+                    '[' | ']' => '[',
+                    '{' | '}' => '{',
+                    _ => unreachable!(),
+                };
+                let r_bracket = match l_bracket {
+                    '(' => ')',
+                    '[' => ']',
+                    '{' => '}',
+                    _ => unreachable!(),
+                };
+                self.jump_to_matching_bracket(precision, elt, jump_from_pos, l_bracket, r_bracket)
+            }
+            // If we stand on non-bracket character, we prefer to jump forward
+            None => self.jump(JumpDirection::Forward, precision, elt, r_brackets.to_vec()),
+        }
+    }
+
     fn jump_and_remember_last_jump(
         &mut self,
         direction: JumpDirection,
@@ -2155,6 +2461,7 @@ fn readline(&mut self, nchars: Option<NonZeroUsize>) -> Option<WString> {
         zelf.cycle_cursor_pos = 0;
 
         zelf.history_search.reset();
+        zelf.incorporate_shared_history();
 
         // It may happen that a command we ran when job control was disabled nevertheless stole the tty
         // from us. In that case when we read from our fd, it will trigger SIGTTIN. So just
@@ -2218,7 +2525,8 @@ fn readline(&mut self, nchars: Option<NonZeroUsize>) -> Option<WString> {
         if !zelf.conf.event.is_empty() {
             event::fire_generic(zelf.parser, zelf.conf.event.to_owned(), vec![]);
         }
-        zelf.exec_prompt();
+        zelf.async_prompt_statuses = zelf.parser.get_last_statuses();
+        zelf.exec_prompt(AsyncPrompt::Refresh);
 
         // Start out as initially dirty.
         zelf.force_exec_prompt_and_repaint = true;
@@ -2229,6 +2537,14 @@ fn readline(&mut self, nchars: Option<NonZeroUsize>) -> Option<WString> {
             }
         }
 
+        // Once the command line is submitted, its prompt stays as it is.
+        zelf.cancel_async_prompt();
+
+        // Collapse the prompt of the submitted command line.
+        if zelf.rls().finished && zelf.exec_transient_prompt() {
+            zelf.screen.reset_line(/*repaint_prompt=*/ true);
+        }
+
         // Redraw the command line. This is what ensures the autosuggestion is hidden, etc. after the
         // user presses enter.
         if zelf.is_repaint_needed(None)
@@ -2329,6 +2645,7 @@ fn read_normal_chars(&mut self) -> Option<CharEvent> {
         );
 
         let mut accumulated_chars = WString::new();
+        let autopair = self.autopair_enabled();
 
         while accumulated_chars.len() < limit {
             terminal_protocols_enable_ifn();
@@ -2337,7 +2654,8 @@ fn read_normal_chars(&mut self) -> Option<CharEvent> {
                 event_needing_handling = Some(evt);
                 break;
             };
-            if !poll_fd_readable(self.conf.inputfd) {
+            // A numeric argument only repeats a single character.
+            if !poll_fd_readable(self.conf.inputfd) || self.rls().numeric_argument.is_some() {
                 event_needing_handling = Some(evt);
                 break;
             }
@@ -2350,6 +2668,11 @@ fn read_normal_chars(&mut self) -> Option<CharEvent> {
             }
 
             if let Some(c) = kevt.key.codepoint_text() {
+                // Characters that may be paired are handled one at a time.
+                if autopair && is_autopair_char(c) {
+                    event_needing_handling = Some(evt);
+                    break;
+                }
                 accumulated_chars.push(c);
             } else {
                 continue;
@@ -2432,8 +2755,33 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
             return ControlFlow::Continue(());
         };
 
+        if matches!(
+            event_needing_handling,
+            CharEvent::Key(_) | CharEvent::Readline(_)
+        ) {
+            // The user is back, so they don't need to be told that a command finished.
+            self.pending_notification = None;
+        }
+
         match event_needing_handling {
             CharEvent::Readline(readline_cmd_evt) => {
+                let readline_cmd = readline_cmd_evt.cmd;
+                // In vi mode, "0" moves to the start of the line, unless it continues a count
+                // like "10".
+                let continues_count = readline_cmd == ReadlineCmd::BeginningOfLine
+                    && self.rls().numeric_argument.is_some()
+                    && readline_cmd_evt.seq.as_char_slice() == ['0'];
+                if readline_cmd == ReadlineCmd::DigitArgument || continues_count {
+                    let seq = readline_cmd_evt.seq.as_char_slice();
+                    let digit = seq.last().and_then(|c| c.to_digit(10));
+                    if let Some(digit) = digit {
+                        let arg = self.rls().numeric_argument.unwrap_or(0) * 10 + digit as usize;
+                        self.rls_mut().numeric_argument = Some(arg.min(reader_vi::MAX_COUNT));
+                    }
+                    self.input_data.function_set_status(digit.is_some());
+                    return ControlFlow::Continue(());
+                }
+
                 if !matches!(
                     self.rls().last_cmd,
                     Some(ReadlineCmd::Yank | ReadlineCmd::YankPop)
@@ -2441,7 +2789,6 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                     self.rls_mut().yank_len = 0;
                 }
 
-                let readline_cmd = readline_cmd_evt.cmd;
                 if readline_cmd == ReadlineCmd::Cancel && self.is_navigating_pager_contents() {
                     self.clear_transient_edit();
                 }
@@ -2455,7 +2802,23 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                     self.clear_pager();
                 }
 
-                self.handle_readline_command(readline_cmd);
+                let count = if command_repeats(readline_cmd) {
+                    self.rls_mut().numeric_argument.take()
+                } else {
+                    None
+                };
+                match count {
+                    Some(count) if count > 1 => self.repeat_readline_command(readline_cmd, count),
+                    _ => self.handle_readline_command(readline_cmd),
+                }
+                // The vi operators take the argument themselves, repaints leave it for the next
+                // command, and all other commands drop it.
+                if !matches!(
+                    readline_cmd,
+                    ReadlineCmd::Repaint | ReadlineCmd::ForceRepaint | ReadlineCmd::RepaintMode
+                ) {
+                    self.rls_mut().numeric_argument = None;
+                }
 
                 if self.history_search.active() && command_ends_history_search(readline_cmd) {
                     // "cancel" means to abort the whole thing, other ending commands mean to finish the
@@ -2469,8 +2832,10 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                 }
 
                 self.rls_mut().last_cmd = Some(readline_cmd);
+                self.copy_to_terminal();
             }
             CharEvent::Command(command) => {
+                self.rls_mut().numeric_argument = None;
                 self.run_input_command_scripts(&command);
             }
             CharEvent::Key(kevt) => {
@@ -2483,7 +2848,10 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                     // Regular character.
                     let (elt, _el) = self.active_edit_line();
                     if let Some(c) = kevt.key.codepoint_text() {
-                        self.insert_char(elt, c);
+                        let count = self.rls_mut().numeric_argument.take().unwrap_or(1);
+                        if count != 1 || !self.autopair_insert(elt, c) {
+                            self.insert_string(elt, &WString::from_chars(vec![c; count]));
+                        }
 
                         if elt == EditableLineTag::Commandline {
                             self.clear_pager();
@@ -2500,9 +2868,19 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                 }
                 ImplicitEvent::CheckExit => (),
                 ImplicitEvent::FocusIn => {
+                    TERMINAL_REPORTS_FOCUS.store(true);
+                    TERMINAL_FOCUSED.store(true);
+                    self.pending_notification = None;
                     event::fire_generic(self.parser, L!("fish_focus_in").to_owned(), vec![]);
                 }
                 ImplicitEvent::FocusOut => {
+                    TERMINAL_REPORTS_FOCUS.store(true);
+                    TERMINAL_FOCUSED.store(false);
+                    if let Some(notification) = self.pending_notification.take() {
+                        Outputter::stdoutput()
+                            .borrow_mut()
+                            .write_wstr(&notification);
+                    }
                     event::fire_generic(self.parser, L!("fish_focus_out").to_owned(), vec![]);
                 }
                 ImplicitEvent::DisableMouseTracking => {
@@ -2517,6 +2895,12 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                         // Rogue reply.
                         return ControlFlow::Continue(());
                     };
+                    if *wait == BlockingWait::Clipboard {
+                        // Any clipboard contents have been added to the kill ring by now.
+                        unblock_input(wait_guard);
+                        self.yank();
+                        return ControlFlow::Continue(());
+                    }
                     let BlockingWait::Startup(stage) = wait else {
                         // Rogue reply.
                         return ControlFlow::Continue(());
@@ -2554,6 +2938,21 @@ fn handle_char_event(&mut self, injected_event: Option<CharEvent>) -> ControlFlo
                     self.screen.push_to_scrollback(cursor_y);
                     unblock_input(self.blocking_wait());
                 }
+                ImplicitEvent::ClipboardContents(contents) => {
+                    if *self.blocking_wait() == Some(BlockingWait::Clipboard)
+                        && !contents.is_empty()
+                        && contents != kill_yank()
+                    {
+                        kill_add(contents);
+                    }
+                }
+                ImplicitEvent::HistoryChanged => {
+                    // Searches hold on to history indexes, so leave them alone. The next prompt
+                    // picks up the changes anyway.
+                    if !self.history_search.active() && self.history_pager.is_none() {
+                        self.incorporate_shared_history();
+                    }
+                }
             },
         }
         ControlFlow::Continue(())
@@ -2597,6 +2996,195 @@ fn push_edit(&mut self, elt: EditableLineTag, edit: Edit) {
         self.data.push_edit(elt, edit);
     }
 
+    /// Read the motion of a vi operator from the keyboard.
+    /// Returns None if it is cancelled, e.g. by escape or a key that is not a motion.
+    fn read_vi_motion(&mut self, operator: ViOperator) -> Option<ViCommand> {
+        let mut parser = ViMotionParser::new(operator);
+        // Keep the functions that follow the operator in its binding, see function_push_args.
+        let mut skipped = vec![];
+        let cmd = loop {
+            let evt = self.readch();
+            let Some(kevt) = evt.get_key() else {
+                let eof = matches!(evt, CharEvent::Implicit(ImplicitEvent::Eof));
+                skipped.push(evt);
+                if eof {
+                    break None;
+                }
+                continue;
+            };
+            let Some(c) = kevt.key.codepoint_text() else {
+                break None;
+            };
+            match parser.push(c) {
+                ViParseResult::Incomplete => (),
+                ViParseResult::Complete(cmd) => break Some(cmd),
+                ViParseResult::Invalid => break None,
+            }
+        };
+        self.insert_front(skipped);
+        cmd
+    }
+
+    /// Return the range of text that a vi operator applies to, or None if the motion fails.
+    /// This may move the cursor.
+    fn vi_motion_range(
+        &mut self,
+        elt: EditableLineTag,
+        operator: ViOperator,
+        cmd: ViCommand,
+    ) -> Option<Range<usize>> {
+        let el = self.edit_line(elt);
+        let pos = el.position();
+        let count = cmd.count;
+        let (direction, target) = match cmd.motion {
+            ViMotion::Jump(direction, precision, target) => {
+                // With a count, only the last jump stops before the target.
+                for i in 1..=count {
+                    let precision = if i == count {
+                        precision
+                    } else {
+                        JumpPrecision::To
+                    };
+                    if !self
+                        .data
+                        .jump_and_remember_last_jump(direction, precision, elt, target)
+                    {
+                        return None;
+                    }
+                }
+                (direction, self.edit_line(elt).position())
+            }
+            ViMotion::RepeatJump { reverse } => {
+                let target = self.last_jump_target?;
+                let direction = match (self.last_jump_direction, reverse) {
+                    (direction, false) => direction,
+                    (JumpDirection::Forward, true) => JumpDirection::Backward,
+                    (JumpDirection::Backward, true) => JumpDirection::Forward,
+                };
+                let precision = self.last_jump_precision;
+                for _ in 0..count {
+                    if !self.data.jump(direction, precision, elt, vec![target]) {
+                        return None;
+                    }
+                }
+                (direction, self.edit_line(elt).position())
+            }
+            ViMotion::MatchingBracket => {
+                if !self
+                    .data
+                    .jump_to_matching_bracket_at_cursor(JumpPrecision::To, elt)
+                {
+                    return None;
+                }
+                // Both brackets are included.
+                let target = self.edit_line(elt).position();
+                return Some(target.min(pos)..target.max(pos) + 1);
+            }
+            ViMotion::TextObject {
+                inner,
+                object: TextObject::Bracket(open, close),
+            } => {
+                let open_pos = reader_vi::enclosing_bracket(el.text(), pos, open, close, count)?;
+                if !self.data.jump_to_matching_bracket(
+                    JumpPrecision::To,
+                    elt,
+                    open_pos,
+                    open,
+                    close,
+                ) {
+                    return None;
+                }
+                let close_pos = self.edit_line(elt).position();
+                return Some(if inner {
+                    open_pos + 1..close_pos
+                } else {
+                    open_pos..close_pos + 1
+                });
+            }
+            _ => return reader_vi::motion_range(el.text(), pos, operator, cmd),
+        };
+        // Like in vim, forward jumps include their target, backward ones exclude the cursor.
+        Some(match direction {
+            JumpDirection::Forward => pos..target + 1,
+            JumpDirection::Backward => target..pos,
+        })
+    }
+
+    /// Apply a vi operator to the text covered by a motion read from the keyboard.
+    /// Returns false if the motion is cancelled or fails.
+    fn vi_operator(&mut self, operator: ViOperator) -> bool {
+        let Some(mut cmd) = self.read_vi_motion(operator) else {
+            return false;
+        };
+        // A numeric argument multiplies the count, so "alt-2 d 3 w" deletes six words.
+        if let Some(count) = self.rls_mut().numeric_argument.take() {
+            cmd.count = (cmd.count * count).min(reader_vi::MAX_COUNT);
+        }
+        let (elt, el) = self.active_edit_line();
+        let pos = el.position();
+        let range = self.vi_motion_range(elt, operator, cmd);
+        self.update_buff_pos(elt, Some(pos));
+        let Some(range) = range else {
+            return false;
+        };
+        // Changing nothing, as in "ci(" on "()", still starts inserting.
+        if range.is_empty() && operator != ViOperator::Change {
+            return false;
+        }
+        match operator {
+            ViOperator::Yank => {
+                let (elt, el) = self.active_edit_line();
+                kill_add(el.text()[range.clone()].to_owned());
+                self.kill_ring_changed = true;
+                if cmd.motion != ViMotion::Line {
+                    self.update_buff_pos(elt, Some(range.start));
+                }
+            }
+            ViOperator::Delete | ViOperator::Change => {
+                if elt == EditableLineTag::Commandline {
+                    self.suppress_autosuggestion = true;
+                }
+                if range.is_empty() {
+                    self.update_buff_pos(elt, Some(range.start));
+                } else {
+                    self.data.kill(elt, range, Kill::Append, true);
+                }
+                // Don't leave the cursor past the end of the line in normal mode.
+                let (elt, el) = self.active_edit_line();
+                let text = el.text();
+                let pos = el.position();
+                if operator == ViOperator::Delete
+                    && self.cursor_end_mode == CursorEndMode::Inclusive
+                    && pos > 0
+                    && text.as_char_slice().get(pos).map_or(true, |&c| c == '\n')
+                    && text.char_at(pos - 1) != '\n'
+                {
+                    self.update_buff_pos(elt, Some(pos - 1));
+                }
+            }
+        }
+        true
+    }
+
+    /// Run the given command `count` times, as one edit that a single undo reverts.
+    fn repeat_readline_command(&mut self, c: ReadlineCmd, count: usize) {
+        // Only the unrepeated command may exit the shell.
+        let c = if c == ReadlineCmd::DeleteOrExit {
+            ReadlineCmd::DeleteChar
+        } else {
+            c
+        };
+        let (_elt, el) = self.active_edit_line_mut();
+        el.begin_edit_group();
+        for _ in 0..count {
+            self.handle_readline_command(c);
+            // Let each kill append to the previous one.
+            self.rls_mut().last_cmd = Some(c);
+        }
+        let (_elt, el) = self.active_edit_line_mut();
+        el.end_edit_group();
+    }
+
     fn handle_readline_command(&mut self, c: ReadlineCmd) {
         #[allow(non_camel_case_types)]
         type rl = ReadlineCmd;
@@ -2739,7 +3327,12 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                     }
                     // Else we repaint as normal.
                 }
-                self.exec_prompt();
+                // With an async prompt, a plain repaint shows the cached prompt again.
+                self.exec_prompt(if c == rl::Repaint && !self.force_exec_prompt_and_repaint {
+                    AsyncPrompt::Reuse
+                } else {
+                    AsyncPrompt::Refresh
+                });
                 self.screen.reset_line(/*repaint_prompt=*/ true);
                 self.layout_and_repaint(L!("readline"));
                 self.force_exec_prompt_and_repaint = false;
@@ -2906,13 +3499,9 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 }
             }
             rl::Yank => {
-                let yank_str = kill_yank();
-                self.data
-                    .insert_string(self.active_edit_line_tag(), &yank_str);
-                self.rls_mut().yank_len = yank_str.len();
-                if self.cursor_end_mode == CursorEndMode::Inclusive {
-                    let (_elt, el) = self.active_edit_line();
-                    self.update_buff_pos(self.active_edit_line_tag(), Some(el.position() - 1));
+                // With the terminal's clipboard, we yank once it has answered.
+                if !self.request_terminal_clipboard() {
+                    self.yank();
                 }
             }
             rl::YankPop => {
@@ -2934,7 +3523,18 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 }
             }
             rl::BackwardDeleteChar => {
-                self.delete_char(true);
+                let (elt, el) = self.active_edit_line();
+                let pos = el.position();
+                if self.autopair_enabled()
+                    && elt == EditableLineTag::Commandline
+                    && autopair_deletion(el.text(), pos)
+                {
+                    self.suppress_autosuggestion = true;
+                    self.erase_substring(elt, pos - 1..pos + 1);
+                    self.update_buff_pos(elt, None);
+                } else {
+                    self.delete_char(true);
+                }
             }
             rl::Exit => {
                 // This is by definition a successful exit, override the status
@@ -2988,6 +3588,7 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 let was_active_before = self.history_search.active();
 
                 if self.history_search.is_at_present() && mode != self.history_search.mode() {
+                    self.incorporate_shared_history();
                     let el = &self.data.command_line;
                     if mode == SearchMode::Token {
                         // Searching by token.
@@ -3006,6 +3607,9 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                             mode,
                             0,
                         );
+                        self.data
+                            .history_search
+                            .prefer_directory(self.parser.vars().get_pwd_slash());
 
                         // Skip the autosuggestion in the history unless it was truncated.
                         let suggest = &self.data.autosuggestion.text;
@@ -3047,7 +3651,11 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
             }
             rl::HistoryPager => {
                 if let Some(history_pager) = &self.history_pager {
-                    if history_pager.end > self.history.size() {
+                    let at_end = match &self.history_pager_ranked {
+                        Some(ranked) => history_pager.end >= ranked.commands.len(),
+                        None => history_pager.end > self.history.size(),
+                    };
+                    if at_end {
                         self.flash(0..self.command_line.len());
                         return;
                     }
@@ -3063,7 +3671,9 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 self.cycle_command_line = self.command_line.text().to_owned();
                 self.cycle_cursor_pos = self.command_line.position();
 
+                self.incorporate_shared_history();
                 self.history_pager = Some(0..1);
+                self.history_pager_mode = HistoryPagerMode::default();
                 // Update the pager data.
                 self.pager.set_search_field_shown(true);
                 self.pager.set_prefix(
@@ -3096,6 +3706,28 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 };
                 self.insert_string(EditableLineTag::SearchField, &search_string);
             }
+            rl::HistoryPagerToggleRegex | rl::HistoryPagerToggleFuzzy => {
+                if self.history_pager.is_none() {
+                    self.input_data.function_set_status(false);
+                    return;
+                }
+                self.input_data.function_set_status(true);
+                let mode = if c == rl::HistoryPagerToggleRegex {
+                    HistoryPagerMode::Regex
+                } else {
+                    HistoryPagerMode::Fuzzy
+                };
+                self.history_pager_mode = if self.history_pager_mode == mode {
+                    HistoryPagerMode::default()
+                } else {
+                    mode
+                };
+                self.fill_history_pager(
+                    HistoryPagerInvocation::Anew,
+                    Some(SelectionMotion::Next),
+                    SearchDirection::Backward,
+                );
+            }
             #[allow(deprecated)]
             rl::HistoryDelete | rl::HistoryPagerDelete => {
                 // Also applies to ordinary history search.
@@ -3666,59 +4298,13 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 }
             }
             rl::JumpToMatchingBracket | rl::JumpTillMatchingBracket => {
-                let (elt, _el) = self.active_edit_line();
-                let el = self.edit_line(elt);
-                let l_brackets = ['(', '[', '{'];
-                let r_brackets = [')', ']', '}'];
-                let cursor = el.position();
                 let precision = match c {
                     rl::JumpToMatchingBracket => JumpPrecision::To,
                     rl::JumpTillMatchingBracket => JumpPrecision::Till,
                     _ => unreachable!(),
                 };
-                let jump_from_pos = match c {
-                    _ if l_brackets.contains(&el.at(cursor))
-                        || r_brackets.contains(&el.at(cursor)) =>
-                    {
-                        Some(cursor)
-                    }
-                    rl::JumpTillMatchingBracket
-                        if cursor > 0 && l_brackets.contains(&el.at(cursor - 1)) =>
-                    {
-                        Some(cursor - 1)
-                    }
-                    rl::JumpTillMatchingBracket
-                        if cursor < el.len() && r_brackets.contains(&el.at(cursor + 1)) =>
-                    {
-                        Some(cursor + 1)
-                    }
-                    _ => None,
-                };
-                let success = match jump_from_pos {
-                    Some(jump_from_pos) => {
-                        let l_bracket = match el.at(jump_from_pos) {
-                            '(' | ')' => '(',
-                            '[' | ']' => '[',
-                            '{' | '}' => '{',
-                            _ => unreachable!(),
-                        };
-                        let r_bracket = match l_bracket {
-                            '(' => ')',
-                            '[' => ']',
-                            '{' => '}',
-                            _ => unreachable!(),
-                        };
-                        self.jump_to_matching_bracket(
-                            precision,
-                            elt,
-                            jump_from_pos,
-                            l_bracket,
-                            r_bracket,
-                        )
-                    }
-                    // If we stand on non-bracket character, we prefer to jump forward
-                    None => self.jump(JumpDirection::Forward, precision, elt, r_brackets.to_vec()),
-                };
+                let (elt, _el) = self.active_edit_line();
+                let success = self.data.jump_to_matching_bracket_at_cursor(precision, elt);
                 self.input_data.function_set_status(success);
             }
             rl::RepeatJump => {
@@ -3760,6 +4346,16 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
 
                 self.input_data.function_set_status(success);
             }
+            rl::ViDelete | rl::ViChange | rl::ViYank => {
+                let operator = match c {
+                    rl::ViDelete => ViOperator::Delete,
+                    rl::ViChange => ViOperator::Change,
+                    rl::ViYank => ViOperator::Yank,
+                    _ => unreachable!(),
+                };
+                let success = self.vi_operator(operator);
+                self.input_data.function_set_status(success);
+            }
             rl::ExpandAbbr => {
                 if self.expand_abbreviation_at_cursor(1) {
                     self.input_data.function_set_status(true);
@@ -3767,6 +4363,10 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                     self.input_data.function_set_status(false);
                 }
             }
+            rl::CorrectCommand => {
+                let success = self.correct_command_at_cursor();
+                self.input_data.function_set_status(success);
+            }
             rl::Undo | rl::Redo => {
                 let (elt, _el) = self.active_edit_line();
                 let ok = if c == rl::Undo {
@@ -3809,7 +4409,7 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                     return;
                 };
                 match wait {
-                    BlockingWait::Startup(_) => panic!(),
+                    BlockingWait::Startup(_) | BlockingWait::Clipboard => panic!(),
                     BlockingWait::CursorPosition(_) => {
                         // TODO: re-queue it I guess.
                         FLOG!(
@@ -3823,6 +4423,9 @@ fn handle_readline_command(&mut self, c: ReadlineCmd) {
                 // This can be reached via `commandline -f and` etc
                 // panic!("should have been handled by inputter_t::readch");
             }
+            rl::DigitArgument => {
+                // Handled in handle_char_event, which knows the key that was pressed.
+            }
         }
     }
 
@@ -3839,7 +4442,7 @@ fn clear_screen_and_repaint(&mut self) {
             self.screen.reset_line(/*repaint_prompt=*/ true);
             self.layout_and_repaint(L!("readline"));
         }
-        self.exec_prompt();
+        self.exec_prompt(AsyncPrompt::Refresh);
         self.screen.reset_line(/*repaint_prompt=*/ true);
         self.layout_and_repaint(L!("readline"));
         self.force_exec_prompt_and_repaint = false;
@@ -4029,14 +4632,16 @@ impl ReaderData {
     fn clear_pager(&mut self) {
         self.pager.clear();
         self.history_pager = None;
+        self.history_pager_ranked = None;
         self.clear(EditableLineTag::SearchField);
         self.command_line_transient_edit = None;
     }
 
     fn get_selection(&self) -> Option<Range<usize>> {
         let selection = self.selection?;
-        let start = selection.start;
         let end = std::cmp::min(selection.stop, self.command_line.len());
+        // The selection may be stale if text was removed while it was active.
+        let start = std::cmp::min(selection.start, end);
         Some(start..end)
     }
 
@@ -4542,8 +5147,145 @@ fn exec_mode_prompt(&mut self) {
         }
     }
 
+    /// Replace the prompts with the output of the transient prompt function, if it exists, so
+    /// the command line stays in the scrollback with a shorter prompt.
+    /// Return whether the prompts were replaced.
+    fn exec_transient_prompt(&mut self) -> bool {
+        if self.conf.transient_prompt_cmd.is_empty()
+            || !function::exists(&self.conf.transient_prompt_cmd, self.parser)
+        {
+            return false;
+        }
+        let mut zelf = scoped_push_replacer_ctx(
+            self,
+            |zelf, new_value| {
+                std::mem::replace(&mut zelf.parser.libdata_mut().is_interactive, new_value)
+            },
+            false,
+        );
+        // Status is ignored.
+        let mut prompt_list = vec![];
+        exec_subshell(
+            &zelf.conf.transient_prompt_cmd,
+            zelf.parser,
+            Some(&mut prompt_list),
+            /*apply_exit_status=*/ false,
+        );
+        zelf.left_prompt_buff = join_strings(&prompt_list, '\n');
+        zelf.mode_prompt_buff.clear();
+        zelf.right_prompt_buff.clear();
+        true
+    }
+
+    /// Return whether the cached left and right prompts should be shown while they are evaluated.
+    fn async_prompt_enabled(&self) -> bool {
+        self.conf.left_prompt_cmd == LEFT_PROMPT_FUNCTION_NAME
+            && check_async_prompt_enabled(self.parser.vars())
+            && function::exists(&self.conf.left_prompt_cmd, self.parser)
+    }
+
+    /// If the prompt is evaluated asynchronously, show the cached prompt, or a placeholder before
+    /// the first evaluation, and schedule an evaluation if `async_prompt` asks for it. If one is
+    /// due, evaluate the prompt instead.
+    /// Return whether the prompt buffers were set.
+    fn exec_prompt_async(&mut self, async_prompt: AsyncPrompt) -> bool {
+        if !self.async_prompt_enabled() {
+            self.async_prompt_cache = None;
+            self.cancel_async_prompt();
+            return false;
+        }
+        if std::mem::take(&mut self.async_prompt_due) {
+            // The prompt sees the statuses of the command before it, not of any bindings run since.
+            let saved_statuses = self.parser.get_last_statuses();
+            self.parser
+                .set_last_statuses(self.async_prompt_statuses.clone());
+            self.exec_left_and_right_prompt();
+            self.parser.set_last_statuses(saved_statuses);
+            self.async_prompt_cache = Some((
+                self.left_prompt_buff.clone(),
+                self.right_prompt_buff.clone(),
+            ));
+            return true;
+        }
+        match &self.data.async_prompt_cache {
+            Some((left, right)) => {
+                self.data.left_prompt_buff = left.clone();
+                self.data.right_prompt_buff = right.clone();
+            }
+            None => self.data.left_prompt_buff = ASYNC_PROMPT_PLACEHOLDER.to_owned(),
+        }
+        if async_prompt == AsyncPrompt::Refresh {
+            self.schedule_async_prompt();
+        }
+        true
+    }
+
+    /// Evaluate the prompt at a repaint once the reader is idle. Completions of background work
+    /// are handled only when no input is pending, so keys that were typed ahead are handled first.
+    fn schedule_async_prompt(&mut self) {
+        self.async_prompt_generation += 1;
+        let generation = self.async_prompt_generation;
+        let canary = Rc::downgrade(&self.canary);
+        let completion = move |zelf: &mut Reader, ()| {
+            if canary.upgrade().is_none() || zelf.async_prompt_generation != generation {
+                return;
+            }
+            zelf.async_prompt_due = true;
+            reader_schedule_prompt_repaint();
+        };
+        debounce_async_prompt().perform_with_completion(|| (), completion);
+    }
+
+    /// Drop any scheduled evaluation of the async prompt.
+    fn cancel_async_prompt(&mut self) {
+        self.async_prompt_generation += 1;
+        self.async_prompt_due = false;
+    }
+
+    /// Run the left and right prompt commands. The output is inserted into the prompt buffers.
+    fn exec_left_and_right_prompt(&mut self) {
+        if !self.conf.left_prompt_cmd.is_empty() {
+            // Status is ignored.
+            let mut prompt_list = vec![];
+            // Historic compatibility hack.
+            // If the left prompt function is deleted, then use a default prompt instead of
+            // producing an error.
+            let left_prompt_deleted = self.conf.left_prompt_cmd == LEFT_PROMPT_FUNCTION_NAME
+                && !function::exists(&self.conf.left_prompt_cmd, self.parser);
+            exec_subshell(
+                if left_prompt_deleted {
+                    DEFAULT_PROMPT
+                } else {
+                    &self.conf.left_prompt_cmd
+                },
+                self.parser,
+                Some(&mut prompt_list),
+                /*apply_exit_status=*/ false,
+            );
+            self.left_prompt_buff = join_strings(&prompt_list, '\n');
+        }
+
+        if !self.conf.right_prompt_cmd.is_empty() {
+            if function::exists(&self.conf.right_prompt_cmd, self.parser) {
+                // Status is ignored.
+                let mut prompt_list = vec![];
+                exec_subshell(
+                    &self.conf.right_prompt_cmd,
+                    self.parser,
+                    Some(&mut prompt_list),
+                    /*apply_exit_status=*/ false,
+                );
+                // Right prompt does not support multiple lines, so just concatenate all of them.
+                for i in prompt_list {
+                    self.right_prompt_buff.push_utfstr(&i);
+                }
+            }
+        }
+    }
+
     /// Reexecute the prompt command. The output is inserted into prompt_buff.
-    fn exec_prompt(&mut self) {
+    /// With `$fish_async_prompt`, `async_prompt` tells whether to evaluate the left and right prompt.
+    fn exec_prompt(&mut self, async_prompt: AsyncPrompt) {
         // Clear existing prompts.
         self.left_prompt_buff.clear();
         self.right_prompt_buff.clear();
@@ -4576,42 +5318,18 @@ fn exec_prompt(&mut self) {
 
             zelf.exec_mode_prompt();
 
-            if !zelf.conf.left_prompt_cmd.is_empty() {
-                // Status is ignored.
-                let mut prompt_list = vec![];
-                // Historic compatibility hack.
-                // If the left prompt function is deleted, then use a default prompt instead of
-                // producing an error.
-                let left_prompt_deleted = zelf.conf.left_prompt_cmd == LEFT_PROMPT_FUNCTION_NAME
-                    && !function::exists(&zelf.conf.left_prompt_cmd, zelf.parser);
-                exec_subshell(
-                    if left_prompt_deleted {
-                        DEFAULT_PROMPT
-                    } else {
-                        &zelf.conf.left_prompt_cmd
-                    },
-                    zelf.parser,
-                    Some(&mut prompt_list),
-                    /*apply_exit_status=*/ false,
-                );
-                zelf.left_prompt_buff = join_strings(&prompt_list, '\n');
-            }
-
-            if !zelf.conf.right_prompt_cmd.is_empty() {
-                if function::exists(&zelf.conf.right_prompt_cmd, zelf.parser) {
-                    // Status is ignored.
-                    let mut prompt_list = vec![];
-                    exec_subshell(
-                        &zelf.conf.right_prompt_cmd,
-                        zelf.parser,
-                        Some(&mut prompt_list),
-                        /*apply_exit_status=*/ false,
-                    );
-                    // Right prompt does not support multiple lines, so just concatenate all of them.
-                    for i in prompt_list {
-                        zelf.right_prompt_buff.push_utfstr(&i);
-                    }
-                }
+            // With an async prompt, the cached prompt is shown until the new one is ready.
+            let used_cached_prompt = zelf.exec_prompt_async(async_prompt);
+
+            if !used_cached_prompt {
+                zelf.exec_left_and_right_prompt();
+            }
+
+            if !used_cached_prompt && zelf.async_prompt_enabled() {
+                zelf.async_prompt_cache = Some((
+                    zelf.left_prompt_buff.clone(),
+                    zelf.right_prompt_buff.clone(),
+                ));
             }
         }
 
@@ -4706,6 +5424,52 @@ fn search_string(&self) -> &wstr {
     }
 }
 
+/// A source of autosuggestions. `$fish_autosuggestion_sources` lists the ones to use, and the first
+/// one with a suggestion wins.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AutosuggestionSource {
+    /// Commands from history that were run in the current directory, or else in the closest parent.
+    CwdHistory,
+    /// The most recent matching command from history.
+    History,
+    /// The first completion of the token at the cursor.
+    Completions,
+    /// An abbreviation whose name starts with the token at the cursor.
+    Abbreviations,
+}
+
+impl AutosuggestionSource {
+    fn from_name(name: &wstr) -> Option<Self> {
+        Some(match name.to_string().as_str() {
+            "cwd-history" => Self::CwdHistory,
+            "history" => Self::History,
+            "completions" => Self::Completions,
+            "abbreviations" => Self::Abbreviations,
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the autosuggestion sources from `$fish_autosuggestion_sources`, in order. If it is unset
+/// or empty, history is preferred over completions.
+fn autosuggestion_sources(vars: &dyn Environment) -> Vec<AutosuggestionSource> {
+    use AutosuggestionSource::*;
+    let Some(var) = vars.get_unless_empty(L!("fish_autosuggestion_sources")) else {
+        return vec![CwdHistory, History, Completions];
+    };
+    let mut sources = vec![];
+    for name in var.as_list() {
+        // Unknown sources are warned about when the variable is set.
+        let Some(source) = AutosuggestionSource::from_name(name) else {
+            continue;
+        };
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
 // Returns a function that can be invoked (potentially
 // on a background thread) to determine the autosuggestion
 fn get_autosuggestion_performer(
@@ -4717,6 +5481,7 @@ fn get_autosuggestion_performer(
     let generation_count = read_generation_count();
     let vars = parser.vars().snapshot();
     let working_directory = parser.vars().get_pwd_slash();
+    let sources = autosuggestion_sources(parser.vars());
     move || {
         assert_is_background_thread();
         let nothing = AutosuggestionResult::default();
@@ -4741,84 +5506,84 @@ fn get_autosuggestion_performer(
                 tokens.first().map(|tok| tok.offset()).unwrap_or(cursor_pos),
             ) == search_string_range
         };
-        if cursor_line_has_process_start {
-            let mut searcher = HistorySearch::new_with_type(
-                history,
-                search_string.to_owned(),
-                SearchType::LinePrefix,
-            );
-            while !ctx.check_cancel() && searcher.go_to_next_match(SearchDirection::Backward) {
-                let item = searcher.current_item();
-
-                // Suggest only a single line each time.
-                let matched_line = item
-                    .str()
-                    .as_char_slice()
-                    .split(|&c| c == '\n')
-                    .rev()
-                    .find(|line| line.starts_with(search_string.as_char_slice()))
-                    .unwrap();
-
-                if autosuggest_validate_from_history(item, &working_directory, &ctx) {
-                    // The command autosuggestion was handled specially, so we're done.
-                    // History items are case-sensitive, see #3978.
-                    let is_whole = matched_line.len() == item.str().len();
-                    return AutosuggestionResult::new(
-                        command_line,
-                        search_string_range,
-                        matched_line.into(),
-                        /*icase=*/ false,
-                        is_whole,
-                    );
-                }
-            }
-        }
-
-        // Maybe cancel here.
-        if ctx.check_cancel() {
-            return nothing;
-        }
 
         // Here we do something a little funny. If the line ends with a space, and the cursor is not
         // at the end, don't use completion autosuggestions. It ends up being pretty weird seeing
         // stuff get spammed on the right while you go back to edit a line
         let cursor_at_end =
             cursor_pos == command_line.len() || command_line.as_char_slice()[cursor_pos] == '\n';
-        if !cursor_at_end && last_char.is_whitespace() {
-            return nothing;
-        }
-
-        // On the other hand, if the line ends with a quote, don't go dumping stuff after the quote.
-        if matches!(last_char, '\'' | '"') && cursor_at_end {
-            return nothing;
+        let can_complete = (cursor_at_end || !last_char.is_whitespace())
+            // On the other hand, if the line ends with a quote, don't go dumping stuff after the quote.
+            && !(matches!(last_char, '\'' | '"') && cursor_at_end);
+
+        // Both history sources are served by the same search, which is done at most once.
+        let mut history_suggestions: Option<HistoryAutosuggestions> = None;
+        let mut needs_load = vec![];
+        for &source in &sources {
+            // Maybe cancel here.
+            if ctx.check_cancel() {
+                return nothing;
+            }
+            let suggestion = match source {
+                AutosuggestionSource::CwdHistory | AutosuggestionSource::History => {
+                    if !cursor_line_has_process_start {
+                        continue;
+                    }
+                    let suggestions = history_suggestions.get_or_insert_with(|| {
+                        history_autosuggestions(
+                            history.clone(),
+                            search_string,
+                            &working_directory,
+                            sources.contains(&AutosuggestionSource::CwdHistory),
+                            sources.contains(&AutosuggestionSource::History),
+                            &ctx,
+                        )
+                    });
+                    let suggestion = if source == AutosuggestionSource::CwdHistory {
+                        &suggestions.closest
+                    } else {
+                        &suggestions.most_recent
+                    };
+                    // History items are case-sensitive, see #3978.
+                    suggestion
+                        .clone()
+                        .map(|(text, is_whole)| (text, /*icase=*/ false, is_whole))
+                }
+                AutosuggestionSource::Completions => {
+                    if !can_complete {
+                        continue;
+                    }
+                    let (text, load) =
+                        completion_autosuggestion(&command_line, search_string_range.end, &ctx);
+                    needs_load.extend(load);
+                    // Normal completions are case-insensitive.
+                    (!text.is_empty()).then_some((text, /*icase=*/ true, false))
+                }
+                AutosuggestionSource::Abbreviations => {
+                    if !can_complete || !cursor_at_end {
+                        continue;
+                    }
+                    abbreviation_autosuggestion(search_string)
+                        .map(|text| (text, /*icase=*/ false, false))
+                }
+            };
+            if let Some((text, icase, is_whole_item_from_history)) = suggestion {
+                let mut result = AutosuggestionResult::new(
+                    command_line,
+                    search_string_range,
+                    text,
+                    icase,
+                    is_whole_item_from_history,
+                );
+                result.needs_load = needs_load;
+                return result;
+            }
         }
-
-        // Try normal completions.
-        let complete_flags = CompletionRequestOptions::autosuggest();
-        let mut would_be_cursor = search_string_range.end;
-        let (mut completions, needs_load) =
-            complete(&command_line[..would_be_cursor], complete_flags, &ctx);
-
-        let suggestion = if completions.is_empty() {
-            WString::new()
-        } else {
-            sort_and_prioritize(&mut completions, complete_flags);
-            let comp = &completions[0];
-            let full_line = completion_apply_to_command_line(
-                &OperationContext::background_interruptible(&vars),
-                &comp.completion,
-                comp.flags,
-                &command_line,
-                &mut would_be_cursor,
-                /*append_only=*/ true,
-            );
-            line_at_cursor(&full_line, would_be_cursor).to_owned()
-        };
         let mut result = AutosuggestionResult::new(
             command_line,
-            search_string_range.clone(),
-            suggestion,
-            true, // normal completions are case-insensitive
+            search_string_range,
+            WString::new(),
+            true,
             /*is_whole_item_from_history=*/ false,
         );
         result.needs_load = needs_load;
@@ -4826,6 +5591,137 @@ fn get_autosuggestion_performer(
     }
 }
 
+/// The suggestions from history for the `cwd-history` and `history` sources: the matching line,
+/// and whether it is the whole command.
+#[derive(Default)]
+struct HistoryAutosuggestions {
+    closest: Option<(WString, bool)>,
+    most_recent: Option<(WString, bool)>,
+}
+
+/// Search history for commands with a line that starts with `search_string`, in a single pass.
+/// With `want_closest`, this finds the command run in `working_directory` or the closest of its
+/// parents, and with `want_most_recent` the most recent command.
+fn history_autosuggestions(
+    history: Arc<History>,
+    search_string: &wstr,
+    working_directory: &wstr,
+    want_closest: bool,
+    want_most_recent: bool,
+    ctx: &OperationContext,
+) -> HistoryAutosuggestions {
+    let mut searcher =
+        HistorySearch::new_with_type(history, search_string.to_owned(), SearchType::LinePrefix);
+    let valid =
+        |item: &HistoryItem| autosuggest_validate_from_history(item, working_directory, ctx);
+    let cancel_check = || ctx.check_cancel();
+    let mut result = HistoryAutosuggestions::default();
+    if want_closest {
+        let (found, most_recent) = if want_most_recent {
+            searcher.go_to_closest_match_and_find_most_recent(
+                working_directory,
+                valid,
+                cancel_check,
+            )
+        } else {
+            let found = searcher.go_to_closest_match(working_directory, valid, cancel_check);
+            (found, None)
+        };
+        // Commands run elsewhere are not suggested by this source.
+        if found && directory_affinity(searcher.current_item().cwd(), working_directory) != 0 {
+            result.closest = Some(history_suggestion_line(
+                searcher.current_item(),
+                search_string,
+            ));
+        }
+        result.most_recent = most_recent.map(|item| history_suggestion_line(&item, search_string));
+    } else if want_most_recent {
+        while !cancel_check() && searcher.go_to_next_match(SearchDirection::Backward) {
+            if valid(searcher.current_item()) {
+                result.most_recent = Some(history_suggestion_line(
+                    searcher.current_item(),
+                    search_string,
+                ));
+                break;
+            }
+        }
+    }
+    result
+}
+
+/// Returns the last line of `item` that starts with `search_string`, as only a single line is
+/// suggested each time, and whether it is the whole command.
+fn history_suggestion_line(item: &HistoryItem, search_string: &wstr) -> (WString, bool) {
+    let matched_line = item
+        .str()
+        .as_char_slice()
+        .split(|&c| c == '\n')
+        .rev()
+        .find(|line| line.starts_with(search_string.as_char_slice()))
+        .unwrap();
+    (matched_line.into(), matched_line.len() == item.str().len())
+}
+
+/// Complete the command line up to `cursor`, the end of the line being suggested for, and return
+/// that line with the first completion applied, or an empty string.
+/// Also returns the commands whose completions need loading.
+fn completion_autosuggestion(
+    command_line: &wstr,
+    cursor: usize,
+    ctx: &OperationContext,
+) -> (WString, Vec<WString>) {
+    let mut would_be_cursor = cursor;
+    let (mut completions, needs_load) = complete(
+        &command_line[..would_be_cursor],
+        CompletionRequestOptions::autosuggest(),
+        ctx,
+    );
+
+    let suggestion = if completions.is_empty() {
+        WString::new()
+    } else {
+        sort_and_prioritize(&mut completions, CompletionRequestOptions::autosuggest());
+        let comp = &completions[0];
+        let full_line = completion_apply_to_command_line(
+            ctx,
+            &comp.completion,
+            comp.flags,
+            command_line,
+            &mut would_be_cursor,
+            /*append_only=*/ true,
+        );
+        line_at_cursor(&full_line, would_be_cursor).to_owned()
+    };
+    (suggestion, needs_load)
+}
+
+/// Suggest the shortest abbreviation whose key starts with the last token of `line`, if it could
+/// be expanded there.
+fn abbreviation_autosuggestion(line: &wstr) -> Option<WString> {
+    let tokens = extract_tokens(line);
+    let index = tokens.iter().rposition(|t| t.range.end() == line.len())?;
+    let token = &tokens[index];
+    let position = if token.is_cmd {
+        abbrs::Position::Command
+    } else {
+        abbrs::Position::Anywhere
+    };
+    // If the token itself is the command, we have no command to pass.
+    let cmd = if !token.is_cmd {
+        tokens[..index]
+            .iter()
+            .rfind(|t| t.is_cmd)
+            .map(|t| &line[Range::<usize>::from(t.range)])
+    } else {
+        None
+    };
+    let prefix = &line[Range::<usize>::from(token.range)];
+    let key = abbrs_complete_key(prefix, position, cmd.unwrap_or(L!("")))?;
+    let mut suggestion = line.to_owned();
+    suggestion.push_utfstr(&key[prefix.len()..]);
+    Some(suggestion)
+}
+
 enum AutosuggestionPortion {
     Count(usize),
     PerMoveWordStyle(MoveWordStyle),
@@ -4872,7 +5768,9 @@ fn autosuggest_completed(&mut self, result: AutosuggestionResult) {
             // We loaded new completions for this command.
             // Re-do our autosuggestion.
             self.update_autosuggestion();
-        } else if !result.is_empty()
+            return;
+        }
+        if !result.is_empty()
             && self.can_autosuggest()
             && string_prefixes_string_maybe_case_insensitive(
                 result.icase,
@@ -5018,6 +5916,27 @@ fn accept_autosuggestion(&mut self, amount: AutosuggestionPortion) {
 struct HighlightResult {
     colors: Vec<HighlightSpec>,
     text: WString,
+    // The syntax error to show below the command line, if any.
+    parse_error_hint: WString,
+}
+
+/// Returns whether `$fish_parse_error_hint` asks for showing syntax errors below the command line.
+fn parse_error_hint_enabled(vars: &dyn Environment) -> bool {
+    vars.get_unless_empty(L!("fish_parse_error_hint"))
+        .is_some_and(|var| var.as_string() != "0")
+}
+
+/// Returns the first line of the message of the first syntax error in `text`, or an empty string.
+/// Unlike `commandline --is-valid`, this also describes why an incomplete command is incomplete.
+fn parse_error_hint(text: &wstr) -> WString {
+    let mut errors = vec![];
+    if parse_util_detect_errors(text, Some(&mut errors), /*allow_incomplete=*/ false).is_ok() {
+        return WString::new();
+    }
+    let Some(error) = errors.first() else {
+        return WString::new();
+    };
+    error.text.lines().next().unwrap_or_default().to_owned()
 }
 
 // Given text and  whether IO is allowed, return a function that performs highlighting. The function
@@ -5026,6 +5945,7 @@ fn get_highlight_performer(
     parser: &Parser,
     el: &EditableLine,
     io_ok: bool,
+    want_parse_error_hint: bool,
 ) -> impl FnOnce() -> HighlightResult {
     let vars = parser.vars().snapshot();
     let generation_count = read_generation_count();
@@ -5038,7 +5958,16 @@ fn get_highlight_performer(
         let ctx = get_bg_context(&vars, generation_count);
         let mut colors = vec![];
         highlight_shell(&text, &mut colors, &ctx, io_ok, Some(position));
-        HighlightResult { colors, text }
+        let parse_error_hint = if want_parse_error_hint {
+            parse_error_hint(&text)
+        } else {
+            WString::new()
+        };
+        HighlightResult {
+            colors,
+            text,
+            parse_error_hint,
+        }
     }
 }
 
@@ -5048,7 +5977,9 @@ fn highlight_complete(&mut self, result: HighlightResult) {
         self.in_flight_highlight_request.clear();
         if result.text == self.command_line.text() {
             assert_eq!(result.colors.len(), self.command_line.len());
-            if self.is_repaint_needed(Some(&result.colors)) {
+            let hint_changed = result.parse_error_hint != self.parse_error_hint;
+            self.parse_error_hint = result.parse_error_hint;
+            if self.is_repaint_needed(Some(&result.colors)) || hint_changed {
                 self.command_line.set_colors(result.colors);
                 self.layout_and_repaint(L!("highlight"));
             }
@@ -5068,8 +5999,12 @@ fn super_highlight_me_plenty(&mut self) {
         self.in_flight_highlight_request = self.command_line.text().to_owned();
 
         FLOG!(reader_render, "Highlighting");
-        let highlight_performer =
-            get_highlight_performer(self.parser, &self.command_line, /*io_ok=*/ true);
+        let highlight_performer = get_highlight_performer(
+            self.parser,
+            &self.command_line,
+            /*io_ok=*/ true,
+            self.conf.syntax_check_ok && parse_error_hint_enabled(self.parser.vars()),
+        );
         let canary = Rc::downgrade(&self.canary);
         let completion = move |zelf: &mut Reader, result| {
             if canary.upgrade().is_none() {
@@ -5120,8 +6055,12 @@ fn finish_highlighting_before_exec(&mut self) {
 
         if !current_highlight_ok {
             // We need to do a quick highlight without I/O.
-            let highlight_no_io =
-                get_highlight_performer(self.parser, &self.command_line, /*io_ok=*/ false);
+            let highlight_no_io = get_highlight_performer(
+                self.parser,
+                &self.command_line,
+                /*io_ok=*/ false,
+                /*want_parse_error_hint=*/ false,
+            );
             self.highlight_complete(highlight_no_io());
         }
     }
@@ -5131,9 +6070,19 @@ struct HistoryPagerResult {
     matched_commands: Vec<Completion>,
     range: Range<usize>,
     first_shown: usize,
+    /// In fuzzy mode, the ranked commands. The range is then a range of ranks.
+    ranked: Option<Arc<RankedHistory>>,
     motion: Option<SelectionMotion>,
 }
 
+/// The commands matching a fuzzy history pager search, best first. These are kept while paging
+/// through them, so the history is only ranked again when the search changes.
+struct RankedHistory {
+    /// The search string the commands were ranked for.
+    search_string: WString,
+    commands: Vec<RankedCommand>,
+}
+
 #[derive(Eq, PartialEq)]
 enum HistoryPagerInvocation {
     Anew,
@@ -5141,29 +6090,54 @@ enum HistoryPagerInvocation {
     Refresh,
 }
 
+/// How the history pager interprets its search field.
+#[derive(Clone, Copy, Default, Eq, PartialEq)]
+enum HistoryPagerMode {
+    /// Match a glob pattern, or else a subsequence. Results are ordered by recency.
+    #[default]
+    Glob,
+    /// Match a regular expression.
+    Regex,
+    /// Rank subsequence matches by match quality and frecency.
+    Fuzzy,
+}
+
+/// The number of history pager entries to show at a time.
+fn history_pager_page_size() -> usize {
+    // Limit the number of elements to half the screen like we do for completions
+    // Note that this is imperfect because we could have a multi-column layout.
+    //
+    // We can still push fish further upward in case the first entry is multiline,
+    // but that can't really be helped.
+    // (subtract 2 for the search line and the prompt)
+    usize::try_from(cmp::max(termsize_last().height / 2 - 2, 12)).unwrap()
+}
+
 fn history_pager_search(
     history: &Arc<History>,
     direction: SearchDirection,
     motion: Option<SelectionMotion>,
     history_index: usize,
     search_string: &wstr,
+    regex: bool,
 ) -> HistoryPagerResult {
-    // Limit the number of elements to half the screen like we do for completions
-    // Note that this is imperfect because we could have a multi-column layout.
-    //
-    // We can still push fish further upward in case the first entry is multiline,
-    // but that can't really be helped.
-    // (subtract 2 for the search line and the prompt)
-    let page_size = usize::try_from(cmp::max(termsize_last().height / 2 - 2, 12)).unwrap();
+    let page_size = history_pager_page_size();
     let mut completions = Vec::with_capacity(page_size);
     let mut search = HistorySearch::new_with(
         history.clone(),
         search_string.to_owned(),
-        SearchType::ContainsGlob,
+        if regex {
+            SearchType::Regex
+        } else {
+            SearchType::ContainsGlob
+        },
         smartcase_flags(search_string),
         history_index,
     );
-    if !search.go_to_next_match(direction) && !parse_util_contains_wildcards(search_string) {
+    if !search.go_to_next_match(direction)
+        && !regex
+        && !parse_util_contains_wildcards(search_string)
+    {
         // If there were no matches, and the user is not intending for
         // wildcard search, try again with subsequence search.
         search = HistorySearch::new_with(
@@ -5202,17 +6176,53 @@ fn history_pager_search(
             Some(SelectionMotion::Prev),
             history.size() + 1,
             search_string,
+            regex,
         )
     } else {
         HistoryPagerResult {
             matched_commands: completions,
             range,
             first_shown,
+            ranked: None,
             motion,
         }
     }
 }
 
+/// Like [`history_pager_search`], but pages through the commands ranked by a fuzzy search,
+/// starting at the given rank.
+fn history_pager_fuzzy_search(
+    ranked: Arc<RankedHistory>,
+    motion: Option<SelectionMotion>,
+    first: usize,
+) -> HistoryPagerResult {
+    let page_size = history_pager_page_size();
+    let first = first.min(ranked.commands.len().saturating_sub(1));
+    let last = cmp::min(first + page_size, ranked.commands.len());
+    let matched_commands = ranked.commands[first..last]
+        .iter()
+        .map(|command| {
+            let mut completion = Completion::new(
+                command.text.clone(),
+                L!("").to_owned(),
+                StringFuzzyMatch::exact_match(),
+                CompleteFlags::REPLACES_LINE
+                    | CompleteFlags::DONT_ESCAPE
+                    | CompleteFlags::DONT_SORT,
+            );
+            completion.match_positions = command.positions.clone();
+            completion
+        })
+        .collect();
+    HistoryPagerResult {
+        matched_commands,
+        range: first..last,
+        first_shown: first + 1,
+        ranked: Some(ranked),
+        motion,
+    }
+}
+
 impl ReaderData {
     fn fill_history_pager(
         &mut self,
@@ -5229,9 +6239,14 @@ fn fill_history_pager(
             }
             HistoryPagerInvocation::Advance => {
                 let history_pager = self.history_pager.as_ref().unwrap();
-                index = match direction {
-                    SearchDirection::Forward => history_pager.start + 1,
-                    SearchDirection::Backward => history_pager.end - 1,
+                index = match (direction, &self.history_pager_ranked) {
+                    // In fuzzy mode, this is the rank of the first command to show.
+                    (SearchDirection::Forward, Some(_)) => history_pager
+                        .start
+                        .saturating_sub(history_pager_page_size()),
+                    (SearchDirection::Backward, Some(_)) => history_pager.end,
+                    (SearchDirection::Forward, None) => history_pager.start + 1,
+                    (SearchDirection::Backward, None) => history_pager.end - 1,
                 }
             }
             HistoryPagerInvocation::Refresh => {
@@ -5243,37 +6258,90 @@ fn fill_history_pager(
             }
         }
         let search_term = self.pager.search_field_line.text().to_owned();
+        let mode = self.history_pager_mode;
+        // When paging, reuse the commands we ranked already.
+        let ranked = self.history_pager_ranked.clone().filter(|ranked| {
+            why == HistoryPagerInvocation::Advance
+                && mode == HistoryPagerMode::Fuzzy
+                && ranked.search_string == search_term
+        });
         let performer = {
             let history = self.history.clone();
             let search_term = search_term.clone();
-            move || history_pager_search(&history, direction, motion, index, &search_term)
+            move || match mode {
+                HistoryPagerMode::Fuzzy => {
+                    let ranked = ranked.unwrap_or_else(|| {
+                        Arc::new(RankedHistory {
+                            commands: history
+                                .fuzzy_search(&search_term, smartcase_flags(&search_term)),
+                            search_string: search_term,
+                        })
+                    });
+                    history_pager_fuzzy_search(ranked, motion, index)
+                }
+                _ => history_pager_search(
+                    &history,
+                    direction,
+                    motion,
+                    index,
+                    &search_term,
+                    mode == HistoryPagerMode::Regex,
+                ),
+            }
         };
         let canary = Rc::downgrade(&self.canary);
         let completion = move |zelf: &mut Reader, result: HistoryPagerResult| {
             if canary.upgrade().is_none() {
                 return;
             }
-            if search_term != zelf.pager.search_field_line.text() {
+            if search_term != zelf.pager.search_field_line.text() || mode != zelf.history_pager_mode
+            {
                 return; // Stale request.
             }
             let history_size = zelf.history.size();
             let history_pager = zelf.history_pager.as_mut().unwrap();
-            assert!(result.range.start < result.range.end);
+            assert!(result.ranked.is_some() || result.range.start < result.range.end);
             *history_pager = result.range;
-            zelf.pager.extra_progress_text =
-                if !result.matched_commands.is_empty() && *history_pager != (0..history_size + 1) {
+            zelf.pager.extra_progress_text = if let Some(ranked) = &result.ranked {
+                let count = ranked.commands.len();
+                if !result.matched_commands.is_empty() && *history_pager != (0..count) {
                     wgettext_fmt!(
-                        "Items %lu to %lu of %lu",
-                        match history_pager.start {
-                            0 => 1,
-                            _ => result.first_shown,
-                        },
-                        history_pager.end - 1,
-                        history_size
+                        "Matches %lu to %lu of %lu",
+                        result.first_shown,
+                        result.first_shown + result.matched_commands.len() - 1,
+                        count
                     )
                 } else {
                     L!("").to_owned()
-                };
+                }
+            } else if !result.matched_commands.is_empty() && *history_pager != (0..history_size + 1)
+            {
+                wgettext_fmt!(
+                    "Items %lu to %lu of %lu",
+                    match history_pager.start {
+                        0 => 1,
+                        _ => result.first_shown,
+                    },
+                    history_pager.end - 1,
+                    history_size
+                )
+            } else {
+                L!("").to_owned()
+            };
+            zelf.history_pager_ranked = result.ranked;
+            let mode_text = match mode {
+                HistoryPagerMode::Glob => None,
+                HistoryPagerMode::Regex => Some(wgettext!("Regex search")),
+                HistoryPagerMode::Fuzzy => Some(wgettext!("Fuzzy search")),
+            };
+            if let Some(mode_text) = mode_text {
+                let mut text = mode_text.to_owned();
+                if !zelf.pager.extra_progress_text.is_empty() {
+                    text.push_str(". ");
+                    text.push_utfstr(&zelf.pager.extra_progress_text);
+                }
+                zelf.pager.extra_progress_text = text;
+            }
             zelf.pager.set_completions(&result.matched_commands, false);
             if why == HistoryPagerInvocation::Refresh {
                 zelf.pager.set_selected_completion_index(old_pager_index);
@@ -5491,6 +6559,44 @@ fn expand_abbreviation_at_cursor(&mut self, cursor_backtrack: usize) -> bool {
         }
         false
     }
+
+    /// Replaces the command of the process at or before the cursor with the closest command name,
+    /// if it is a plain word that is not a command. Returns whether it was replaced.
+    fn correct_command_at_cursor(&mut self) -> bool {
+        let (elt, el) = self.active_edit_line();
+        if elt != EditableLineTag::Commandline {
+            return false;
+        }
+        let Some(range) = command_range_at_cursor(el.text(), el.position()) else {
+            return false;
+        };
+        let cmd = &el.text()[range.clone()];
+        // Leave anything that is expanded or quoted alone.
+        if !cmd
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | ':' | '@'))
+        {
+            return false;
+        }
+        let vars = self.parser.vars();
+        if command_is_valid(cmd, StatementDecoration::none, &vars.get_pwd_slash(), vars) {
+            return false;
+        }
+        let Some(correction) = command_suggestions(cmd, vars).into_iter().next() else {
+            return false;
+        };
+        self.push_edit(elt, Edit::new(range, correction));
+        true
+    }
+}
+
+/// Returns the range of the last command that starts at or before the cursor.
+fn command_range_at_cursor(cmdline: &wstr, cursor_pos: usize) -> Option<Range<usize>> {
+    extract_tokens(cmdline)
+        .into_iter()
+        .rev()
+        .find(|t| t.is_cmd && t.range.start() <= cursor_pos)
+        .map(|t| t.range.into())
 }
 
 /// Indicates if the given command char ends paging.
@@ -5516,6 +6622,8 @@ fn command_ends_paging(c: ReadlineCmd, focused_on_search_field: bool) -> bool {
         rl::Complete
         | rl::CompleteAndSearch
         | rl::HistoryPager
+        | rl::HistoryPagerToggleRegex
+        | rl::HistoryPagerToggleFuzzy
         | rl::BackwardChar
         | rl::BackwardCharPassive
         | rl::ForwardChar
@@ -5572,7 +6680,10 @@ fn command_ends_paging(c: ReadlineCmd, focused_on_search_field: bool) -> bool {
         | rl::BeginningOfBuffer
         | rl::EndOfBuffer
         | rl::Undo
-        | rl::Redo =>
+        | rl::Redo
+        | rl::ViDelete
+        | rl::ViChange
+        | rl::ViYank =>
         // These commands operate on the search field if that's where the focus is.
         {
             !focused_on_search_field
@@ -5581,6 +6692,48 @@ fn command_ends_paging(c: ReadlineCmd, focused_on_search_field: bool) -> bool {
     }
 }
 
+/// Indicates if a numeric argument repeats the given command.
+fn command_repeats(c: ReadlineCmd) -> bool {
+    #[allow(non_camel_case_types)]
+    type rl = ReadlineCmd;
+    matches!(
+        c,
+        rl::ForwardChar
+            | rl::BackwardChar
+            | rl::BackwardCharPassive
+            | rl::ForwardSingleChar
+            | rl::ForwardCharPassive
+            | rl::ForwardWord
+            | rl::BackwardWord
+            | rl::ForwardBigword
+            | rl::BackwardBigword
+            | rl::ForwardToken
+            | rl::BackwardToken
+            | rl::UpLine
+            | rl::DownLine
+            | rl::DeleteChar
+            | rl::DeleteOrExit
+            | rl::BackwardDeleteChar
+            | rl::KillWord
+            | rl::KillBigword
+            | rl::KillToken
+            | rl::BackwardKillWord
+            | rl::BackwardKillPathComponent
+            | rl::BackwardKillBigword
+            | rl::BackwardKillToken
+            | rl::TransposeChars
+            | rl::TransposeWords
+            | rl::UpcaseWord
+            | rl::DowncaseWord
+            | rl::CapitalizeWord
+            | rl::TogglecaseChar
+            | rl::InsertLineUnder
+            | rl::InsertLineOver
+            | rl::Undo
+            | rl::Redo
+    )
+}
+
 /// Indicates if the given command ends the history search.
 fn command_ends_history_search(c: ReadlineCmd) -> bool {
     #[allow(non_camel_case_types)]
@@ -5596,6 +6749,8 @@ fn command_ends_history_search(c: ReadlineCmd) -> bool {
             | rl::HistoryTokenSearchForward
             | rl::HistoryDelete
             | rl::HistoryPagerDelete
+            | rl::HistoryPagerToggleRegex
+            | rl::HistoryPagerToggleFuzzy
             | rl::BeginningOfHistory
             | rl::EndOfHistory
             | rl::Repaint
@@ -5657,7 +6812,8 @@ fn check_for_orphaned_process(loop_count: usize, shell_pgid: libc::pid_t) -> boo
 
 /// Run the specified command with the correct terminal modes, and while taking care to perform job
 /// notification, set the title, etc.
-fn reader_run_command(parser: &Parser, cmd: &wstr) -> EvalRes {
+/// Returns the result and how long the command took.
+fn reader_run_command(parser: &Parser, cmd: &wstr) -> (EvalRes, Duration) {
     let ft = tok_command(cmd);
 
     // Provide values for `status current-command` and `status current-commandline`
@@ -5679,12 +6835,11 @@ fn reader_run_command(parser: &Parser, cmd: &wstr) -> EvalRes {
     let time_before = Instant::now();
     let eval_res = parser.eval(cmd, &IoChain::new());
     job_reap(parser, true);
+    let duration = Instant::now().duration_since(time_before);
 
     // Update the execution duration iff a command is requested for execution
     // issue - #4926
     if !ft.is_empty() {
-        let time_after = Instant::now();
-        let duration = time_after.duration_since(time_before);
         parser.vars().set_one(
             ENV_CMD_DURATION,
             EnvMode::UNEXPORT,
@@ -5709,7 +6864,7 @@ fn reader_run_command(parser: &Parser, cmd: &wstr) -> EvalRes {
         proc_update_jiffies(parser);
     }
 
-    eval_res
+    (eval_res, duration)
 }
 
 fn reader_shell_test(parser: &Parser, bstr: &wstr) -> Result<(), ParserTestErrorBits> {
@@ -5731,6 +6886,17 @@ fn reader_shell_test(parser: &Parser, bstr: &wstr) -> Result<(), ParserTestError
 }
 
 impl<'a> Reader<'a> {
+    /// If history sharing is enabled, picks up the commands that other shells have run since we
+    /// last looked at the history file, and watches the file for more.
+    fn incorporate_shared_history(&self) {
+        if history_sharing_enabled(self.vars()) {
+            watch_history_file(Some(&*self.history));
+            self.history.incorporate_file_changes();
+        } else {
+            watch_history_file(None);
+        }
+    }
+
     // Import history from older location (config path) if our current history is empty.
     fn import_history_if_necessary(&mut self) {
         if self.history.is_empty() {
@@ -5798,7 +6964,9 @@ fn add_to_history(&mut self) {
             // Mark this item as ephemeral if should_add_to_history says no (#615).
             let mode = if !self.should_add_to_history(&text) {
                 PersistenceMode::Ephemeral
-            } else if in_private_mode(self.vars()) {
+            } else if in_private_mode(self.vars()) || matches_secret_pattern(self.vars(), &text) {
+                // Commands that look like they contain secrets are still available for recall
+                // in this session, but never written to disk.
                 PersistenceMode::Memory
             } else {
                 PersistenceMode::Disk
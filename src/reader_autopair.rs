@@ -0,0 +1,132 @@
+//! Automatic pairing of brackets and quotes in the command line.
+//!
+//! If `$fish_autopair` is set, typing an opening bracket or quote also inserts the closing one, typing
+//! a closing one steps over the one that is already there, and deleting the opening one of an empty
+//! pair deletes the closing one too. This looks at the quoting at the cursor, so nothing is paired in
+//! comments or after a backslash, and inside quotes only the closing quote is stepped over.
+
+use crate::env::Environment;
+#[allow(unused_imports)]
+use crate::future::IsSomeAnd;
+use crate::parse_util::{
+    parse_util_cmdsubst_extent, parse_util_locate_cmdsubst_range, MaybeParentheses,
+};
+use crate::reader::get_quote;
+use crate::tokenizer::{TokenType, Tokenizer, TOK_ACCEPT_UNFINISHED, TOK_SHOW_COMMENTS};
+use crate::wchar::prelude::*;
+use crate::wcstringutil::count_preceding_backslashes;
+
+/// Returns whether `$fish_autopair` asks for pairing brackets and quotes.
+pub fn autopair_enabled(vars: &dyn Environment) -> bool {
+    vars.get_unless_empty(L!("fish_autopair"))
+        .is_some_and(|var| var.as_string() != "0")
+}
+
+/// Returns the closing character that goes with `c`, if it is an opening bracket or a quote.
+fn closer(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' | '\'' => Some(c),
+        _ => None,
+    }
+}
+
+/// Returns whether typing `c` may do something other than inserting it.
+pub fn is_autopair_char(c: char) -> bool {
+    closer(c).is_some() || matches!(c, ')' | ']' | '}')
+}
+
+/// What to do instead of inserting a typed character.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AutopairEdit {
+    /// Insert the character followed by this closing one, and put the cursor between them.
+    InsertPair(char),
+    /// Only move the cursor over the character, which is already there.
+    StepOver,
+}
+
+/// The context of a position in the command line.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Context {
+    Normal,
+    Quoted(char),
+    /// After a backslash that escapes the next character.
+    Escaped,
+    Comment,
+}
+
+/// Returns the context at `cursor`, as the tokenizer sees it.
+fn context_at(text: &wstr, cursor: usize) -> Context {
+    // The innermost command substitution has quotes and comments of its own.
+    let cmdsub = parse_util_cmdsubst_extent(text, cursor);
+    let before = &text[cmdsub.start..cursor];
+    if Tokenizer::new(before, TOK_ACCEPT_UNFINISHED | TOK_SHOW_COMMENTS)
+        .any(|tok| tok.type_ == TokenType::comment && tok.end() == before.len())
+    {
+        return Context::Comment;
+    }
+    if count_preceding_backslashes(before, before.len()) % 2 == 1 {
+        return Context::Escaped;
+    }
+
+    // Only the quotes after the last command substitution matter. If that one is in double
+    // quotes, they continue after it.
+    let mut rest = before;
+    let mut rest_is_quoted = false;
+    let mut offset = 0;
+    let mut is_quoted = false;
+    while let MaybeParentheses::CommandSubstitution(parens) = parse_util_locate_cmdsubst_range(
+        before,
+        &mut offset,
+        /*accept_incomplete=*/ false,
+        Some(&mut is_quoted),
+        None,
+    ) {
+        rest = &before[parens.end()..];
+        rest_is_quoted = is_quoted;
+    }
+    let mut rest = rest.to_owned();
+    if rest_is_quoted {
+        rest.insert(0, '"');
+    }
+    match get_quote(&rest, rest.len()) {
+        Some(q) => Context::Quoted(q),
+        None => Context::Normal,
+    }
+}
+
+/// Returns what typing `c` at `cursor` should do instead of inserting it, if anything.
+pub fn autopair_insertion(text: &wstr, cursor: usize, c: char) -> Option<AutopairEdit> {
+    let next = text.as_char_slice().get(cursor).copied();
+    match context_at(text, cursor) {
+        Context::Escaped | Context::Comment => None,
+        // Brackets are not special in quotes, so only the closing quote is.
+        Context::Quoted(quote) => {
+            (c == quote && next == Some(quote)).then_some(AutopairEdit::StepOver)
+        }
+        Context::Normal => {
+            if matches!(c, ')' | ']' | '}') {
+                return (next == Some(c)).then_some(AutopairEdit::StepOver);
+            }
+            let closer = closer(c)?;
+            // Only pair at the end of a token, so typing in front of a word does not add a closer
+            // there.
+            let at_token_end = next.is_none_or(|next| {
+                next.is_whitespace() || matches!(next, ')' | ']' | '}' | ';' | '|' | '&')
+            });
+            at_token_end.then_some(AutopairEdit::InsertPair(closer))
+        }
+    }
+}
+
+/// Returns whether deleting backwards at `cursor` should delete both characters of an empty pair.
+pub fn autopair_deletion(text: &wstr, cursor: usize) -> bool {
+    let chars = text.as_char_slice();
+    if cursor == 0 || cursor == chars.len() {
+        return false;
+    }
+    closer(chars[cursor - 1]) == Some(chars[cursor])
+        && context_at(text, cursor - 1) == Context::Normal
+}
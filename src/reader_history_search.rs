@@ -53,6 +53,10 @@ pub struct ReaderHistorySearch {
     /// Our history search itself.
     search: Option<HistorySearch>,
 
+    /// A search for the match run closest to this working directory, which is found before the
+    /// others. See [`Self::prefer_directory`].
+    closest_search: Option<(HistorySearch, WString)>,
+
     /// The ordered list of matches. This may grow long.
     matches: Vec<SearchMatch>,
 
@@ -167,6 +171,7 @@ pub fn reset_to_mode(
         self.match_index = 0;
         self.mode = mode;
         self.token_offset = token_offset;
+        self.closest_search = None;
         let flags = SearchFlags::NO_DEDUP | smartcase_flags(&text);
         // We can skip dedup in history_search_t because we do it ourselves in skips_.
         self.search = Some(HistorySearch::new_with(
@@ -182,6 +187,21 @@ pub fn reset_to_mode(
         ));
     }
 
+    /// Make the first match of a line or prefix search the one that was run in the given
+    /// directory or the closest of its parents. The other matches follow by recency.
+    pub fn prefer_directory(&mut self, working_directory: WString) {
+        assert!(self.by_line() || self.by_prefix());
+        let search = self.search();
+        let closest_search = HistorySearch::new_with(
+            search.history().clone(),
+            search.original_term().to_owned(),
+            search.search_type(),
+            search.flags(),
+            0,
+        );
+        self.closest_search = Some((closest_search, working_directory));
+    }
+
     /// Reset to inactive search.
     pub fn reset(&mut self) {
         self.matches.clear();
@@ -190,6 +210,7 @@ pub fn reset(&mut self) {
         self.mode = SearchMode::Inactive;
         self.token_offset = 0;
         self.search = None;
+        self.closest_search = None;
     }
 
     /// Adds the given match if we haven't seen it before.
@@ -199,15 +220,40 @@ fn add_if_new(&mut self, search_match: SearchMatch) {
         }
     }
 
+    /// Return the offset of `needle` in `haystack`, ignoring case like our search.
+    fn find(&self, haystack: &wstr, needle: &wstr) -> Option<usize> {
+        if self.search().ignores_case() {
+            return ifind(haystack, needle, false);
+        }
+        haystack.find(needle)
+    }
+
+    /// Attempt to append the match that was run closest to the working directory, if we are
+    /// asked to look for it and have not yet.
+    /// Return true if it was appended.
+    fn append_closest_match(&mut self) -> bool {
+        let Some((mut closest_search, working_directory)) = self.closest_search.take() else {
+            return false;
+        };
+        let skips = &self.skips;
+        if !closest_search.go_to_closest_match(
+            &working_directory,
+            |item| !skips.contains(item.str()),
+            || false,
+        ) {
+            return false;
+        }
+        let text = closest_search.current_string();
+        let Some(offset) = self.find(text, self.search_string()) else {
+            return false;
+        };
+        self.add_if_new(SearchMatch::new(text.to_owned(), offset));
+        true
+    }
+
     /// Attempt to append matches from the current history item.
     /// Return true if something was appended.
     fn append_matches_from_search(&mut self) -> bool {
-        fn find(zelf: &ReaderHistorySearch, haystack: &wstr, needle: &wstr) -> Option<usize> {
-            if zelf.search().ignores_case() {
-                return ifind(haystack, needle, false);
-            }
-            haystack.find(needle)
-        }
         let before = self.matches.len();
         let text = self.search().current_string();
         let needle = self.search_string();
@@ -218,7 +264,7 @@ fn find(zelf: &ReaderHistorySearch, haystack: &wstr, needle: &wstr) -> Option<us
             // However, because the user experience of having it crash is horrible,
             // and the worst thing that can otherwise happen here is that a search is unsuccessful,
             // we just check it instead.
-            if let Some(offset) = find(self, text, needle) {
+            if let Some(offset) = self.find(text, needle) {
                 self.add_if_new(SearchMatch::new(text.to_owned(), offset));
             }
         } else if self.mode == SearchMode::Token {
@@ -230,7 +276,7 @@ fn find(zelf: &ReaderHistorySearch, haystack: &wstr, needle: &wstr) -> Option<us
                     continue;
                 }
                 let text = tok.text_of(&token);
-                if let Some(offset) = find(self, text, needle) {
+                if let Some(offset) = self.find(text, needle) {
                     local_tokens.push(SearchMatch::new(text.to_owned(), offset));
                 }
             }
@@ -260,6 +306,11 @@ fn move_backwards(&mut self) -> bool {
             return true;
         }
 
+        if self.append_closest_match() {
+            self.match_index += 1;
+            return true;
+        }
+
         // Add more items from our search.
         while self
             .search_mut()
@@ -0,0 +1,497 @@
+//! The operator-pending mode of the reader's vi key bindings.
+//!
+//! After an operator like `d` (delete), `c` (change) or `y` (yank), the reader waits for a motion,
+//! optionally preceded by a count, like `w`, `3e`, `f,`, `%`, or a text object like `iw` or `a(`.
+//! The operator then applies to the text between the cursor and where the motion ends.
+//! This module parses the motion and computes its range for the motions that only depend on the
+//! text; jumps and brackets are left to the reader, which remembers the last jump.
+
+use crate::reader::{JumpDirection, JumpPrecision};
+use crate::tokenizer::{MoveWordStateMachine, MoveWordStyle};
+use crate::wchar::prelude::*;
+use std::ops::Range;
+
+/// The largest count we accept, to keep a held-down digit from making us loop forever.
+pub const MAX_COUNT: usize = 9999;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ViOperator {
+    Delete,
+    Change,
+    Yank,
+}
+
+impl ViOperator {
+    /// The key of the operator, which repeated applies the operator to whole lines.
+    fn key(self) -> char {
+        match self {
+            ViOperator::Delete => 'd',
+            ViOperator::Change => 'c',
+            ViOperator::Yank => 'y',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextObject {
+    /// `w` and `W`
+    Word { big: bool },
+    /// `(`, `[` and `{`, or the matching closing brackets, `b` and `B`
+    Bracket(char, char),
+    /// `"`, `'` and `` ` ``
+    Quote(char),
+    /// Any other character, for the text between its closest occurrences around the cursor
+    Delimiter(char),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ViMotion {
+    /// `h`
+    Left,
+    /// `l`
+    Right,
+    /// `0`
+    LineStart,
+    /// `^`
+    FirstNonBlank,
+    /// `$`
+    LineEnd,
+    /// `w` and `W`
+    WordStart { big: bool },
+    /// `e` and `E`
+    WordEnd { big: bool },
+    /// `b` and `B`
+    BackwardWord { big: bool },
+    /// `ge` and `gE`
+    BackwardWordEnd { big: bool },
+    /// `f`, `F`, `t` and `T`, followed by the target character
+    Jump(JumpDirection, JumpPrecision, char),
+    /// `;` and `,`
+    RepeatJump { reverse: bool },
+    /// `%`
+    MatchingBracket,
+    /// The operator key again, as in `dd`, for whole lines.
+    Line,
+    /// `i` (inner) or `a` (around), followed by the object
+    TextObject { inner: bool, object: TextObject },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ViCommand {
+    /// How often to repeat the motion; for bracket objects, how many levels to go out.
+    pub count: usize,
+    pub motion: ViMotion,
+}
+
+pub enum ViParseResult {
+    /// More keys are needed.
+    Incomplete,
+    Complete(ViCommand),
+    /// The keys do not form a motion, which cancels the operator.
+    Invalid,
+}
+
+/// Parses the keys typed after an operator, one at a time.
+pub struct ViMotionParser {
+    operator: ViOperator,
+    count: Option<usize>,
+    /// The first key of a two-key motion, like `g` or `f`.
+    prefix: Option<char>,
+}
+
+impl ViMotionParser {
+    pub fn new(operator: ViOperator) -> Self {
+        Self {
+            operator,
+            count: None,
+            prefix: None,
+        }
+    }
+
+    pub fn push(&mut self, c: char) -> ViParseResult {
+        let motion = if let Some(prefix) = self.prefix {
+            match (prefix, c) {
+                ('g', 'e') => ViMotion::BackwardWordEnd { big: false },
+                ('g', 'E') => ViMotion::BackwardWordEnd { big: true },
+                ('g', _) => return ViParseResult::Invalid,
+                ('i' | 'a', _) => {
+                    let object = match c {
+                        'w' => TextObject::Word { big: false },
+                        'W' => TextObject::Word { big: true },
+                        '(' | ')' | 'b' => TextObject::Bracket('(', ')'),
+                        '[' | ']' => TextObject::Bracket('[', ']'),
+                        '{' | '}' | 'B' => TextObject::Bracket('{', '}'),
+                        '"' | '\'' | '`' => TextObject::Quote(c),
+                        _ => TextObject::Delimiter(c),
+                    };
+                    ViMotion::TextObject {
+                        inner: prefix == 'i',
+                        object,
+                    }
+                }
+                ('f', _) => ViMotion::Jump(JumpDirection::Forward, JumpPrecision::To, c),
+                ('F', _) => ViMotion::Jump(JumpDirection::Backward, JumpPrecision::To, c),
+                ('t', _) => ViMotion::Jump(JumpDirection::Forward, JumpPrecision::Till, c),
+                ('T', _) => ViMotion::Jump(JumpDirection::Backward, JumpPrecision::Till, c),
+                _ => unreachable!(),
+            }
+        } else {
+            match c {
+                '0'..='9' if c != '0' || self.count.is_some() => {
+                    let digit = c.to_digit(10).unwrap() as usize;
+                    let count = self.count.unwrap_or(0) * 10 + digit;
+                    self.count = Some(count.min(MAX_COUNT));
+                    return ViParseResult::Incomplete;
+                }
+                'g' | 'i' | 'a' | 'f' | 'F' | 't' | 'T' => {
+                    self.prefix = Some(c);
+                    return ViParseResult::Incomplete;
+                }
+                _ if c == self.operator.key() => ViMotion::Line,
+                'h' => ViMotion::Left,
+                'l' => ViMotion::Right,
+                '0' => ViMotion::LineStart,
+                '^' => ViMotion::FirstNonBlank,
+                '$' => ViMotion::LineEnd,
+                'w' => ViMotion::WordStart { big: false },
+                'W' => ViMotion::WordStart { big: true },
+                'e' => ViMotion::WordEnd { big: false },
+                'E' => ViMotion::WordEnd { big: true },
+                'b' => ViMotion::BackwardWord { big: false },
+                'B' => ViMotion::BackwardWord { big: true },
+                ';' => ViMotion::RepeatJump { reverse: false },
+                ',' => ViMotion::RepeatJump { reverse: true },
+                '%' => ViMotion::MatchingBracket,
+                _ => return ViParseResult::Invalid,
+            }
+        };
+        ViParseResult::Complete(ViCommand {
+            count: self.count.unwrap_or(1).max(1),
+            motion,
+        })
+    }
+}
+
+fn word_style(big: bool) -> MoveWordStyle {
+    if big {
+        MoveWordStyle::Whitespace
+    } else {
+        MoveWordStyle::Punctuation
+    }
+}
+
+/// Return whether `c` is whitespace within a line.
+fn is_blank(c: char) -> bool {
+    c != '\n' && c.is_whitespace()
+}
+
+/// Return the range of the line containing `pos`, without its newline.
+fn line_range(text: &[char], pos: usize) -> Range<usize> {
+    let start = text[..pos]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |i| i + 1);
+    let end = text[pos..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(text.len(), |i| pos + i);
+    start..end
+}
+
+/// Return the end of the word starting at `pos`, as if moving right with forward-word.
+fn forward_word_end(text: &[char], pos: usize, style: MoveWordStyle) -> usize {
+    let mut state = MoveWordStateMachine::new(style);
+    let mut end = pos;
+    while end < text.len() && state.consume_char(text[end]) {
+        end += 1;
+    }
+    // Always consume at least one character.
+    end.max(pos + 1).min(text.len())
+}
+
+/// Return the start of the word ending at `pos`, as if moving left with backward-word.
+fn backward_word_start(text: &[char], pos: usize, style: MoveWordStyle) -> usize {
+    let mut state = MoveWordStateMachine::new(style);
+    let mut start = pos;
+    while start > 0 && state.consume_char(text[start - 1]) {
+        start -= 1;
+    }
+    start.min(pos.saturating_sub(1))
+}
+
+/// Return the range of `text` that `operator` applies to, for the motion `cmd` from `pos`.
+/// Return None if the motion fails, or if it is not a text-only motion.
+pub fn motion_range(
+    text: &wstr,
+    pos: usize,
+    operator: ViOperator,
+    cmd: ViCommand,
+) -> Option<Range<usize>> {
+    let text = text.as_char_slice();
+    let len = text.len();
+    let count = cmd.count;
+    let line = line_range(text, pos);
+    let range = match cmd.motion {
+        ViMotion::Left => pos - count.min(pos - line.start)..pos,
+        ViMotion::Right => pos..(pos + count).min(line.end),
+        ViMotion::LineStart => line.start..pos,
+        ViMotion::FirstNonBlank => {
+            let first = text[line.clone()]
+                .iter()
+                .position(|&c| !is_blank(c))
+                .map_or(line.end, |i| line.start + i);
+            first.min(pos)..first.max(pos)
+        }
+        ViMotion::LineEnd => {
+            let mut end = line.end;
+            for _ in 1..count {
+                if end < len {
+                    end = line_range(text, end + 1).end;
+                }
+            }
+            pos..end
+        }
+        ViMotion::WordStart { big } => {
+            let mut end = pos;
+            for i in 0..count {
+                if end >= len {
+                    break;
+                }
+                let last = i + 1 == count;
+                if !text[end].is_whitespace() {
+                    end = forward_word_end(text, end, word_style(big));
+                    // Like vim, "cw" changes only the word, not the whitespace after it.
+                    if last && operator == ViOperator::Change {
+                        while end > pos + 1 && text[end - 1].is_whitespace() {
+                            end -= 1;
+                        }
+                        break;
+                    }
+                }
+                // Skip to the next word, but don't join the last word with the next line.
+                while end < len && text[end].is_whitespace() && !(last && text[end] == '\n') {
+                    end += 1;
+                }
+            }
+            pos..end
+        }
+        ViMotion::WordEnd { big } => {
+            // The end is inclusive.
+            let mut end = pos;
+            for _ in 0..count {
+                if end + 1 >= len {
+                    break;
+                }
+                end = forward_word_end(text, end + 1, word_style(big)) - 1;
+            }
+            pos..(end + 1).min(len)
+        }
+        ViMotion::BackwardWord { big } => {
+            let mut start = pos;
+            for _ in 0..count {
+                if start == 0 {
+                    break;
+                }
+                start = backward_word_start(text, start, word_style(big));
+            }
+            start..pos
+        }
+        ViMotion::BackwardWordEnd { big } => {
+            // Both ends are inclusive.
+            let mut start = pos;
+            for _ in 0..count {
+                let mut prev = start;
+                if text.get(start).is_some_and(|c| !c.is_whitespace()) {
+                    prev = backward_word_start(text, start + 1, word_style(big));
+                }
+                while prev > 0 && text[prev - 1].is_whitespace() {
+                    prev -= 1;
+                }
+                if prev == 0 {
+                    break;
+                }
+                start = prev - 1;
+            }
+            if start == pos {
+                return None;
+            }
+            start..(pos + 1).min(len)
+        }
+        ViMotion::Line => {
+            let mut start = line.start;
+            let mut end = line.end;
+            for _ in 1..count {
+                if end < len {
+                    end = line_range(text, end + 1).end;
+                }
+            }
+            // Changing keeps the line itself. Otherwise, take its newline, or the one before it
+            // if it is the last line, like kill-whole-line.
+            if operator != ViOperator::Change {
+                if end < len {
+                    end += 1;
+                } else {
+                    start = start.saturating_sub(1);
+                }
+            }
+            return Some(start..end);
+        }
+        ViMotion::TextObject { inner, object } => {
+            return match object {
+                TextObject::Word { big } => word_object(text, pos, big, inner, count),
+                TextObject::Quote(quote) => quote_object(text, pos, quote, inner),
+                TextObject::Delimiter(delimiter) => delimiter_object(text, pos, delimiter, inner),
+                TextObject::Bracket(..) => None,
+            };
+        }
+        ViMotion::Jump(..) | ViMotion::RepeatJump { .. } | ViMotion::MatchingBracket => {
+            return None;
+        }
+    };
+    Some(range).filter(|range| !range.is_empty())
+}
+
+/// Return the range of the "inner word" or "a word" text object at `pos`, with `count` words.
+fn word_object(
+    text: &[char],
+    pos: usize,
+    big: bool,
+    inner: bool,
+    count: usize,
+) -> Option<Range<usize>> {
+    if pos >= text.len() || text[pos] == '\n' {
+        return None;
+    }
+    let class = |c: char| char_class(c, big);
+    // The end of the run of characters of the same class that starts at `i`, within the line.
+    let run_end = |i: usize| {
+        i + text[i..]
+            .iter()
+            .take_while(|&&c| c != '\n' && class(c) == class(text[i]))
+            .count()
+    };
+    let mut start = pos;
+    while start > 0 && text[start - 1] != '\n' && class(text[start - 1]) == class(text[pos]) {
+        start -= 1;
+    }
+    let mut end = start;
+    for _ in 0..count {
+        if end >= text.len() || text[end] == '\n' {
+            break;
+        }
+        let blank = is_blank(text[end]);
+        end = run_end(end);
+        // "A word" also takes the whitespace after the word, or the word after the whitespace.
+        if !inner && end < text.len() && text[end] != '\n' && (blank || is_blank(text[end])) {
+            end = run_end(end);
+        }
+    }
+    // Without whitespace after the word, "a word" takes the whitespace before it instead.
+    if !inner && !is_blank(text[start]) && !is_blank(text[end - 1]) {
+        while start > 0 && is_blank(text[start - 1]) {
+            start -= 1;
+        }
+    }
+    Some(start..end)
+}
+
+/// Return the class of `c` for word text objects: whitespace, word characters, or punctuation.
+/// Big words consist of everything but whitespace.
+fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() {
+        1
+    } else {
+        2
+    }
+}
+
+/// Return the range of the quote text object at `pos`: the quoted string on the line that contains
+/// the cursor, or else the first one after the cursor.
+fn quote_object(text: &[char], pos: usize, quote: char, inner: bool) -> Option<Range<usize>> {
+    let line = line_range(text, pos);
+    let mut quotes = vec![];
+    let mut escaped = false;
+    for i in line.clone() {
+        if escaped {
+            escaped = false;
+        } else if text[i] == '\\' {
+            escaped = true;
+        } else if text[i] == quote {
+            quotes.push(i);
+        }
+    }
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_open, close)| pos <= close)?;
+    if inner {
+        return Some(open + 1..close);
+    }
+    // "Around" takes the whitespace after the string, or else the whitespace before it.
+    let mut start = open;
+    let mut end = close + 1;
+    while end < line.end && is_blank(text[end]) {
+        end += 1;
+    }
+    if end == close + 1 {
+        while start > line.start && is_blank(text[start - 1]) {
+            start -= 1;
+        }
+    }
+    Some(start..end)
+}
+
+/// Return the range of the text object for any other character, like `i|`: the text between its
+/// last occurrence before the cursor and its next one, on the same line.
+fn delimiter_object(
+    text: &[char],
+    pos: usize,
+    delimiter: char,
+    inner: bool,
+) -> Option<Range<usize>> {
+    let line = line_range(text, pos);
+    let open = line.start
+        + text[line.start..pos]
+            .iter()
+            .rposition(|&c| c == delimiter)?;
+    let close = pos + text[pos..line.end].iter().position(|&c| c == delimiter)?;
+    Some(if inner {
+        open + 1..close
+    } else {
+        open..close + 1
+    })
+}
+
+/// Return the position of the `count`th opening bracket that encloses `pos`.
+/// If the cursor is on a bracket, its pair is the innermost one.
+pub fn enclosing_bracket(
+    text: &wstr,
+    pos: usize,
+    open: char,
+    close: char,
+    count: usize,
+) -> Option<usize> {
+    let text = text.as_char_slice();
+    if text.is_empty() {
+        return None;
+    }
+    let mut depth = 0;
+    let mut remaining = count;
+    for i in (0..=pos.min(text.len() - 1)).rev() {
+        if text[i] == close && i != pos {
+            depth += 1;
+        } else if text[i] == open {
+            if depth == 0 {
+                remaining -= 1;
+                if remaining == 0 {
+                    return Some(i);
+                }
+            } else {
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
@@ -8,7 +8,9 @@
 //! of text around to handle text insertion.
 
 use crate::editable_line::line_at_cursor;
-use crate::input_common::{CURSOR_UP_SUPPORTED, SCROLL_FORWARD_SUPPORTED};
+use crate::input_common::{
+    CURSOR_UP_SUPPORTED, SCROLL_FORWARD_SUPPORTED, SYNCHRONIZED_OUTPUT_SUPPORTED,
+};
 use crate::key::ViewportPosition;
 use crate::pager::{PageRendering, Pager, PAGER_MIN_HEIGHT};
 use crate::FLOG;
@@ -34,7 +36,7 @@
 #[allow(unused_imports)]
 use crate::future::IsSomeAnd;
 use crate::global_safety::RelaxedAtomicBool;
-use crate::highlight::{HighlightColorResolver, HighlightSpec};
+use crate::highlight::{HighlightColorResolver, HighlightRole, HighlightSpec};
 use crate::output::Outputter;
 use crate::terminal::{term, tparm1};
 use crate::termsize::{termsize_last, Termsize};
@@ -66,6 +68,9 @@ pub struct Line {
     pub text: Vec<HighlightedChar>,
     pub is_soft_wrapped: bool,
     pub indentation: usize,
+    /// Whether this line follows a hard line break in the command line, so it is shown after a
+    /// continuation prompt.
+    pub is_continuation: bool,
 }
 
 impl Line {
@@ -271,8 +276,10 @@ pub fn scrolled(&self) -> bool {
     /// \param commandline the command line
     /// \param explicit_len the number of characters of the "explicit" (non-autosuggestion) portion
     /// of the command line \param colors the colors to use for the commanad line \param indent the
-    /// indent to use for the command line \param cursor_pos where the cursor is \param pager the
-    /// pager to render below the command line \param page_rendering to cache the current pager view
+    /// indent to use for the command line \param cursor_pos where the cursor is \param hint a
+    /// message to show on the line below the command line, unless it is empty or this is the final
+    /// rendering \param pager the pager to render below the command line and hint \param
+    /// page_rendering to cache the current pager view
     pub fn write(
         &mut self,
         left_prompt: &wstr,
@@ -283,6 +290,7 @@ pub fn write(
         mut indent: Vec<i32>,
         cursor_pos: usize,
         pager_search_field_position: Option<usize>,
+        hint: &wstr,
         vars: &dyn Environment,
         pager: &mut Pager,
         page_rendering: &mut PageRendering,
@@ -457,7 +465,7 @@ struct ScrolledCursor {
             self.desired.add_line();
         }
 
-        let full_line_count = self.desired.cursor.y
+        let mut full_line_count = self.desired.cursor.y
             - if self.desired.cursor.x == 0
                 && self
                     .desired
@@ -471,6 +479,30 @@ struct ScrolledCursor {
                 0
             }
             + calc_prompt_lines(&layout.left_prompt);
+
+        // Show the hint on its own line, if the command line leaves room for it.
+        if !hint.is_empty()
+            && !is_final_rendering
+            && scrolled_cursor.scroll_amount == 0
+            && full_line_count + 1 < screen_height
+        {
+            let color = HighlightSpec::with_fg(HighlightRole::error);
+            let line = self.desired.add_line();
+            // Stay clear of the last column, so the line does not wrap.
+            let max_width = screen_width - 1;
+            let fits = hint.chars().map(wcwidth_rendered_min_0).sum::<usize>() <= max_width;
+            let mut width = 0;
+            for c in hint.chars() {
+                width += wcwidth_rendered_min_0(c);
+                if !fits && width >= max_width {
+                    line.append(get_ellipsis_char(), color, CharOffset::None);
+                    break;
+                }
+                line.append(c, color, CharOffset::None);
+            }
+            full_line_count += 1;
+        }
+
         let pager_available_height = std::cmp::max(
             1,
             curr_termsize
@@ -547,6 +579,12 @@ pub fn reset_line(&mut self, repaint_prompt: bool /* = false */) {
                 .map_or(1, |p| calc_prompt_lines(p));
             self.actual.cursor.y += prompt_line_count.checked_sub(1).unwrap();
             self.actual_left_prompt = None;
+            // If the new prompt has fewer lines, everything moves up, so the old prompt lines
+            // count as lines to clear.
+            self.actual_lines_before_reset = std::cmp::max(
+                self.actual_lines_before_reset,
+                self.actual.line_count() + prompt_line_count - 1,
+            );
         }
         self.actual.resize(0);
         self.need_clear_lines = true;
@@ -797,6 +835,7 @@ fn desired_append_char(
             let indentation = prompt_width + indent * INDENT_STEP;
             let line = self.desired.line_mut(line_no);
             line.indentation = indentation;
+            line.is_continuation = true;
             for _ in 0..indentation {
                 if !self.desired_append_char(
                     offset_in_cmdline,
@@ -1058,12 +1097,24 @@ fn update(
         let mut set_color = |zelf: &mut Self, c| {
             let fg = color_resolver.resolve_spec(&c, false, vars);
             let bg = color_resolver.resolve_spec(&c, true, vars);
-            zelf.outp.borrow_mut().set_color(fg, bg);
+            let underline_color = color_resolver.resolve_underline_color(&c, vars);
+            let mut outp = zelf.outp.borrow_mut();
+            outp.set_color(fg, bg);
+            outp.set_underline_color(underline_color);
         };
 
         let mut cached_layouts = LAYOUT_CACHE_SHARED.lock().unwrap();
         let mut zelf = self.scoped_buffer();
 
+        // If the terminal supports synchronized output, have it show the repaint all at once
+        // instead of drawing each of the steps below.
+        let synchronized_update_start = SYNCHRONIZED_OUTPUT_SUPPORTED.load().then(|| {
+            let mut outp = zelf.outp.borrow_mut();
+            let start = outp.contents().len();
+            let _ = outp.write_all(b"\x1b[?2026h"); // begin synchronized update
+            start
+        });
+
         // Determine size of left and right prompt. Note these have already been truncated.
         let left_prompt_layout = cached_layouts.calc_prompt_layout(left_prompt, None, usize::MAX);
         let left_prompt_width = left_prompt_layout.last_line_width;
@@ -1145,6 +1196,7 @@ fn update(
                 start = left_prompt_layout.line_breaks.last().map_or(0, |lb| lb + 1);
             }
             zelf.write_str(&left_prompt[start..]);
+            zelf.write_bytes(b"\x1b]133;B\x07");
             zelf.actual_left_prompt = Some(left_prompt.to_owned());
             zelf.actual.cursor.x = left_prompt_width;
             if Some(left_prompt_width) == screen_width && zelf.should_wrap(0) {
@@ -1163,6 +1215,15 @@ fn s_line(zelf: &Screen, i: usize) -> &Line {
         for i in 0..zelf.desired.line_count() {
             zelf.actual.create_line(i);
             let is_first_line = i == 0 && !zelf.scrolled();
+
+            // Mark the start of each continuation line as a secondary prompt, unless the terminal
+            // already has it marked from the last repaint.
+            if o_line(&zelf, i).is_continuation
+                && (zelf.scrolled() || !s_line(&zelf, i).is_continuation)
+            {
+                zelf.r#move(0, i);
+                zelf.write_bytes(b"\x1b]133;A;k=s\x07\x1b]133;B\x07");
+            }
             let start_pos = if is_first_line { left_prompt_width } else { 0 };
             let mut current_width = 0;
             let mut has_cleared_line = false;
@@ -1309,7 +1370,9 @@ fn s_line(zelf: &Screen, i: usize) -> &Line {
                 zelf.r#move(0, 0);
                 zelf.r#move(screen_width.unwrap() - right_prompt_width, i);
                 set_color(&mut zelf, HighlightSpec::new());
+                zelf.write_bytes(b"\x1b]133;P;k=r\x07");
                 zelf.write_str(right_prompt);
+                zelf.write_bytes(b"\x1b]133;B\x07");
                 zelf.actual.cursor.x += right_prompt_width;
 
                 // We output in the last column. Some terms (Linux) push the cursor further right, past
@@ -1356,6 +1419,16 @@ fn s_line(zelf: &Screen, i: usize) -> &Line {
         // assignment!
         zelf.actual = zelf.desired.clone();
         zelf.last_right_prompt_width = right_prompt_width;
+
+        if let Some(start) = synchronized_update_start {
+            let mut outp = zelf.outp.borrow_mut();
+            if outp.contents().len() == start + b"\x1b[?2026h".len() {
+                // Nothing changed, so don't bother the terminal.
+                outp.truncate_contents(start);
+            } else {
+                let _ = outp.write_all(b"\x1b[?2026l"); // end synchronized update
+            }
+        }
     }
 }
 
@@ -0,0 +1,143 @@
+//! Suggestions for misspelled command names.
+//!
+//! When a command cannot be found, the names of the builtins, functions and executables in $PATH
+//! that are within a small edit distance of it are offered instead, so `gti` suggests `git` and
+//! `sl` suggests `ls`. These are used by `type --suggest`, and so by the default
+//! `fish_command_not_found` handler, and by the `correct-command` input function.
+
+use crate::builtins::shared::builtin_get_names;
+use crate::env::Environment;
+use crate::function;
+use crate::path::{path_get_executables, DEFAULT_PATH};
+use crate::wchar::prelude::*;
+use crate::wutil::wstat;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The most suggestions to offer for one command.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the Damerau-Levenshtein distance between `a` and `b`, in its optimal string alignment
+/// variant: the number of insertions, deletions, substitutions and transpositions of adjacent
+/// characters needed to turn one into the other, without editing any substring twice.
+pub fn edit_distance(a: &wstr, b: &wstr) -> usize {
+    let a = a.as_char_slice();
+    let b = b.as_char_slice();
+    // Only the last two rows of the distance matrix are needed to compute the next one.
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Returns the largest edit distance at which a name is taken as a misspelling of one with `len`
+/// characters. Short names are close to too many others to allow more than one edit.
+fn max_distance(len: usize) -> usize {
+    match len {
+        0..=1 => 0,
+        2..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// The executables found in one directory of $PATH, with the modification time of the directory
+/// when it was read.
+struct PathDirectory {
+    mtime: Option<SystemTime>,
+    names: Vec<WString>,
+}
+
+/// The executables in each directory of $PATH that has been looked at, so they are only listed
+/// again after the directory changes.
+static PATH_INDEX: Lazy<Mutex<HashMap<WString, PathDirectory>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the names of all commands: builtins, functions (including autoloadable ones) and
+/// executables in $PATH.
+pub fn command_names(vars: &dyn Environment) -> HashSet<WString> {
+    let mut names: HashSet<WString> = builtin_get_names().map(|name| name.to_owned()).collect();
+    names.extend(function::get_names(false, vars));
+
+    let path_var = vars.get(L!("PATH"));
+    let dirs = match &path_var {
+        Some(var) => var.as_list(),
+        None => &DEFAULT_PATH[..],
+    };
+    let mut index = PATH_INDEX.lock().unwrap();
+    for dir in dirs {
+        if dir.is_empty() {
+            continue;
+        }
+        let mtime = wstat(dir).and_then(|md| md.modified()).ok();
+        let entry = index.entry(dir.clone()).or_insert_with(|| PathDirectory {
+            mtime: None,
+            names: vec![],
+        });
+        if entry.mtime.is_none() || entry.mtime != mtime {
+            entry.mtime = mtime;
+            entry.names = path_get_executables(dir);
+        }
+        names.extend(entry.names.iter().cloned());
+    }
+    names
+}
+
+/// Returns whether `a` and `b` consist of the same characters, which makes a transposition the
+/// likely typo.
+fn same_characters(a: &wstr, b: &wstr) -> bool {
+    let mut a = a.as_char_slice().to_vec();
+    let mut b = b.as_char_slice().to_vec();
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
+
+/// Returns the commands whose names are close enough to `cmd` to be what was meant, best first.
+/// Nothing is suggested for a path or for a name that is a command.
+pub fn command_suggestions(cmd: &wstr, vars: &dyn Environment) -> Vec<WString> {
+    let max = max_distance(cmd.len());
+    if max == 0 || cmd.contains('/') {
+        return vec![];
+    }
+    let names = command_names(vars);
+    if names.contains(cmd) {
+        return vec![];
+    }
+    let mut candidates: Vec<_> = names
+        .into_iter()
+        .filter(|name| name.len().abs_diff(cmd.len()) <= max)
+        .filter_map(|name| {
+            let distance = edit_distance(cmd, &name);
+            (distance <= max).then_some((distance, name))
+        })
+        .collect();
+    candidates.sort_by(|(d1, n1), (d2, n2)| {
+        d1.cmp(d2)
+            .then_with(|| same_characters(cmd, n2).cmp(&same_characters(cmd, n1)))
+            .then_with(|| {
+                n1.len()
+                    .abs_diff(cmd.len())
+                    .cmp(&n2.len().abs_diff(cmd.len()))
+            })
+            .then_with(|| n1.cmp(n2))
+    });
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_distance, name)| name)
+        .collect()
+}
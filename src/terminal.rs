@@ -45,6 +45,11 @@ pub struct Term {
     pub enter_dim_mode: Option<CString>,
     pub enter_underline_mode: Option<CString>,
     pub exit_underline_mode: Option<CString>,
+    // Extended capabilities for styled and colored underlines and strikethrough, as used by tmux.
+    pub set_underline_style: Option<CString>,
+    pub set_underline_color: Option<CString>,
+    pub enter_strikethrough_mode: Option<CString>,
+    pub exit_strikethrough_mode: Option<CString>,
     pub enter_reverse_mode: Option<CString>,
     pub enter_standout_mode: Option<CString>,
     pub exit_standout_mode: Option<CString>,
@@ -194,6 +199,10 @@ fn new(db: terminfo::Database) -> Self {
             enter_dim_mode: get_str_cap(&db, "mh"),
             enter_underline_mode: get_str_cap(&db, "us"),
             exit_underline_mode: get_str_cap(&db, "ue"),
+            set_underline_style: get_str_cap(&db, "Smulx"),
+            set_underline_color: get_str_cap(&db, "Setulc"),
+            enter_strikethrough_mode: get_str_cap(&db, "smxx"),
+            exit_strikethrough_mode: get_str_cap(&db, "rmxx"),
             enter_reverse_mode: get_str_cap(&db, "mr"),
             enter_standout_mode: get_str_cap(&db, "so"),
             exit_standout_mode: get_str_cap(&db, "se"),
@@ -402,6 +411,8 @@ pub fn setup_fallback_term() -> Arc<Term> {
         enter_dim_mode: Some(CString::new("\x1b[2m").unwrap()),
         enter_underline_mode: Some(CString::new("\x1b[4m").unwrap()),
         exit_underline_mode: Some(CString::new("\x1b[24m").unwrap()),
+        enter_strikethrough_mode: Some(CString::new("\x1b[9m").unwrap()),
+        exit_strikethrough_mode: Some(CString::new("\x1b[29m").unwrap()),
         enter_reverse_mode: Some(CString::new("\x1b[7m").unwrap()),
         enter_standout_mode: Some(CString::new("\x1b[7m").unwrap()),
         exit_standout_mode: Some(CString::new("\x1b[27m").unwrap()),
@@ -0,0 +1,39 @@
+use crate::clipboard::{
+    base64_decode, base64_encode, osc52_copy_sequence, parse_osc52_response, CLIPBOARD,
+};
+use crate::wchar::prelude::*;
+
+#[test]
+fn test_base64() {
+    let cases: &[(&[u8], &str)] = &[
+        (b"", ""),
+        (b"f", "Zg=="),
+        (b"fo", "Zm8="),
+        (b"foo", "Zm9v"),
+        (b"foob", "Zm9vYg=="),
+        (b"fooba", "Zm9vYmE="),
+        (b"foobar", "Zm9vYmFy"),
+        (b"\xff\xfe\x00", "//4A"),
+    ];
+    for &(data, encoded) in cases {
+        assert_eq!(base64_encode(data), encoded);
+        assert_eq!(base64_decode(encoded.as_bytes()).as_deref(), Some(data));
+    }
+    // Padding is optional.
+    assert_eq!(base64_decode(b"Zm8").as_deref(), Some(&b"fo"[..]));
+    assert_eq!(base64_decode(b"Zm9v!"), None);
+}
+
+#[test]
+fn test_osc52() {
+    assert_eq!(
+        osc52_copy_sequence(CLIPBOARD, L!("fish")),
+        b"\x1b]52;c;ZmlzaA==\x07"
+    );
+    assert_eq!(
+        parse_osc52_response(b"52;c;ZmlzaA=="),
+        Some(L!("fish").to_owned())
+    );
+    assert_eq!(parse_osc52_response(b"52;;"), Some(WString::new()));
+    assert_eq!(parse_osc52_response(b"11;rgb:0000/0000/0000"), None);
+}
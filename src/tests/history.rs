@@ -1,7 +1,8 @@
 use crate::common::{is_windows_subsystem_for_linux, str2wcstring, wcs2osstring, wcs2string, WSL};
 use crate::env::{EnvMode, EnvStack};
 use crate::history::{
-    self, History, HistoryItem, HistorySearch, PathList, SearchDirection, VACUUM_FREQUENCY,
+    self, History, HistoryItem, HistorySearch, MalformedRecord, PathList, SearchDirection,
+    VACUUM_FREQUENCY,
 };
 use crate::path::path_get_data;
 use crate::tests::prelude::*;
@@ -76,7 +77,7 @@ macro_rules! test_history_matches {
     let history = History::with_name(L!("test_history"));
     history.clear();
     for s in items {
-        history.add_commandline(s.to_owned());
+        history.add_commandline(s.to_owned(), history::PersistenceMode::Disk);
     }
 
     // Helper to set expected items to those matching a predicate, in reverse order.
@@ -147,6 +148,34 @@ macro_rules! test_history_matches {
     let expected = set_expected(|s| s.to_lowercase() == "alph");
     test_history_matches!(searcher, expected);
 
+    // Items matching a regex, case-sensitive.
+    let mut searcher = HistorySearch::new_with_type(
+        history.clone(),
+        L!("^(Beta|alph)$").to_owned(),
+        history::SearchType::Regex,
+    );
+    let expected = set_expected(|s| s == "Beta" || s == "alph");
+    test_history_matches!(searcher, expected);
+
+    // Items matching a regex, case-insensitive. The pattern must not be lowercased.
+    let mut searcher = HistorySearch::new_with(
+        history.clone(),
+        L!("^\\w(ETA|LPH)$").to_owned(),
+        history::SearchType::Regex,
+        nocase,
+        0,
+    );
+    let expected = set_expected(|s| s.to_lowercase() == "beta" || s.to_lowercase() == "alph");
+    test_history_matches!(searcher, expected);
+
+    // Invalid regexes match nothing.
+    let mut searcher = HistorySearch::new_with_type(
+        history.clone(),
+        L!("(").to_owned(),
+        history::SearchType::Regex,
+    );
+    test_history_matches!(searcher, vec![]);
+
     // Test item removal case-sensitive.
     let mut searcher = HistorySearch::new(history.clone(), L!("Alpha").to_owned());
     test_history_matches!(searcher, vec![L!("Alpha")]);
@@ -178,13 +207,17 @@ macro_rules! test_history_matches {
         let mut item =
             HistoryItem::new(value, SystemTime::now(), 0, history::PersistenceMode::Disk);
         item.set_required_paths(paths);
+        if i % 2 == 0 {
+            item.set_command_result(i % 4, Duration::from_millis(u64::try_from(i).unwrap()));
+            item.set_run_context(Some(sprintf!("/some/dir %d\n\\", i)), Some(rng.gen()));
+        }
         before.push_back(item.clone());
         history.add(item, false);
     }
     history.save();
 
     // Empty items should just be dropped (#6032).
-    history.add_commandline(L!("").into());
+    history.add_commandline(L!("").into(), history::PersistenceMode::Disk);
     assert!(!history.item_at_index(1).unwrap().is_empty());
 
     // Read items back in reverse order and ensure they're the same.
@@ -198,10 +231,14 @@ macro_rules! test_history_matches {
         assert_eq!(bef.str(), aft.str());
         assert_eq!(bef.timestamp(), aft.timestamp());
         assert_eq!(bef.get_required_paths(), aft.get_required_paths());
+        assert_eq!(bef.exit_status(), aft.exit_status());
+        assert_eq!(bef.duration(), aft.duration());
+        assert_eq!(bef.cwd(), aft.cwd());
+        assert_eq!(bef.session_id(), aft.session_id());
     }
 
     // Items should be explicitly added to the history.
-    history.add_commandline(L!("test-command").into());
+    history.add_commandline(L!("test-command").into(), history::PersistenceMode::Disk);
     assert!(history_contains(&history, L!("test-command")));
 
     // Clean up after our tests.
@@ -237,7 +274,7 @@ fn pound_on_history(item_count: usize, idx: usize) -> Arc<History> {
     let hist = History::new(L!("race_test"));
     let hist_lines = generate_history_lines(item_count, idx);
     for line in hist_lines {
-        hist.add_commandline(line);
+        hist.add_commandline(line, history::PersistenceMode::Disk);
         hist.save();
     }
     hist
@@ -352,7 +389,7 @@ fn test_history_external_rewrites() {
     // Write some history to disk.
     {
         let hist = pound_on_history(VACUUM_FREQUENCY / 2, 0);
-        hist.add_commandline("needle".into());
+        hist.add_commandline("needle".into(), history::PersistenceMode::Disk);
         hist.save();
     }
     std::thread::sleep(Duration::from_secs(1));
@@ -400,7 +437,7 @@ fn test_history_merge() {
 
     // Add a different item to each.
     for i in 0..COUNT {
-        hists[i].add_commandline(texts[i].to_owned());
+        hists[i].add_commandline(texts[i].to_owned(), history::PersistenceMode::Disk);
     }
 
     // Save them.
@@ -439,7 +476,7 @@ fn test_history_merge() {
 
     // Add some more per-history items.
     for i in 0..COUNT {
-        hists[i].add_commandline(alt_texts[i].to_owned());
+        hists[i].add_commandline(alt_texts[i].to_owned(), history::PersistenceMode::Disk);
     }
     // Everybody should have old items, but only one history should have each new item.
     #[allow(clippy::needless_range_loop)]
@@ -471,7 +508,7 @@ fn test_history_merge() {
         if i > 0 {
             time_barrier();
         }
-        writer.add_commandline(more_texts[i].to_owned());
+        writer.add_commandline(more_texts[i].to_owned(), history::PersistenceMode::Disk);
         writer.incorporate_external_changes();
         reader.incorporate_external_changes();
         for text in more_texts.iter().take(i) {
@@ -588,6 +625,198 @@ fn test_history_path_detection() {
     history.clear();
 }
 
+#[test]
+#[serial]
+fn test_history_command_result() {
+    let _cleanup = test_init();
+    let name = L!("command_result");
+    let test_vars = EnvStack::new();
+    let history = History::with_name(name);
+    history.clear();
+
+    // The item is held back until its result is known, and then written once.
+    history.clone().add_pending_with_file_detection(
+        L!("make -j8"),
+        &test_vars,
+        history::PersistenceMode::Disk,
+    );
+    time_barrier();
+    assert!(History::new(name).item_at_index(1).is_none());
+
+    history.record_command_result(2, Duration::from_millis(1500));
+    history.resolve_pending();
+    let finished = History::new(name);
+    let item = finished.item_at_index(1).unwrap();
+    assert_eq!(item.str(), "make -j8");
+    assert_eq!(item.exit_status(), Some(2));
+    assert_eq!(item.duration(), Some(Duration::from_millis(1500)));
+
+    let path = path_get_data().expect("Failed to get data directory");
+    let contents =
+        std::fs::read_to_string(wcs2osstring(&(path + L!("/") + name + L!("_history")))).unwrap();
+    assert_eq!(contents.matches("- cmd: ").count(), 1);
+
+    history.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_closest_match() {
+    let _cleanup = test_init();
+    let history = History::with_name(L!("closest_match_test"));
+    history.clear();
+
+    // Oldest first.
+    let items = [
+        ("make test", Some("/src/project")),
+        ("make install", Some("/src")),
+        ("make check", Some("/src/project/sub")),
+        ("make clean", Some("/elsewhere")),
+        ("make all", None),
+    ];
+    let epoch = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    for (i, (cmd, cwd)) in items.into_iter().enumerate() {
+        let when = epoch + Duration::from_secs(u64::try_from(i).unwrap());
+        let mut item = HistoryItem::new(cmd.into(), when, 0, history::PersistenceMode::Disk);
+        item.set_run_context(cwd.map(WString::from), None);
+        history.add(item, false);
+    }
+
+    let closest = |dir: &wstr, accept: &dyn Fn(&HistoryItem) -> bool| {
+        let mut search = HistorySearch::new_with_type(
+            history.clone(),
+            L!("make").to_owned(),
+            history::SearchType::Prefix,
+        );
+        search
+            .go_to_closest_match(dir, accept, || false)
+            .then(|| search.current_string().to_owned())
+    };
+    let any = |_: &HistoryItem| true;
+
+    // The directory itself wins over parents and more recent commands.
+    assert_eq!(closest(L!("/src/project"), &any).unwrap(), "make test");
+    assert_eq!(closest(L!("/src/project/"), &any).unwrap(), "make test");
+    // Otherwise the closest parent wins.
+    assert_eq!(
+        closest(L!("/src/project/sub/deeper"), &any).unwrap(),
+        "make check"
+    );
+    assert_eq!(closest(L!("/src/other"), &any).unwrap(), "make install");
+    // Without any related directory, recency decides.
+    assert_eq!(closest(L!("/unrelated"), &any).unwrap(), "make all");
+    // Rejected items are skipped.
+    let no_test = |item: &HistoryItem| item.str() != "make test";
+    assert_eq!(
+        closest(L!("/src/project"), &no_test).unwrap(),
+        "make install"
+    );
+    assert_eq!(closest(L!("/src/project"), &|_| false), None);
+
+    // The most recent match can be found in the same pass.
+    let closest_and_most_recent = |dir: &wstr, accept: &dyn Fn(&HistoryItem) -> bool| {
+        let mut search = HistorySearch::new_with_type(
+            history.clone(),
+            L!("make").to_owned(),
+            history::SearchType::Prefix,
+        );
+        let (found, most_recent) =
+            search.go_to_closest_match_and_find_most_recent(dir, accept, || false);
+        (
+            found.then(|| search.current_string().to_owned()),
+            most_recent.map(|item| item.str().to_owned()),
+        )
+    };
+    assert_eq!(
+        closest_and_most_recent(L!("/src/project"), &any),
+        (
+            Some(L!("make test").to_owned()),
+            Some(L!("make all").to_owned())
+        )
+    );
+    assert_eq!(
+        closest_and_most_recent(L!("/src/project"), &no_test),
+        (
+            Some(L!("make install").to_owned()),
+            Some(L!("make all").to_owned())
+        )
+    );
+
+    // Only recent matches are considered, so a buried one from the directory loses.
+    for i in 0..history::CLOSEST_MATCH_CANDIDATES {
+        let when = epoch + Duration::from_secs(u64::try_from(items.len() + i).unwrap());
+        let item = HistoryItem::new(
+            sprintf!("make %d", i),
+            when,
+            0,
+            history::PersistenceMode::Disk,
+        );
+        history.add(item, false);
+    }
+    let newest = sprintf!("make %d", history::CLOSEST_MATCH_CANDIDATES - 1);
+    assert_eq!(closest(L!("/src/project"), &any).unwrap(), newest);
+    // Rejected matches count too, so they do not make it look further.
+    let only_test = |item: &HistoryItem| item.str() == "make test";
+    assert_eq!(closest(L!("/src/project"), &only_test), None);
+    assert_eq!(
+        closest_and_most_recent(L!("/src/project"), &only_test),
+        (None, None)
+    );
+    // Only matches that could be closer are checked.
+    let checked = std::cell::Cell::new(0);
+    let count = |_: &HistoryItem| {
+        checked.set(checked.get() + 1);
+        true
+    };
+    assert_eq!(closest(L!("/src/project"), &count).unwrap(), newest);
+    assert_eq!(checked.get(), 1);
+
+    history.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_retention() {
+    let _cleanup = test_init();
+    let name = L!("retention_test");
+    let hist = History::new(name);
+    hist.clear();
+
+    // Oldest first.
+    let now = SystemTime::now();
+    let items = [
+        ("ancient", Duration::from_secs(100 * 24 * 60 * 60)),
+        ("a", Duration::from_secs(50)),
+        ("b", Duration::from_secs(40)),
+        ("a", Duration::from_secs(30)),
+        ("c", Duration::from_secs(20)),
+        ("d", Duration::from_secs(10)),
+    ];
+    for (cmd, age) in items {
+        let item = HistoryItem::new(cmd.into(), now - age, 0, history::PersistenceMode::Disk);
+        hist.add(item, false);
+    }
+
+    // Removing an item forces the file to be rewritten.
+    let rewrite = || {
+        hist.remove(L!("unused"));
+        hist.save();
+        History::new(name).get_history()
+    };
+
+    let vars = EnvStack::new();
+    vars.set_one(L!("fish_history_max_age"), EnvMode::GLOBAL, "30d".into());
+    history::update_history_retention(&vars);
+    assert_eq!(rewrite(), ["d", "c", "a", "b"]);
+
+    vars.set_one(L!("fish_history_max_items"), EnvMode::GLOBAL, "2".into());
+    history::update_history_retention(&vars);
+    assert_eq!(rewrite(), ["d", "c"]);
+
+    history::set_history_retention(history::HistoryRetention::default());
+    hist.clear();
+}
+
 fn install_sample_history(name: &wstr) {
     let path = path_get_data().expect("Failed to get data directory");
     std::fs::copy(
@@ -650,3 +879,385 @@ fn test_history_formats() {
     assert_eq!(test_history_imported_from_corrupted.get_history(), expected);
     test_history_imported_from_corrupted.clear();
 }
+
+#[test]
+#[serial]
+fn test_history_check_and_repair() {
+    let _cleanup = test_init();
+    let name = L!("history_sample_corrupt1");
+    install_sample_history(name);
+    let hist = History::new(name);
+    assert_eq!(
+        hist.check().unwrap(),
+        [
+            (26, MalformedRecord::MissingCommand),
+            (151, MalformedRecord::RepeatedPrefix),
+            (311, MalformedRecord::Truncated),
+        ]
+    );
+
+    let backup = hist.repair().unwrap().unwrap();
+    assert_eq!(hist.check().unwrap(), []);
+    assert_eq!(
+        History::new(name).get_history(),
+        [
+            "no_newline_at_end_of_file",
+            "corrupt_prefix",
+            "this_command_is_ok"
+        ]
+    );
+
+    // The backup is the original file.
+    let original =
+        std::fs::read(env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/history_sample_corrupt1")
+            .unwrap();
+    assert_eq!(std::fs::read(wcs2osstring(&backup)).unwrap(), original);
+
+    // Repairing a clean file does nothing, and keeps the backup of the damaged one.
+    assert_eq!(hist.repair().unwrap(), None);
+    assert_eq!(std::fs::read(wcs2osstring(&backup)).unwrap(), original);
+    std::fs::remove_file(wcs2osstring(&backup)).unwrap();
+    hist.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_incorporate_file_changes() {
+    let _cleanup = test_init();
+    // Two histories with the same name stand in for two shells.
+    let name = L!("share_test");
+    let ours = History::new(name);
+    ours.clear();
+    let theirs = History::new(name);
+    time_barrier();
+
+    let secret = HistoryItem::new(
+        "secret".into(),
+        SystemTime::now(),
+        0,
+        history::PersistenceMode::Memory,
+    );
+    ours.add(secret, false);
+    ours.add_commandline("ours".into(), history::PersistenceMode::Disk);
+    theirs.add_commandline("theirs".into(), history::PersistenceMode::Disk);
+    assert!(!history_contains(&ours, L!("theirs")));
+    assert!(ours.incorporate_file_changes());
+    assert!(history_contains(&ours, L!("theirs")));
+    assert!(history_contains(&ours, L!("ours")));
+    assert!(history_contains(&ours, L!("secret")));
+    assert!(!ours.incorporate_file_changes());
+
+    // Items appended later are read incrementally, and go before our own pending item.
+    let pending = HistoryItem::new(
+        "pending".into(),
+        SystemTime::now(),
+        0,
+        history::PersistenceMode::Disk,
+    );
+    ours.add(pending, true);
+    theirs.add_commandline("theirs again".into(), history::PersistenceMode::Disk);
+    assert!(ours.incorporate_file_changes());
+    assert_eq!(ours.item_at_index(1).unwrap().str(), "theirs again");
+    ours.resolve_pending();
+    assert_eq!(ours.item_at_index(1).unwrap().str(), "pending");
+    assert_eq!(ours.item_at_index(2).unwrap().str(), "theirs again");
+    assert!(!ours.incorporate_file_changes());
+
+    ours.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_incorporate_file_changes_with_results() {
+    let _cleanup = test_init();
+    let name = L!("share_results_test");
+    let theirs = History::new(name);
+    theirs.clear();
+    let vars = EnvStack::new();
+    vars.set_one(L!("PWD"), EnvMode::GLOBAL, L!("/src/project").to_owned());
+    // Run commands like the reader does, so their items carry a result and directory.
+    let run = |cmd: &wstr, exit_status: i32| {
+        theirs
+            .clone()
+            .add_pending_with_file_detection(cmd, &vars, history::PersistenceMode::Disk);
+        theirs.record_command_result(exit_status, Duration::from_millis(20));
+        theirs.resolve_pending();
+    };
+    run(L!("make"), 0);
+    run(L!("make test"), 2);
+    time_barrier();
+
+    // A command that is newer than our boundary is left out at first, and then read once.
+    let ours = History::new(name);
+    time_barrier();
+    run(L!("make install"), 0);
+    assert_eq!(ours.size(), 2);
+    assert!(ours.incorporate_file_changes());
+    assert_eq!(ours.size(), 3);
+    let item = ours.item_at_index(1).unwrap();
+    assert_eq!(item.str(), "make install");
+    assert_eq!(item.cwd(), Some(L!("/src/project")));
+    assert!(!ours.incorporate_file_changes());
+
+    run(L!("make clean"), 1);
+    assert!(ours.incorporate_file_changes());
+    assert_eq!(ours.size(), 4);
+    let item = ours.item_at_index(1).unwrap();
+    assert_eq!(item.str(), "make clean");
+    assert_eq!(item.exit_status(), Some(1));
+    assert_eq!(ours.item_at_index(4).unwrap().str(), "make");
+
+    theirs.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_parse_time() {
+    let _cleanup = test_init();
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let parse = |spec: &str| history::parse_history_time(&WString::from(spec), now);
+    let secs = Duration::from_secs;
+    const HOUR: u64 = 60 * 60;
+    assert_eq!(parse("90s"), Some(now - secs(90)));
+    assert_eq!(parse("2h"), Some(now - secs(2 * HOUR)));
+    assert_eq!(parse("3d"), Some(now - secs(3 * 24 * HOUR)));
+    assert_eq!(parse("@1234"), Some(UNIX_EPOCH + secs(1234)));
+
+    // Dates are in UTC.
+    assert_eq!(parse("1970-01-01"), Some(UNIX_EPOCH));
+    assert_eq!(parse("2023-11-14 22:13:20"), Some(now));
+    let midnight = parse("2024-05-01").unwrap();
+    assert_eq!(midnight, UNIX_EPOCH + secs(1_714_521_600));
+    assert_eq!(
+        parse("2024-05-01 12:30"),
+        Some(midnight + secs(12 * HOUR + 30 * 60))
+    );
+    assert_eq!(parse("2024-02-29"), Some(midnight - secs(62 * 24 * HOUR)));
+
+    for invalid in [
+        "",
+        "3",
+        "2x",
+        "tomorrow",
+        "2024-13-45",
+        "2023-02-29",
+        "2024-5-1",
+        "2024-05-01 24:00",
+        "2024-05-01 12:30 pm",
+        "12:30",
+    ] {
+        assert_eq!(parse(invalid), None, "{invalid}");
+    }
+}
+
+#[test]
+#[serial]
+fn test_history_fuzzy_search() {
+    let _cleanup = test_init();
+    let fuzzy_match = |needle: &str, haystack: &str, case_sensitive| {
+        history::fuzzy_match(
+            &WString::from(needle),
+            &WString::from(haystack),
+            case_sensitive,
+        )
+    };
+    let score = |needle, haystack| fuzzy_match(needle, haystack, false).unwrap().score;
+
+    assert_eq!(
+        fuzzy_match("gco", "git checkout", false).unwrap().positions,
+        [0, 4, 9]
+    );
+    assert_eq!(fuzzy_match("", "ls", false).unwrap().positions, []);
+    assert_eq!(fuzzy_match("sl", "ls", false), None);
+    assert_eq!(fuzzy_match("GIT", "git", true), None);
+    assert_eq!(
+        fuzzy_match("GIT", "git", false).unwrap().positions,
+        [0, 1, 2]
+    );
+    // Word boundaries beat the middle of words.
+    assert!(score("gc", "git commit") > score("gc", "logic"));
+    // Consecutive characters beat scattered ones.
+    assert!(score("make", "xmakex") > score("make", "mxaxkxe"));
+    // The best alignment is found, not just the first.
+    assert_eq!(
+        fuzzy_match("ab", "axb ab", false).unwrap().positions,
+        [4, 5]
+    );
+
+    let history = History::with_name(L!("fuzzy_test"));
+    history.clear();
+    let now = SystemTime::now();
+    let add = |text: &str, age_days: u64| {
+        let when = now - Duration::from_secs(age_days * 24 * 60 * 60);
+        let item = HistoryItem::new(text.into(), when, 0, history::PersistenceMode::Memory);
+        history.add(item, false);
+    };
+    add("git commit", 100);
+    add("git checkout", 20);
+    add("ls", 2);
+    add("git checkout", 2);
+    add("git checkout", 1);
+    add("grep cat", 0);
+
+    // Equally good matches are ordered by frecency.
+    let ranked = history.fuzzy_search(L!("gc"), history::SearchFlags::IGNORE_CASE);
+    let texts: Vec<_> = ranked.iter().map(|r| r.text.as_utfstr()).collect();
+    assert_eq!(
+        texts,
+        [L!("git checkout"), L!("grep cat"), L!("git commit")]
+    );
+    assert_eq!(ranked[0].positions, [0, 4]);
+    // Better matches still come first.
+    let ranked = history.fuzzy_search(L!("gco"), history::SearchFlags::IGNORE_CASE);
+    assert_eq!(ranked[0].text, L!("git commit"));
+
+    history.clear();
+}
+
+#[test]
+#[serial]
+fn test_history_export_and_import() {
+    let _cleanup = test_init();
+    use history::HistoryFormat;
+    let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+    let secs = Duration::from_secs;
+    let details = |item: &HistoryItem| {
+        (
+            item.str().to_owned(),
+            item.timestamp(),
+            item.exit_status(),
+            item.duration(),
+            item.cwd().map(|cwd| cwd.to_owned()),
+            item.session_id(),
+            item.get_required_paths().to_vec(),
+        )
+    };
+
+    // zsh extended history, with a continuation line and a metafied character.
+    let contents =
+        std::fs::read(env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/history_sample_zsh").unwrap();
+    let items = history::parse_history(HistoryFormat::Zsh, &contents, now).unwrap();
+    let summary: Vec<_> = items
+        .iter()
+        .map(|item| (item.str().to_owned(), item.timestamp(), item.duration()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "git status".into(),
+                UNIX_EPOCH + secs(1_700_000_000),
+                Some(secs(3))
+            ),
+            (
+                "echo multi\nline".into(),
+                UNIX_EPOCH + secs(1_700_000_100),
+                Some(secs(0))
+            ),
+            (
+                "echo →".into(),
+                UNIX_EPOCH + secs(1_700_000_200),
+                Some(secs(0))
+            ),
+            ("echo plain".into(), now, None),
+        ]
+    );
+
+    // Exporting and re-importing keeps all details, but skips items not meant for disk.
+    let hist = History::with_name(L!("export_test"));
+    hist.clear();
+    let mut item = HistoryItem::new(
+        "make -C build".into(),
+        UNIX_EPOCH + secs(1_700_000_000),
+        0,
+        history::PersistenceMode::Disk,
+    );
+    item.set_command_result(2, Duration::from_millis(1500));
+    item.set_run_context(Some("/tmp/\"quoted\"".into()), Some(0xfeed));
+    item.set_required_paths(vec!["build".into()]);
+    hist.add(item.clone(), false);
+    let secret = HistoryItem::new("secret".into(), now, 0, history::PersistenceMode::Memory);
+    hist.add(secret, false);
+    let plain = HistoryItem::new(
+        "echo\ttab".into(),
+        UNIX_EPOCH + secs(1_700_000_001),
+        0,
+        history::PersistenceMode::Disk,
+    );
+    hist.add(plain.clone(), false);
+    // Bytes that are not valid UTF-8 survive, and the JSON stays valid.
+    let mut binary = HistoryItem::new(
+        str2wcstring(b"echo \xff\xc3"),
+        UNIX_EPOCH + secs(1_700_000_002),
+        0,
+        history::PersistenceMode::Disk,
+    );
+    binary.set_run_context(Some(str2wcstring(b"/tmp/\xfe")), None);
+    hist.add(binary.clone(), false);
+    let expected = [details(&item), details(&plain), details(&binary)];
+    for format in [HistoryFormat::Fish, HistoryFormat::Json] {
+        let exported = hist.export(format.for_export().unwrap());
+        if format == HistoryFormat::Json {
+            assert!(std::str::from_utf8(&exported).is_ok());
+        }
+        let parsed = history::parse_history(format, &exported, now).unwrap();
+        let parsed: Vec<_> = parsed.iter().map(details).collect();
+        assert_eq!(parsed, expected);
+    }
+    hist.clear();
+
+    // JSON may also be a single array, with escaped characters.
+    let json = r#"[{"cmd": "a", "when": 1}, {"cmd": "\u00e9\ud83d\ude00\n"}]"#;
+    let items = history::parse_history(HistoryFormat::Json, json.as_bytes(), now).unwrap();
+    let summary: Vec<_> = items
+        .iter()
+        .map(|item| (item.str().to_owned(), item.timestamp()))
+        .collect();
+    assert_eq!(
+        summary,
+        [("a".into(), UNIX_EPOCH + secs(1)), ("é😀\n".into(), now),]
+    );
+    // Errors report the offset of the offending entry.
+    let json = b"{\"cmd\": \"a\"}\n{\"when\": 1}";
+    assert_eq!(
+        history::parse_history(HistoryFormat::Json, json, now).err(),
+        Some(13)
+    );
+    // Surrogates must come in proper pairs.
+    for json in [
+        r#"{"cmd": "\ud83d\u0041"}"#,
+        r#"{"cmd": "\ud83d\uffff"}"#,
+        r#"{"cmd": "\ud83dx"}"#,
+        r#"{"cmd": "\ude00"}"#,
+    ] {
+        assert_eq!(
+            history::parse_history(HistoryFormat::Json, json.as_bytes(), now).err(),
+            Some(0),
+            "{json}"
+        );
+    }
+
+    // Imported items are saved and ordered by their timestamps.
+    let items = history::parse_history(HistoryFormat::Zsh, &contents, SystemTime::now()).unwrap();
+    hist.import(items);
+    let expected = ["echo plain", "echo →", "echo multi\nline", "git status"];
+    assert_eq!(hist.get_history(), expected);
+    assert_eq!(History::new(L!("export_test")).get_history(), expected);
+    hist.clear();
+
+    // While a command is running, such as the import itself, the import waits for it.
+    let running = HistoryItem::new(
+        "history import".into(),
+        SystemTime::now(),
+        0,
+        history::PersistenceMode::Disk,
+    );
+    hist.add(running, true);
+    let items = history::parse_history(HistoryFormat::Zsh, &contents, SystemTime::now()).unwrap();
+    hist.import(items);
+    assert!(!history_contains(&hist, L!("git status")));
+    hist.resolve_pending();
+    assert!(history_contains(&hist, L!("git status")));
+    assert!(history_contains(&hist, L!("history import")));
+    hist.clear();
+}
@@ -1,4 +1,5 @@
 mod abbrs;
+mod clipboard;
 mod common;
 mod complete;
 mod debounce;
@@ -16,8 +17,11 @@
 mod parse_util;
 mod parser;
 mod reader;
+mod reader_autopair;
+mod reader_vi;
 mod redirection;
 mod screen;
+mod spelling;
 mod std;
 mod string_escape;
 mod termsize;
@@ -0,0 +1,66 @@
+use crate::reader_autopair::{autopair_deletion, autopair_insertion, AutopairEdit};
+use crate::wchar::prelude::*;
+
+/// Type `c` into `text`, where the cursor is at the `|`, and return what is done instead of
+/// inserting it.
+fn typed(text: &str, c: char) -> Option<AutopairEdit> {
+    let pos = text.chars().position(|c| c == '|').unwrap();
+    let text = WString::from_str(&text.replacen('|', "", 1));
+    autopair_insertion(&text, pos, c)
+}
+
+fn deletes_pair(text: &str) -> bool {
+    let pos = text.chars().position(|c| c == '|').unwrap();
+    let text = WString::from_str(&text.replacen('|', "", 1));
+    autopair_deletion(&text, pos)
+}
+
+#[test]
+fn test_autopair_insertion() {
+    use AutopairEdit::*;
+    assert_eq!(typed("echo |", '('), Some(InsertPair(')')));
+    assert_eq!(typed("echo |", '['), Some(InsertPair(']')));
+    assert_eq!(typed("|", '{'), Some(InsertPair('}')));
+    assert_eq!(typed("echo |", '"'), Some(InsertPair('"')));
+    assert_eq!(typed("echo (|)", '\''), Some(InsertPair('\'')));
+    assert_eq!(typed("echo $foo|", '['), Some(InsertPair(']')));
+    // Not in front of a word.
+    assert_eq!(typed("echo |foo", '('), None);
+    assert_eq!(typed("echo |foo", '"'), None);
+
+    // Closers step over the ones that are already there.
+    assert_eq!(typed("echo (foo|)", ')'), Some(StepOver));
+    assert_eq!(typed("echo (foo|", ')'), None);
+    assert_eq!(typed("echo \"foo|\"", '"'), Some(StepOver));
+    assert_eq!(typed("echo 'foo|' bar", '\''), Some(StepOver));
+
+    // Nothing happens in quotes, except for the closing quote.
+    assert_eq!(typed("echo \"foo |\"", '('), None);
+    assert_eq!(typed("echo 'foo |'", '"'), None);
+    assert_eq!(typed("echo \"$(foo |)\"", '('), Some(InsertPair(')')));
+    assert_eq!(typed("echo \"$(foo)|\"", '"'), Some(StepOver));
+
+    // Nor in comments or after a backslash.
+    assert_eq!(typed("echo # foo |", '('), None);
+    assert_eq!(typed("echo foo#|", '('), Some(InsertPair(')')));
+    assert_eq!(typed("# foo\necho |", '('), Some(InsertPair(')')));
+    assert_eq!(typed("echo \"$(foo)\" # |", '('), None);
+    assert_eq!(typed("echo (foo # bar\n|", '('), Some(InsertPair(')')));
+    assert_eq!(typed("echo \\|", '('), None);
+    assert_eq!(typed("echo \\\\|", '('), Some(InsertPair(')')));
+    assert_eq!(typed("echo \\(foo|)", ')'), Some(StepOver));
+}
+
+#[test]
+fn test_autopair_deletion() {
+    assert!(deletes_pair("echo (|)"));
+    assert!(deletes_pair("echo [|]"));
+    assert!(deletes_pair("echo \"|\""));
+    assert!(deletes_pair("echo '|'"));
+    assert!(!deletes_pair("echo (|"));
+    assert!(!deletes_pair("echo (|x)"));
+    assert!(!deletes_pair("echo \"foo\"|\""));
+    assert!(!deletes_pair("echo \\(|)"));
+    assert!(!deletes_pair("# echo (|)"));
+    assert!(!deletes_pair("echo '(|)'"));
+}
@@ -0,0 +1,154 @@
+use crate::reader::{JumpDirection, JumpPrecision};
+use crate::reader_vi::{
+    enclosing_bracket, motion_range, TextObject, ViCommand, ViMotion, ViMotionParser, ViOperator,
+    ViParseResult,
+};
+use crate::wchar::prelude::*;
+
+fn parse(operator: ViOperator, keys: &str) -> Option<ViCommand> {
+    let mut parser = ViMotionParser::new(operator);
+    let mut chars = keys.chars().peekable();
+    while let Some(c) = chars.next() {
+        match parser.push(c) {
+            ViParseResult::Incomplete => continue,
+            ViParseResult::Complete(cmd) => {
+                assert!(chars.peek().is_none(), "motion {keys:?} ended early");
+                return Some(cmd);
+            }
+            ViParseResult::Invalid => return None,
+        }
+    }
+    panic!("motion {keys:?} is incomplete");
+}
+
+/// Apply `keys` after `operator` to `text`, where the cursor is at the `|`, and return the text
+/// the operator covers.
+fn covered(operator: ViOperator, text: &str, keys: &str) -> Option<String> {
+    let pos = text.chars().position(|c| c == '|').unwrap();
+    let text = WString::from_str(&text.replacen('|', "", 1));
+    let cmd = parse(operator, keys).unwrap();
+    let range = motion_range(&text, pos, operator, cmd)?;
+    Some(text[range].to_string())
+}
+
+#[test]
+fn test_vi_motion_parser() {
+    use ViOperator::*;
+    let cmd = |count, motion| Some(ViCommand { count, motion });
+    assert_eq!(
+        parse(Delete, "w"),
+        cmd(1, ViMotion::WordStart { big: false })
+    );
+    assert_eq!(
+        parse(Delete, "12E"),
+        cmd(12, ViMotion::WordEnd { big: true })
+    );
+    assert_eq!(parse(Delete, "0"), cmd(1, ViMotion::LineStart));
+    assert_eq!(parse(Delete, "d"), cmd(1, ViMotion::Line));
+    assert_eq!(parse(Change, "3c"), cmd(3, ViMotion::Line));
+    assert_eq!(parse(Yank, "d"), None);
+    assert_eq!(
+        parse(Delete, "gE"),
+        cmd(1, ViMotion::BackwardWordEnd { big: true })
+    );
+    assert_eq!(parse(Delete, "gx"), None);
+    assert_eq!(
+        parse(Delete, "2t,"),
+        cmd(
+            2,
+            ViMotion::Jump(JumpDirection::Forward, JumpPrecision::Till, ',')
+        )
+    );
+    assert_eq!(
+        parse(Change, "i)"),
+        cmd(
+            1,
+            ViMotion::TextObject {
+                inner: true,
+                object: TextObject::Bracket('(', ')')
+            }
+        )
+    );
+    assert_eq!(
+        parse(Yank, "a'"),
+        cmd(
+            1,
+            ViMotion::TextObject {
+                inner: false,
+                object: TextObject::Quote('\'')
+            }
+        )
+    );
+    assert_eq!(
+        parse(Delete, "i|"),
+        cmd(
+            1,
+            ViMotion::TextObject {
+                inner: true,
+                object: TextObject::Delimiter('|')
+            }
+        )
+    );
+    assert_eq!(parse(Delete, "z"), None);
+    assert_eq!(parse(Delete, "99999w").unwrap().count, 9999);
+}
+
+#[test]
+fn test_vi_motion_range() {
+    use ViOperator::*;
+    let check = |operator, text, keys, expected: Option<&str>| {
+        assert_eq!(
+            covered(operator, text, keys).as_deref(),
+            expected,
+            "{keys:?} on {text:?}"
+        );
+    };
+
+    check(Delete, "echo |foo bar", "w", Some("foo "));
+    check(Delete, "echo |foo bar", "2w", Some("foo bar"));
+    check(Change, "echo |foo bar", "w", Some("foo"));
+    check(Delete, "echo |foo\nbar", "w", Some("foo"));
+    check(Delete, "echo |foo/bar baz", "W", Some("foo/bar "));
+    check(Delete, "echo |foo bar", "e", Some("foo"));
+    check(Delete, "echo foo |bar", "b", Some("foo "));
+    check(Delete, "echo foo b|ar", "ge", Some("o ba"));
+    check(Delete, "|echo", "ge", None);
+    check(Delete, "echo |foo bar", "$", Some("foo bar"));
+    check(Delete, "echo |foo bar", "0", Some("echo "));
+    check(Delete, "  echo |foo", "^", Some("echo "));
+    check(Delete, "ec|ho", "2h", Some("ec"));
+    check(Delete, "ec|ho", "5l", Some("ho"));
+    check(Delete, "|", "l", None);
+
+    check(Delete, "a\nb|b\nc", "d", Some("bb\n"));
+    check(Delete, "a\nbb\n|c", "d", Some("\nc"));
+    check(Delete, "a|a\nbb\nc", "2d", Some("aa\nbb\n"));
+    check(Change, "a\nb|b\nc", "c", Some("bb"));
+    check(Yank, "|", "y", Some(""));
+
+    check(Delete, "echo f|oo bar", "iw", Some("foo"));
+    check(Delete, "echo f|oo bar", "aw", Some("foo "));
+    check(Delete, "echo foo b|ar", "aw", Some(" bar"));
+    check(Delete, "echo f|oo bar", "2iw", Some("foo "));
+    check(Delete, "echo a/b|/c d", "iW", Some("a/b/c"));
+    check(Change, "echo \"a |b\" c", "i\"", Some("a b"));
+    check(Change, "echo \"a |b\" c", "a\"", Some("\"a b\" "));
+    check(Change, "echo 'a\\'|b'", "i'", Some("a\\'b"));
+    check(Change, "echo |foo", "i\"", None);
+    check(Delete, "a,b,|c,d", "i,", Some("c"));
+    check(Yank, "a/b|/c", "a/", Some("/b/"));
+    check(Delete, "echo |foo", "i/", None);
+}
+
+#[test]
+fn test_vi_enclosing_bracket() {
+    let text = L!("echo (a (bxc) d)");
+    let find = |pos, count| enclosing_bracket(text, pos, '(', ')', count);
+    assert_eq!(find(10, 1), Some(8));
+    assert_eq!(find(10, 2), Some(5));
+    assert_eq!(find(10, 3), None);
+    // On a closing bracket, its own pair encloses the cursor.
+    assert_eq!(find(12, 1), Some(8));
+    assert_eq!(find(14, 1), Some(5));
+    assert_eq!(find(2, 1), None);
+}
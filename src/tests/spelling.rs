@@ -0,0 +1,64 @@
+use crate::spelling::{command_suggestions, edit_distance};
+use crate::tests::prelude::*;
+use crate::wchar::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn test_edit_distance() {
+    let distance = |a: &str, b: &str| edit_distance(&WString::from_str(a), &WString::from_str(b));
+    assert_eq!(distance("git", "git"), 0);
+    assert_eq!(distance("", "ls"), 2);
+    assert_eq!(distance("ls", ""), 2);
+    // Transpositions are a single edit.
+    assert_eq!(distance("gti", "git"), 1);
+    assert_eq!(distance("sl", "ls"), 1);
+    assert_eq!(distance("pyhton", "python"), 1);
+    assert_eq!(distance("gitt", "git"), 1);
+    assert_eq!(distance("gt", "git"), 1);
+    assert_eq!(distance("gut", "git"), 1);
+    assert_eq!(distance("mkae", "make"), 1);
+    assert_eq!(distance("dokcer", "docker"), 1);
+    assert_eq!(distance("kubeclt", "kubectl"), 1);
+    assert_eq!(distance("ca", "abc"), 3);
+    assert_eq!(distance("grpe", "grep"), 1);
+    assert_eq!(distance("sytemctl", "systemctl"), 1);
+    assert_eq!(distance("systmectl", "systemctl"), 1);
+    assert_eq!(distance("sl", "xyz"), 3);
+}
+
+#[test]
+#[serial]
+fn test_command_suggestions() {
+    let _cleanup = test_init();
+    let dir = "test/spelling_test";
+    std::fs::create_dir_all(dir).unwrap();
+    for name in ["git", "gitk", "ls", "lsblk", "python3", "notexec"] {
+        let path = format!("{}/{}", dir, name);
+        std::fs::write(&path, []).unwrap();
+        let mode = if name == "notexec" { 0o644 } else { 0o755 };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    let mut vars = TestEnvironment::default();
+    vars.vars
+        .insert(L!("PATH").to_owned(), WString::from_str(dir));
+    let suggestions = |cmd: &str| command_suggestions(&WString::from_str(cmd), &vars);
+
+    assert_eq!(suggestions("gti"), vec![L!("git")]);
+    assert_eq!(suggestions("sl"), vec![L!("ls")]);
+    assert_eq!(suggestions("gitl"), vec![L!("gitk"), L!("git")]);
+    assert_eq!(suggestions("pyhton3"), vec![L!("python3")]);
+    assert_eq!(suggestions("notexc"), Vec::<WString>::new());
+    // Builtins are suggested too.
+    assert_eq!(suggestions("sert"), vec![L!("set")]);
+    // Commands, paths and single characters are not corrected.
+    assert_eq!(suggestions("git"), Vec::<WString>::new());
+    assert_eq!(suggestions("./gti"), Vec::<WString>::new());
+    assert_eq!(suggestions("l"), Vec::<WString>::new());
+
+    // New executables are found without restarting.
+    let path = format!("{}/make", dir);
+    std::fs::write(&path, []).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(suggestions("mkae"), vec![L!("make")]);
+}
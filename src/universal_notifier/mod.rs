@@ -1,3 +1,4 @@
+use crate::wchar::prelude::*;
 use once_cell::sync::OnceCell;
 use std::os::fd::RawFd;
 
@@ -68,6 +69,17 @@ pub fn create_notifier() -> Box<dyn UniversalNotifier> {
     Box::new(NullNotifier)
 }
 
+/// Create a notifier that reports writes to the file at the given path, including appends, if the
+/// platform supports it.
+pub fn create_file_watch(path: &wstr) -> Option<Box<dyn UniversalNotifier>> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    if let Some(notifier) = inotify::InotifyNotifier::new_at(path) {
+        return Some(Box::new(notifier));
+    }
+    let _ = path;
+    None
+}
+
 // Default instance. Other instances are possible for testing.
 static DEFAULT_NOTIFIER: OnceCell<Box<dyn UniversalNotifier>> = OnceCell::new();
 
@@ -0,0 +1,7 @@
+#RUN: %fish %s
+
+# Unknown sources are ignored with a warning.
+set -g fish_autosuggestion_sources history histroy completions
+#CHECKERR: warning: Ignoring unknown source in $fish_autosuggestion_sources: histroy
+set -g fish_autosuggestion_sources cwd-history abbreviations
+set -e fish_autosuggestion_sources
@@ -16,6 +16,13 @@ $fish -C 'function fish_command_not_found; echo command-not-found $argv; end' -c
 #CHECKERR: nonexistent-command-abcd foo bar baz
 #CHECKERR: ^~~~~~~~~~~~~~~~~~~~~~~^
 
+$fish -C 'function frobnicate; end' -ic 'frobincate --now'
+#CHECKERR: fish: Unknown command: frobincate
+#CHECKERR: Did you mean: frobnicate?
+#CHECKERR: fish:
+#CHECKERR: frobincate --now
+#CHECKERR: ^~~~~~~~~^
+
 $fish -C 'functions --erase fish_command_not_found' -c 'nonexistent-command apple friday'
 #CHECKERR: fish: Unknown command: nonexistent-command
 #CHECKERR: nonexistent-command apple friday
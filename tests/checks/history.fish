@@ -62,11 +62,97 @@ builtin history --contains save
 #CHECKERR: history: save: subcommand takes no options
 builtin history -t merge
 #CHECKERR: history: merge: subcommand takes no options
+builtin history clear-session --status 0
+#CHECKERR: history: clear-session: subcommand takes no options
+builtin history search --status nope
+#CHECKERR: history: nope: invalid integer
+builtin history search --session=nope
+#CHECKERR: history: nope: invalid session identifier
+builtin history search --regex '(kubectl|helm'
+#CHECKERR: history: Regular expression compile error: missing closing parenthesis
+#CHECKERR: history: (kubectl|helm
+#CHECKERR: history: {{ *}}^
+builtin history save --regex
+#CHECKERR: history: save: subcommand takes no options
+builtin history search --since tomorrow
+#CHECKERR: history: tomorrow: invalid date or duration
+builtin history clear --until 2h
+#CHECKERR: history: clear: subcommand takes no options
+builtin history check --prefix
+#CHECKERR: history: check: subcommand takes no options
+builtin history repair xyz
+#CHECKERR: history: repair: expected 0 arguments; got 1
+builtin history delete --exact --case-sensitive --since 1d foo
+#CHECKERR: builtin history delete does not support --status, --cwd, --session, --since, --until or --show-details
+history delete --status 0 foo
+#CHECKERR: builtin history delete does not support --status, --cwd, --session, --since, --until or --show-details
 
 # Now do a history command that should succeed so we exit with a zero,
 # success, status.
 builtin history save
 
+# Damaged history files are reported and can be repaired.
+set -g fish_history damaged
+mkdir -p $XDG_DATA_HOME/fish
+printf '%s\n' '- cmd: echo ok' '  when: 1700000000' garbage '- cmd: echo fine' '  when: 1700000001' >$XDG_DATA_HOME/fish/damaged_history
+builtin history check
+#CHECK: offset 34: line is not part of an entry
+echo $status
+#CHECK: 1
+builtin history repair
+#CHECK: history: saved the original history file as {{.*}}/damaged_history.{{\d+}}.bak
+builtin history check
+echo $status
+#CHECK: 0
+# A clean file is left alone.
+builtin history repair
+echo $status
+#CHECK: 0
+builtin history search
+#CHECK: echo fine
+#CHECK: echo ok
+builtin history search --since @1700000001
+#CHECK: echo fine
+builtin history search --until @1700000001 echo
+#CHECK: echo ok
+builtin history search --since 1h
+builtin history search --since 2023-11-14 --until "2023-11-14 22:13:21" --reverse
+#CHECK: echo ok
+#CHECK: echo fine
+set -e fish_history
+
+# History can be exported as JSON and imported from other shells.
+set -g fish_history interchange
+printf '%s\n' ': 1700000000:3;git status' ': 1700000100:0;echo multi\\' line 'echo plain' >$XDG_DATA_HOME/fish/zsh_history
+builtin history import --format=zsh $XDG_DATA_HOME/fish/zsh_history
+builtin history search --reverse --show-time='%s '
+#CHECK: 1700000000 git status
+#CHECK: 1700000100 echo multi
+#CHECK: line
+#CHECK: {{\d+}} echo plain
+builtin history export --format=json | string match -v '*echo plain*'
+#CHECK: {"cmd":"git status","when":1700000000,"duration":3000}
+#CHECK: {"cmd":"echo multi\nline","when":1700000100,"duration":0}
+echo '[{"cmd": "echo é", "when": 1600000000, "status": 1}]' | builtin history import --format=json
+builtin history search --status 1
+#CHECK: echo é
+builtin history import --format=yaml
+#CHECKERR: history: yaml: invalid history format
+builtin history export --format=zsh
+#CHECKERR: history: export: only the fish and json formats are supported
+builtin history export --prefix
+#CHECKERR: history: export: subcommand takes no options
+echo '{"cmd": "ok"}{"when": 1}' | builtin history import --format=json
+#CHECKERR: history: -: invalid history entry at offset 13
+builtin history import /no/such/file
+#CHECKERR: history: /no/such/file: No such file or directory (os error 2)
+builtin history clear
+set -e fish_history
+
 set -g fish_private_mode 1
 builtin history merge
 #CHECKERR: history: can't merge history in private mode
+builtin history repair
+#CHECKERR: history: can't repair history in private mode
+builtin history import --format=json </dev/null
+#CHECKERR: history: can't import history in private mode
@@ -0,0 +1,36 @@
+#RUN: fish=%fish %fish %s
+#REQUIRES: command -v tic
+# Terminals that advertise them in terminfo get styled and colored underlines.
+
+set -l terminfo (mktemp -d)
+# Like kitty's entry, this has a stray "%;" in Setulc.
+printf '%s\n' \
+    'xterm-ext|xterm with styled and colored underlines,' \
+    '	Smulx=\E[4:%p1%dm, Setulc=\E[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%d%;m,' \
+    '	use=xterm-256color,' >$terminfo/xterm-ext.src
+tic -x -o $terminfo $terminfo/xterm-ext.src
+
+function ext_fish -V terminfo
+    TERMINFO=$terminfo TERM=xterm-ext fish_term24bit=1 $fish $argv
+end
+
+ext_fish -c 'string escape -- (set_color --underline=curly)'
+# CHECK: \e\[4:3m
+ext_fish -c 'string escape -- (set_color --underline=single)'
+# CHECK: \e\[4m
+ext_fish -c 'string escape -- (set_color --underline-color red)'
+# CHECK: \e\[58:5:1m
+ext_fish -c 'string escape -- (set_color --underline-color ff0080)'
+# CHECK: \e\[58:2::255:0:128m
+ext_fish -c 'string escape -- (set_color --underline-color normal)'
+# CHECK: \e\[59m
+
+# The same styles work in $fish_color_* variables.
+ext_fish -c '
+    set fish_color_command red --underline=dashed --underline-color=brblue --strikethrough
+    set fish_color_param green
+    echo "echo foo" | fish_indent --ansi | string escape
+' | head -n1
+# CHECK: \e\[31m\e\[4:5m\e\[9m\e\[58:5:12mecho\ \e\[32m\e\[24m\e\[29m\e\[59mfoo
+
+rm -r $terminfo
@@ -0,0 +1,19 @@
+# RUN: %fish %s
+# Tests for the text styles of `set_color`.
+
+# The test driver uses TERM=xterm, which has neither styled nor colored underlines,
+# so those fall back to a single underline and nothing, respectively.
+string escape -- (set_color --underline=curly)
+# CHECK: \e\[4m
+string escape -- (set_color --underline-color red)
+string escape -- (set_color --strikethrough)
+# CHECK: \e\[9m
+string escape -- (set_color -s red)
+# CHECK: \e\[9m\e\[31m
+string escape -- (set_color --blink)
+# CHECK: \e\[5m
+
+set_color --underline=wavy
+# CHECKERR: set_color: Unknown underline style 'wavy'
+set_color --underline-color mooganta
+# CHECKERR: set_color: Unknown color 'mooganta'
@@ -0,0 +1,45 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    set -g fish_async_prompt 1
+    function fish_prompt
+        set -l last_pipestatus $pipestatus
+        if set -q slow_prompt
+            sleep 3
+        end
+        printf "prompt <%s> <status=%s> " "$slow_prompt" "$last_pipestatus"
+    end
+    function fish_right_prompt
+        echo right
+    end
+    bind ctrl-g repaint
+    bind ctrl-t "commandline -i !; false"
+'
+
+isolated-tmux-start
+tmux-sleep
+
+isolated-tmux capture-pane -p
+# CHECK: prompt <> <status=0> {{ +}}right
+
+# The previous prompt is shown right away, and the slow prompt runs once fish waits for input.
+isolated-tmux send-keys 'set -g slow_prompt 1; false | true' Enter
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt <> <status=0> set -g slow_prompt 1; false | true {{ +}}right
+# CHECK: prompt <> <status=0> {{ +}}right
+
+# Keys typed while the prompt runs are handled afterwards. The prompt sees the status of the
+# command, not of the binding.
+isolated-tmux send-keys 'echo typed' C-t
+sleep 4
+isolated-tmux capture-pane -p
+# CHECK: prompt <> <status=0> set -g slow_prompt 1; false | true {{ +}}right
+# CHECK: prompt <1> <status=1 0> echo typed! {{ +}}right
+
+# A plain repaint shows the cached prompt without running the prompt functions again.
+isolated-tmux send-keys C-g
+tmux-sleep
+isolated-tmux capture-pane -p | tail -n 1
+# CHECK: prompt <1> <status=1 0> echo typed! {{ +}}right
@@ -0,0 +1,43 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    set -g fish_autopair 1
+    set -g fish_autosuggestion_enabled 0
+'
+
+isolated-tmux-start
+
+# Closers are inserted, and typing them steps over them.
+isolated-tmux send-keys 'echo (echo "hi'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo (echo "hi")
+isolated-tmux send-keys '")' Enter
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo (echo "hi")
+# CHECK: hi
+# CHECK: prompt 1>
+
+# Deleting the opening character deletes an empty pair.
+isolated-tmux send-keys C-l 'echo [' BSpace x
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo x
+
+# Undo removes the pair at once.
+isolated-tmux send-keys C-u 'echo {' C-z
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo
+
+# Nothing is paired after a backslash, in comments or in front of a word.
+isolated-tmux send-keys C-u 'echo \( # ('
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo \( # (
+isolated-tmux send-keys C-u 'echo foo' C-a '('
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> (echo foo
@@ -0,0 +1,37 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    function foo; end
+    complete -c foo -f -a "start stop-everything"
+    abbr -a --position anywhere qqabbreviation expanded
+'
+
+isolated-tmux-start
+
+# Completions are suggested without history.
+isolated-tmux send-keys 'foo st'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> foo start
+
+isolated-tmux send-keys C-u 'foo stop-everything' Enter C-l 'foo st'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> foo stop-everything
+
+# The order of the sources is their ranking.
+isolated-tmux send-keys C-u 'set -g fish_autosuggestion_sources completions history' Enter C-l 'foo st'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt {{\d+}}> foo start
+
+isolated-tmux send-keys C-u 'echo qqab'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt {{\d+}}> echo qqab
+
+isolated-tmux send-keys C-u 'set -g fish_autosuggestion_sources abbreviations' Enter C-l 'echo qqab'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt {{\d+}}> echo qqabbreviation
@@ -0,0 +1,42 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    set -g fish_terminal_clipboard copy paste
+    bind ctrl-g begin-selection
+'
+
+isolated-tmux-start
+isolated-tmux set-option -g set-clipboard on
+
+# Killed text is copied to the clipboard.
+isolated-tmux send-keys 'echo hello world' C-w
+tmux-sleep
+isolated-tmux show-buffer
+echo
+# CHECK: world
+
+# Yanking inserts the clipboard, and yank-pop goes back to older kills.
+isolated-tmux set-buffer 'from tmux'
+isolated-tmux send-keys C-y
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo hello from tmux
+isolated-tmux send-keys M-y
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo hello world
+
+# The selection is copied as it changes.
+isolated-tmux send-keys C-g M-b M-b
+tmux-sleep
+isolated-tmux show-buffer
+echo
+# CHECK: hello world
+
+# Without an answer from the terminal, yanking uses the kill ring.
+isolated-tmux set-option -g set-clipboard off
+isolated-tmux send-keys C-e C-u 'echo ' C-y
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo echo hello world
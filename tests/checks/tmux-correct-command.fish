@@ -0,0 +1,37 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    function frobnicate; echo frobnicated $argv; end
+    set -g fish_autosuggestion_enabled 0
+'
+
+isolated-tmux-start
+
+# A misspelled command is replaced with the closest command.
+isolated-tmux send-keys 'frobincate --now' M-m
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> frobnicate --now
+isolated-tmux send-keys Enter
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> frobnicate --now
+# CHECK: frobnicated --now
+# CHECK: prompt 1>
+
+# The command before the cursor is corrected, and undo restores it.
+isolated-tmux send-keys C-l 'echo hi | frobnicaet' M-m
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo hi | frobnicate
+isolated-tmux send-keys C-z
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo hi | frobnicaet
+
+# Commands and arguments are left alone.
+isolated-tmux send-keys C-u 'echo frobincate' M-m
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1> echo frobincate
@@ -147,3 +147,37 @@ isolated-tmux send-keys Escape
 tmux-sleep
 isolated-tmux capture-pane -p
 # CHECK: prompt 50>
+
+# Toggle regex search.
+isolated-tmux send-keys C-r '^true [0-9]$' M-r
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 50> true 2
+# CHECK: search: ^true [0-9]$
+# CHECK: ► true 2  ► true 4  ► true 6  ► true 8
+# CHECK: Regex search
+
+isolated-tmux send-keys M-r
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 50>
+# CHECK: search: ^true [0-9]$
+# CHECK: (no matches)
+
+# Fuzzy search ranks better matches first.
+isolated-tmux send-keys Escape
+tmux-sleep
+isolated-tmux send-keys C-r M-z t5!
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 50> true 5!
+# CHECK: search: t5!
+# CHECK: ► true 5!  ► true 15!
+# CHECK: Fuzzy search
+
+isolated-tmux send-keys M-z
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 50> true 5!
+# CHECK: search: t5!
+# CHECK: ► true 5!  ► true 15!
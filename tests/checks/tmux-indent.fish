@@ -0,0 +1,42 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+isolated-tmux-start
+
+# New lines in an unclosed block are indented.
+isolated-tmux send-keys 'if true' Enter 'for i in 1' Enter 'echo loop'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> if true
+# CHECK:               for i in 1
+# CHECK:                   echo loop
+
+# Typing "end" or "else" moves the line out right away.
+# Note that "end" alone would be the name of a key.
+isolated-tmux send-keys Enter e n d
+tmux-sleep
+isolated-tmux send-keys Enter else
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> if true
+# CHECK:               for i in 1
+# CHECK:                   echo loop
+# CHECK:               end
+# CHECK:           else
+
+isolated-tmux send-keys Enter e n d Enter C-l
+isolated-tmux send-keys 'switch x' Enter 'case a' Enter 'echo a' Enter case
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt {{\d+}}> switch x
+# CHECK:               case a
+# CHECK:                   echo a
+# CHECK:               case
+
+# The indentation is not part of the command line.
+isolated-tmux send-keys ' b' Enter e n d Enter C-l
+isolated-tmux send-keys 'begin' Enter 'echo block' Enter e n d Enter C-l
+isolated-tmux send-keys 'string escape -- $history[1]' Enter
+tmux-sleep
+isolated-tmux capture-pane -p | string match -r '^begin.*'
+# CHECK: begin\necho\ block\nend
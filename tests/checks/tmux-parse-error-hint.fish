@@ -0,0 +1,47 @@
+#RUN: %fish %s
+#REQUIRES: command -v tmux
+
+set -g isolated_tmux_fish_extra_args -C '
+    set -g fish_parse_error_hint 1
+'
+
+isolated-tmux-start
+
+isolated-tmux send-keys 'echo "foo'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo "foo
+# CHECK: Unexpected end of string, quotes are not balanced
+
+isolated-tmux send-keys '"'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo "foo"
+
+isolated-tmux send-keys C-u 'if true'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> if true
+# CHECK: Missing end to balance this if statement
+
+isolated-tmux send-keys C-u 'echo $?'
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> echo $?
+# CHECK: $? is not the exit status. In fish, please use $status.
+
+# The hint goes away when the command runs.
+isolated-tmux send-keys C-u 'if true; echo ok; end' Enter
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 0> if true; echo ok; end
+# CHECK: ok
+# CHECK: prompt 1>
+
+# Nor is it left behind when the command line is cancelled.
+isolated-tmux send-keys C-l 'echo "foo'
+tmux-sleep
+isolated-tmux send-keys C-c
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: prompt 1>
@@ -21,3 +21,27 @@ isolated-tmux capture-pane -p
 # CHECK: > echo foo
 # CHECK: foo
 # CHECK: > full prompt >
+
+# With a fish_transient_prompt function, the reader replaces both prompts itself.
+isolated-tmux send-keys C-u '
+    function fish_prompt
+        printf "prompt-line-1\nprompt-line-2> "
+    end
+    function fish_right_prompt
+        printf "right"
+    end
+    function fish_transient_prompt
+        printf "\$ "
+    end
+    bind enter execute
+' Enter C-l
+isolated-tmux send-keys 'echo bar' Enter
+isolated-tmux send-keys 'echo baz' Enter
+tmux-sleep
+isolated-tmux capture-pane -p
+# CHECK: $ echo bar
+# CHECK: bar
+# CHECK: $ echo baz
+# CHECK: baz
+# CHECK: prompt-line-1
+# CHECK: prompt-line-2> {{ +}}right
@@ -140,3 +140,16 @@ chmod +x ./test
 
 PATH=.:$PATH type -P test
 # CHECK: ./test
+
+function frobnicate
+end
+function frobnicates
+end
+PATH= type --suggest frobincate
+# CHECK: frobnicate
+# CHECK: frobnicates
+PATH= type --suggest frobnicate
+echo $status
+# CHECK: 1
+type --suggest -q frobincate
+# CHECKERR: type: invalid option combination
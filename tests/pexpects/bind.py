@@ -258,6 +258,46 @@ sleep(0.200)
 send("0$i34\r")
 expect_prompt("echo 12345")
 
+# Test the operators with motions, counts and text objects.
+send("echo foo bar baz")
+send("\033")
+sleep(0.200)
+send("0wdw\r")
+expect_prompt(TO_END + "bar baz\r\n", unmatched="vi delete word")
+
+send("echo a,b,c,d")
+send("\033")
+sleep(0.200)
+send("0fad2f,\r")
+expect_prompt(TO_END + "c,d\r\n", unmatched="vi delete with counted jump")
+
+send("echo one two three four")
+send("\033")
+sleep(0.200)
+send("0w2dw\r")
+expect_prompt(TO_END + "three four\r\n", unmatched="vi count before operator")
+
+send("echo abcdefghijkl")
+send("\033")
+sleep(0.200)
+send("0w10x\r")
+expect_prompt(TO_END + "kl\r\n", unmatched="vi count with a zero")
+
+send("echo 'one two' three")
+send("\033")
+sleep(0.200)
+send("0f'lci'x\r")
+expect_prompt(TO_END + "x three\r\n", unmatched="vi change inside quotes")
+
+send("echo foo")
+send("\033")
+sleep(0.200)
+send("yiwA ")
+send("\033")
+sleep(0.200)
+send("p\r")
+expect_prompt(TO_END + "foo foo\r\n", unmatched="vi yank inner word")
+
 # Now test that exactly the expected bind modes are defined
 sendline("bind --list-modes")
 expect_prompt(
@@ -312,6 +352,21 @@ send("echo git@github.com:fish-shell/fish-shell")
 send("\x17\x17\x17\r")
 expect_prompt("git@", unmatched="ctrl-w does not stop at @")
 
+# A numeric argument repeats the next command.
+send("echo git@github.com:fish-shell/fish-shell")
+send("\0333\x17\r")
+expect_prompt("git@", unmatched="alt-3 ctrl-w does not kill three path components")
+
+send("echo abcdef")
+send("\x01\0331\0330\x06")
+send("\0332x\r")
+expect_prompt("abcdexxf", unmatched="alt-1 alt-0 does not accumulate")
+
+# One undo reverts all of the repetitions.
+send("echo abcdef")
+send("\0333\x7f\x1a\r")
+expect_prompt("abcdef", unmatched="undo does not revert the repeated command")
+
 sendline("abbr --add foo 'echo foonanana'")
 expect_prompt()
 sendline("bind ' ' expand-abbr or self-insert")
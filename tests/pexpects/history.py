@@ -144,6 +144,29 @@ expect_re("echo TERM")
 sendline("")
 expect_prompt("TERM")
 
+# History search starts with the command run closest to the current directory
+sendline("set -g dir (mktemp -d); mkdir $dir/sub; cd $dir")
+expect_prompt()
+sendline("echo marker-dir | string upper")
+expect_prompt("MARKER-DIR")
+sendline("cd /")
+expect_prompt()
+sendline("echo marker-root | string upper")
+expect_prompt("MARKER-ROOT")
+sendline("cd $dir/sub")
+expect_prompt()
+send("marker\x1b[A")  # up-arrow
+expect_re("echo marker-dir")
+sendline("")
+expect_prompt("MARKER-DIR")
+# The others follow by recency.
+send("marker\x1b[A\x1b[A")
+expect_re("echo marker-root")
+sendline("")
+expect_prompt("MARKER-ROOT")
+sendline("cd /; rm -r $dir")
+expect_prompt()
+
 # Check that leading space makes an ephemeral item
 sendline(" echo ephemeral")
 expect_prompt("ephemeral")
@@ -203,3 +226,21 @@ sendline("true")
 expect_prompt()
 sendline("echo a; history search '*spaced*' | cat; echo b")
 expect_prompt("a\r\n.* echo spaced\r\nb\r\n")
+
+# Commands matching fish_history_secret_patterns are available in this session,
+# but not written to the history file.
+sendline("functions -e fish_should_add_to_history; set -g fish_history_secret_patterns 'Authorization: \\S+'")
+expect_prompt()
+sendline("echo 'Authorization: hunter2' >/dev/null")
+expect_prompt()
+sendline("echo a; history search hunter2 | cat; echo b")
+expect_prompt(r"a\r\necho 'Authorization: hunter2' >/dev/null\r\nb\r\n")
+sendline("history save; string match -q -- '*Authori''zation: hunter2*' <$XDG_DATA_HOME/fish/fish_history; echo found $status")
+expect_prompt("found 1")
+# The same goes for commands added with "history append".
+sendline("history append 'Authorization: swordfish'")
+expect_prompt()
+sendline("echo a; history search --exact --case-sensitive 'Authorization: swordfish' | cat; echo b")
+expect_prompt(r"a\r\nAuthorization: swordfish\r\nb\r\n")
+sendline("history save; string match -q -- '*sword''fish*' <$XDG_DATA_HOME/fish/fish_history; echo found $status")
+expect_prompt("found 1")
@@ -0,0 +1,58 @@
+#!/usr/bin/env python3
+from pexpect_helper import SpawnedProc
+
+sp = SpawnedProc()
+send, sendline, sleep, expect_prompt, expect_re, expect_str = (
+    sp.send,
+    sp.sendline,
+    sp.sleep,
+    sp.expect_prompt,
+    sp.expect_re,
+    sp.expect_str,
+)
+expect_prompt()
+
+sendline("set -g fish_notify_threshold 0")
+expect_prompt()
+
+# Until the terminal reports focus, it may not be able to, so the notification is sent right away.
+sendline("true")
+expect_str("\x1b]9;true finished with status 0\x07")
+expect_prompt()
+
+# Once we know the terminal is not focused, the notification is sent right away.
+send("\x1b[O")
+sendline("false")
+expect_str("\x1b]9;false finished with status 1\x07")
+expect_prompt()
+
+# If the terminal seems focused, the notification waits for it to report losing focus.
+send("\x1b[I")
+sendline("true")
+expect_prompt()
+expect_str("\x1b]9;", timeout=0.5, shouldfail=True)
+send("\x1b[O")
+expect_str("\x1b]9;true finished with status 0\x07")
+
+# Nothing is sent if the terminal turns out to be focused, or the user starts typing.
+send("\x1b[I")
+sendline("true")
+expect_prompt()
+send("\x1b[I")
+send("\x1b[O")
+expect_str("\x1b]9;", timeout=0.5, shouldfail=True)
+send("\x1b[I")
+sendline("true")
+expect_prompt()
+send("echo")
+send("\x1b[O")
+expect_str("\x1b]9;", timeout=0.5, shouldfail=True)
+send("\x03")
+expect_prompt(increment=False)
+
+# Commands that finish faster than the threshold are not reported.
+sendline("set -g fish_notify_threshold 100000")
+expect_prompt()
+sendline("false")
+expect_prompt()
+expect_str("\x1b]9;", timeout=0.5, shouldfail=True)
@@ -0,0 +1,54 @@
+#!/usr/bin/env python3
+from pexpect_helper import SpawnedProc
+import os
+
+# The test driver uses TERM=dumb, which makes fish not repaint in place.
+env = os.environ.copy()
+env["TERM"] = "xterm"
+
+sp = SpawnedProc(env=env)
+send, sendline, sleep, expect_prompt, expect_re, expect_str = (
+    sp.send,
+    sp.sendline,
+    sp.sleep,
+    sp.expect_prompt,
+    sp.expect_re,
+    sp.expect_str,
+)
+
+# The prompt is marked from its start to where the command line begins.
+expect_str("\x1b]133;A;click_events=1\x07")
+expect_prompt()
+expect_str("\x1b]133;B\x07")
+
+# Command output is marked, and the status is reported when the command finishes.
+sendline("false")
+expect_str("\x1b]133;C\x07")
+expect_str("\x1b]133;D;1\x07")
+expect_prompt()
+
+sendline("echo hello")
+expect_str("\x1b]133;C\x07")
+expect_str("hello")
+expect_str("\x1b]133;D;0\x07")
+expect_prompt()
+
+# An empty command line finishes without a status.
+sendline("")
+expect_str("\x1b]133;D\x07")
+expect_prompt()
+
+# Continuation lines of a multi-line command line are marked as secondary prompts.
+sendline("begin")
+expect_str("\x1b]133;A;k=s\x07\x1b]133;B\x07")
+sendline("echo inside")
+sendline("end")
+expect_str("\x1b]133;C\x07")
+expect_str("inside")
+expect_str("\x1b]133;D;0\x07")
+expect_prompt()
+
+# The right prompt is marked separately.
+sendline("function fish_right_prompt; echo right; end")
+expect_prompt()
+expect_str("\x1b]133;P;k=r\x07right\x1b]133;B\x07")
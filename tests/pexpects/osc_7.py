@@ -0,0 +1,30 @@
+#!/usr/bin/env python3
+from pexpect_helper import SpawnedProc
+import os
+import tempfile
+
+sp = SpawnedProc()
+send, sendline, sleep, expect_prompt, expect_re, expect_str = (
+    sp.send,
+    sp.sendline,
+    sp.sleep,
+    sp.expect_prompt,
+    sp.expect_re,
+    sp.expect_str,
+)
+
+# The working directory is reported at startup, in case it was inherited.
+expect_str("\x1b]7;file://")
+expect_prompt()
+
+# Changing directories reports the new one, percent-encoded.
+dir = tempfile.mkdtemp(prefix="osc 7 ")
+sendline("cd " + "'" + dir + "'")
+expect_re(r"\x1b\]7;file://[^/\x07]*" + dir.replace(" ", "%20") + "\x07")
+expect_prompt()
+
+# Non-interactive shells don't report anything.
+sendline("set -l fish (status fish-path); $fish -c 'cd /' | count")
+expect_prompt("0\r\n")
+
+os.rmdir(dir)
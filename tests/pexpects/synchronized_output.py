@@ -0,0 +1,31 @@
+#!/usr/bin/env python3
+from pexpect_helper import SpawnedProc
+import os
+
+# The test driver uses TERM=dumb, which makes fish not repaint in place.
+env = os.environ.copy()
+env["TERM"] = "xterm"
+
+sp = SpawnedProc(env=env)
+send, sendline, sleep, expect_prompt, expect_re, expect_str = (
+    sp.send,
+    sp.sendline,
+    sp.sleep,
+    sp.expect_prompt,
+    sp.expect_re,
+    sp.expect_str,
+)
+
+expect_prompt()
+
+# Repaints are not wrapped in a synchronized update unless the terminal supports it.
+send("echo foo")
+expect_str("\x1b[?2026h", timeout=1, shouldfail=True)
+
+# Reply to the DECRQM query for synchronized output that fish sends at startup.
+send("\x1b[?2026;2$y")
+send(" bar")
+expect_str("\x1b[?2026h")
+expect_str("\x1b[?2026l")
+sendline("")
+expect_prompt("foo bar")